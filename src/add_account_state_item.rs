@@ -1,37 +1,43 @@
-use crate::database_context::DatabaseManager;
+use crate::database_context::{Account, DatabaseManager, EMAIL_FIELD_KEY, USERNAME_FIELD_KEY};
 use crate::state_item::{wait_for_seconds, StateItem};
-use crate::terminal_context::{TerminalContext};
+use crate::terminal_context::{StyleAttribute, TerminalContext};
 use crate::transition::Transition;
 use crossterm::event::KeyCode;
 use std::sync::{Arc, Mutex};
-use rand::distributions::Alphanumeric;
-use rand::Rng;
-use rand::seq::SliceRandom;
+use crate::password_generator::{generate_password, DEFAULT_PASSWORD_LENGTH};
+use crate::entry_templates::{get_templates, EntryTemplate, FieldKind, ValidationError};
+use crate::entry_view::EntryView;
+use crate::file_accesssor::{expand_user_path, read_file_from_disk};
+use crate::ssh_key::{SshKey, PUBLIC_KEY_FIELD_KEY};
 use crate::input_handler::*;
 use crate::password_widget::PasswordWidget;
 use crate::texts::get_texts;
-use crate::widget::Widget;
 
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Clone, Copy)]
 enum AddAccountState {
-	SetAccount,
+	SelectType,
 	AccountExists,
-	AddEmailRequest,
-	EnterEmail,
+	AddFieldRequest,
+	EnterField,
 	GeneratePasswordRequest,
-	EnterPassword,
-	PasswordGenerated,
-	PasswordSet,
+	GenerateSshKeyRequest,
+	AccountSaved,
 	Cancel,
 }
 
 pub struct AddEntryStateItem {
 	switch_state: Arc<Mutex<bool>>,
 	internal_state: AddAccountState,
-	account_name: String,
-	email_name: String,
-	password_buffer: String,
+	state_before_cancel: AddAccountState,
+	selected_template: usize,
+	account: Account,
+	entry_view: Option<EntryView>,
+	field_idx: usize,
+	input_buffer: String,
+	validation_error: Option<ValidationError>,
 	password_widget: PasswordWidget,
+	existing_account: Option<Account>,
+	next_state: Option<Transition>,
 	db_manager: Arc<Mutex<DatabaseManager>>,
 }
 
@@ -39,126 +45,334 @@ impl AddEntryStateItem {
 	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>) -> Self {
 		AddEntryStateItem {
 			switch_state: Arc::new(Mutex::new(false)),
-			internal_state: AddAccountState::SetAccount,
-			account_name: String::new(),
-			email_name: String::new(),
-			password_buffer: String::new(),
+			internal_state: AddAccountState::SelectType,
+			state_before_cancel: AddAccountState::SelectType,
+			selected_template: 0,
+			account: Account::new(""),
+			entry_view: None,
+			field_idx: 0,
+			input_buffer: String::new(),
+			validation_error: None,
 			password_widget: PasswordWidget::new(String::new()),
+			existing_account: None,
+			next_state: None,
 			db_manager,
 		}
 	}
 
-	fn show_account_data(&self, show_account: bool, show_email: bool, show_password: bool, context: &mut TerminalContext) {
+	fn get_template(&self) -> &'static EntryTemplate {
+		&get_templates()[self.selected_template]
+	}
+
+	fn show_template_selection(&self, context: &mut TerminalContext) {
 		context.print_at_position(0, 0, &get_texts().add_account.heading);
-		context.print_at_position(0, 2, &get_texts().account.account_name);
-		if show_account {
-			context.print_at_position(0, 3, self.account_name.as_str());
+		context.print_at_position(0, 2, &get_texts().add_account.select_type);
+		for (idx, template) in get_templates().iter().enumerate() {
+			if idx == self.selected_template {
+				context.print_styled_at_position(0, 4 + idx as u16, &template.name, StyleAttribute::InverseColor);
+			} else {
+				context.print_at_position(0, 4 + idx as u16, &template.name);
+			}
 		}
-		context.print_at_position(0, 5, &get_texts().account.email);
-		if show_email {
-			context.print_at_position(0, 6, self.email_name.as_str());
+		let content = vec![&get_texts().input.down_arrow, &get_texts().input.up_arrow, &get_texts().input.enter, &get_texts().input.escape];
+		context.draw_control_footer(content);
+	}
+
+	fn select_template_input(&mut self, key_code: KeyCode) {
+		let template_count = get_templates().len();
+		match key_code {
+			KeyCode::Up => {
+				self.selected_template = if self.selected_template == 0 { template_count - 1 } else { self.selected_template - 1 };
+			}
+			KeyCode::Down => {
+				self.selected_template = (self.selected_template + 1) % template_count;
+			}
+			KeyCode::Enter => {
+				self.account = Account::new(&self.get_template().key);
+				self.account.rotation_days = self.get_template().rotation_days;
+				self.entry_view = Some(EntryView::new(&self.account));
+				self.field_idx = 0;
+				self.start_field_input();
+			}
+			_ => {}
 		}
-		context.print_at_position(0, 8, &get_texts().account.password);
-		if show_password {
-			self.password_widget.display(context, 0, 9);
+	}
+
+	fn show_account_data(&self, context: &mut TerminalContext, highlight_field: bool) {
+		let heading = format!("{} - {}", &get_texts().add_account.heading, self.get_template().name);
+		context.print_at_position(0, 0, &heading);
+		if let Some(entry_view) = &self.entry_view {
+			let highlighted = if highlight_field { Some(self.field_idx) } else { None };
+			entry_view.display(context, &self.account, 2, highlighted);
 		}
 	}
 
-	fn finalize_account_creation(&mut self) {
-		self.write_to_database();
-		self.switch_to_main_menu_state(2);
+	fn get_current_field_label(&self) -> String {
+		match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows()[self.field_idx].label.clone(),
+			None => String::new(),
+		}
 	}
 
-	fn switch_to_main_menu_state(&mut self, duration: u64) {
-		wait_for_seconds(duration, Arc::clone(&self.switch_state))
+	fn get_current_field_name(&self) -> String {
+		match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows()[self.field_idx].get_name().to_string(),
+			None => String::new(),
+		}
 	}
 
-	fn write_to_database(&self) {
-		let database_manager = self.db_manager.lock().unwrap();
-		let db_context = match database_manager.get_database_context() {
-			Some(context) => context,
+	fn is_current_field_hidden(&self) -> bool {
+		match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows()[self.field_idx].is_hidden(),
+			None => false,
+		}
+	}
+
+	fn get_current_field_kind(&self) -> FieldKind {
+		match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows()[self.field_idx].kind,
+			None => FieldKind::Text,
+		}
+	}
+
+	fn start_field_input(&mut self) {
+		let entry_view = match &self.entry_view {
+			Some(entry_view) => entry_view,
 			None => return,
 		};
 
-		let email = if self.email_name.len() == 0 {
-			None
+		let field = &entry_view.get_rows()[self.field_idx];
+		// Values derived from other fields, like the public key of an SSH key, are not asked for
+		if !self.account.get_field(&field.key).unwrap_or_default().is_empty() {
+			self.next_field();
+			return;
+		}
+
+		self.input_buffer.clear();
+		self.password_widget.update_password(String::new());
+		self.validation_error = None;
+		self.internal_state = if !field.required {
+			AddAccountState::AddFieldRequest
 		} else {
-			Some(self.email_name.clone())
+			self.get_value_state(field.kind)
 		};
+	}
 
-		db_context.add_account(&self.account_name, &self.password_buffer, email).unwrap();
-		database_manager.safe_database();
+	fn get_value_state(&self, kind: FieldKind) -> AddAccountState {
+		match kind {
+			FieldKind::Secret => AddAccountState::GeneratePasswordRequest,
+			FieldKind::SshKey => AddAccountState::GenerateSshKeyRequest,
+			_ => AddAccountState::EnterField,
+		}
 	}
 
-	fn generate_password(&mut self) {
-		const SPECIAL_CHARS: &[u8] = b"!@#$%^&*()_+-=[]{}|;:,.<>?";
-		let length = 30;
-		let mut rng = rand::thread_rng();
+	fn commit_field_value(&mut self, value: String) {
+		let key = match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows()[self.field_idx].key.clone(),
+			None => return,
+		};
 
-		let mut password: Vec<char> = (0..length)
-			.map(|_| rng.sample(Alphanumeric) as char)
-			.collect();
+		if let Some(entry_view) = &mut self.entry_view {
+			entry_view.update_value(self.field_idx, &value);
+		}
+		self.account.set_field(&key, value);
+		self.next_field();
+	}
 
-		for _ in 0..(length / 4) {
-			let idx = rng.gen_range(0..length);
-			let special_char = *SPECIAL_CHARS.choose(&mut rng).unwrap() as char;
-			password[idx] = special_char;
+	fn commit_ssh_key(&mut self, ssh_key: SshKey) {
+		let public_key = ssh_key.get_public_key_line();
+		if let Some(entry_view) = &mut self.entry_view {
+			if let Some(row_idx) = entry_view.get_rows().iter().position(|row| row.key == PUBLIC_KEY_FIELD_KEY) {
+				entry_view.update_value(row_idx, &public_key);
+			}
 		}
+		self.account.set_field(PUBLIC_KEY_FIELD_KEY, public_key);
+		self.commit_field_value(ssh_key.to_stored());
+	}
 
+	/// For SSH keys the input is the path of the key file to import.
+	fn import_ssh_key(&mut self) {
+		let path = expand_user_path(&self.input_buffer);
+		let pem = match read_file_from_disk(&path).map(|data| String::from_utf8_lossy(&data).to_string()) {
+			Ok(pem) => pem,
+			Err(_) => {
+				self.validation_error = Some(ValidationError::UnreadableFile);
+				return;
+			}
+		};
 
-		self.password_buffer = password.into_iter().collect();
+		match SshKey::from_openssh_pem(&pem) {
+			Ok(ssh_key) => self.commit_ssh_key(ssh_key),
+			Err(e) => self.validation_error = Some(e.into()),
+		}
+	}
+
+	/// The row after which the service and its user are known, so duplicates can be detected.
+	/// The username falls back to the email, so this is the last of both rows.
+	fn get_identity_row_idx(&self) -> usize {
+		let rows = match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows(),
+			None => return 0,
+		};
+		rows.iter().rposition(|row| row.key == USERNAME_FIELD_KEY || row.key == EMAIL_FIELD_KEY).unwrap_or(0)
 	}
 
-	fn check_if_account_already_exists(&self) -> bool {
+	fn next_field(&mut self) {
+		if self.field_idx == self.get_identity_row_idx() {
+			self.existing_account = self.find_existing_account();
+			if self.existing_account.is_some() {
+				self.internal_state = AddAccountState::AccountExists;
+				return;
+			}
+		}
+		self.advance_field();
+	}
+
+	fn advance_field(&mut self) {
+		let field_count = match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows().len(),
+			None => 0,
+		};
+
+		self.field_idx += 1;
+		if self.field_idx >= field_count {
+			self.finalize_account_creation();
+		} else {
+			self.start_field_input();
+		}
+	}
+
+	fn field_input(&mut self, key_code: KeyCode) {
+		if !get_text_input(key_code, &mut self.input_buffer) {
+			self.password_widget.update_password(self.input_buffer.clone());
+			return;
+		}
+
+		if self.get_current_field_kind() == FieldKind::SshKey {
+			self.import_ssh_key();
+			return;
+		}
+
+		let validation = match &self.entry_view {
+			Some(entry_view) => entry_view.get_rows()[self.field_idx].validate(&self.input_buffer),
+			None => Ok(()),
+		};
+
+		match validation {
+			Ok(()) => self.commit_field_value(self.input_buffer.clone()),
+			Err(e) => self.validation_error = Some(e),
+		}
+	}
+
+	fn show_field_input(&self, context: &mut TerminalContext) {
+		let is_ssh_key = self.get_current_field_kind() == FieldKind::SshKey;
+		let mut heading = if is_ssh_key { get_texts().add_account.import_ssh_key_prompt.clone() } else { self.get_current_field_label() };
+		if let Some(e) = &self.validation_error {
+			heading = format!("{} {}", heading, e.get_message());
+		}
+
+		let content = if self.is_current_field_hidden() && !is_ssh_key {
+			self.password_widget.get_password_to_display().cloned().unwrap_or_default()
+		} else {
+			self.input_buffer.clone()
+		};
+		context.draw_input_footer(&heading, content);
+	}
+
+	fn finalize_account_creation(&mut self) {
+		self.write_to_database();
+		self.internal_state = AddAccountState::AccountSaved;
+		self.switch_to_main_menu_state(2);
+	}
+
+	fn switch_to_main_menu_state(&mut self, duration: u64) {
+		wait_for_seconds(duration, Arc::clone(&self.switch_state))
+	}
+
+	fn write_to_database(&self) {
+		let database_manager = self.db_manager.lock().unwrap();
+		let db_context = match database_manager.get_database_context() {
+			Some(context) => context,
+			None => return,
+		};
+
+		db_context.add_account(&self.account).unwrap();
+		database_manager.safe_database();
+	}
+
+	fn find_existing_account(&self) -> Option<Account> {
 		let database_manager = self.db_manager.lock().unwrap();
 		let db_context = match database_manager.get_database_context() {
 			Some(context) => context,
 			None => panic!("Could not access database!"),
 		};
-		let results = match db_context.search_accounts_by_name(&self.account_name) {
-			Ok(accounts) => accounts.len(),
+		match db_context.find_accounts_by_identity(&self.account.account_name, self.account.get_username()) {
+			Ok(accounts) => accounts.into_iter().next(),
 			Err(e) => { panic!("Could not read accounts from database! {}", e.to_string()); }
+		}
+	}
+
+	fn show_account_exists(&self, context: &mut TerminalContext) {
+		let username = self.account.get_username();
+		let entry = if username.is_empty() {
+			self.account.account_name.clone()
+		} else {
+			format!("{} / {}", self.account.account_name, username)
 		};
-		results > 0
+		let text = format!("{} {}", &get_texts().add_account.account_exists, entry);
+		let center_y = context.get_height() / 2;
+		let pos_x = (context.get_width() / 2).saturating_sub(text.chars().count() as u16 / 2);
+		context.print_at_position(pos_x, center_y, text.as_str());
+
+		let texts = &get_texts().add_account;
+		context.draw_control_footer(vec![&texts.view_existing_input, &texts.add_another_input, &get_texts().input.escape]);
+	}
+
+	fn account_exists_input(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Char('v') => {
+				if let Some(account) = &self.existing_account {
+					self.next_state = Some(Transition::ToShowAccount(account.clone()));
+				}
+			}
+			KeyCode::Char('a') => {
+				self.existing_account = None;
+				self.advance_field();
+			}
+			_ => {}
+		}
 	}
 }
 
 impl StateItem for AddEntryStateItem {
 	fn display(&self, context: &mut TerminalContext) {
 		match self.internal_state {
-			AddAccountState::SetAccount => {
-				self.show_account_data(false, false, false, context);
-				context.draw_input_footer(&get_texts().account.account_name, self.account_name.clone())
+			AddAccountState::SelectType => {
+				self.show_template_selection(context);
 			}
 			AddAccountState::AccountExists => {
-				let text = format!("{} {}", &get_texts().add_account.account_exists, self.account_name);
-				let center_y = context.get_height() / 2;
-				let pos_x = context.get_width() / 2 - text.len() as u16 / 2;
-				context.print_at_position(pos_x, center_y, text.as_str());
+				self.show_account_exists(context);
 			}
-			AddAccountState::AddEmailRequest => {
-				self.show_account_data(true, false, false, context);
-				context.draw_request_footer(&get_texts().add_account.add_email_question);
+			AddAccountState::AddFieldRequest => {
+				self.show_account_data(context, true);
+				let question = get_texts().add_account.add_field_question.replace("{}", &self.get_current_field_name());
+				context.draw_request_footer(&question);
 			}
-			AddAccountState::EnterEmail => {
-				self.show_account_data(true, false, false, context);
-				context.draw_input_footer(&get_texts().account.email, self.email_name.clone())
+			AddAccountState::EnterField => {
+				self.show_account_data(context, true);
+				self.show_field_input(context);
 			}
 			AddAccountState::GeneratePasswordRequest => {
-				self.show_account_data(true, true, false, context);
+				self.show_account_data(context, true);
 				context.draw_request_footer(&get_texts().add_account.generate_pwd_question);
 			}
-			AddAccountState::EnterPassword => {
-				self.show_account_data(true, true, false, context);
-				self.password_widget.display_as_footer(context);
+			AddAccountState::GenerateSshKeyRequest => {
+				self.show_account_data(context, true);
+				context.draw_request_footer(&get_texts().add_account.generate_ssh_key_question);
 			}
-			AddAccountState::PasswordGenerated => {
-				self.show_account_data(true, true, true, context);
-				context.draw_control_footer(vec![&get_texts().add_account.pwd_generated]);
-			}
-			AddAccountState::PasswordSet => {
-				self.show_account_data(true, true, true, context);
-				context.draw_control_footer(vec![&get_texts().add_account.pwd_set]);
+			AddAccountState::AccountSaved => {
+				self.show_account_data(context, false);
+				context.draw_control_footer(vec![&get_texts().add_account.account_saved]);
 			}
 			AddAccountState::Cancel => {
 				let text = &get_texts().add_account.cancel_question;
@@ -174,85 +388,64 @@ impl StateItem for AddEntryStateItem {
 
 	fn register_input(&mut self, key_code: KeyCode) {
 		match self.internal_state {
-			AddAccountState::SetAccount => {
-				if get_text_input(key_code, &mut self.account_name) {
-					if self.check_if_account_already_exists()
-					{
-						self.internal_state = AddAccountState::AccountExists;
-						self.switch_to_main_menu_state(2);
+			AddAccountState::SelectType => self.select_template_input(key_code),
+			AddAccountState::AccountExists => self.account_exists_input(key_code),
+			AddAccountState::AddFieldRequest => {
+				if let Some(confirm) = evaluate_yes_no_answer(key_code) {
+					if confirm {
+						self.internal_state = self.get_value_state(self.get_current_field_kind());
 					} else {
-						self.internal_state = AddAccountState::AddEmailRequest;
+						self.next_field();
 					}
 				}
-				if key_code == KeyCode::Esc {
-					self.internal_state = AddAccountState::Cancel;
-				}
 			}
-			AddAccountState::AccountExists => {}
-			AddAccountState::AddEmailRequest => {
+			AddAccountState::EnterField => self.field_input(key_code),
+			AddAccountState::GeneratePasswordRequest => {
 				if let Some(confirm) = evaluate_yes_no_answer(key_code) {
 					if confirm {
-						self.internal_state = AddAccountState::EnterEmail;
+						let password = generate_password(DEFAULT_PASSWORD_LENGTH, true);
+						self.commit_field_value(password);
 					} else {
-						self.internal_state = AddAccountState::GeneratePasswordRequest;
+						self.internal_state = AddAccountState::EnterField
 					}
 				}
-				if key_code == KeyCode::Esc {
-					self.internal_state = AddAccountState::Cancel;
-				}
 			}
-			AddAccountState::EnterEmail => {
-				if get_text_input(key_code, &mut self.email_name) {
-					self.internal_state = AddAccountState::GeneratePasswordRequest;
-				}
-				if key_code == KeyCode::Esc {
-					self.internal_state = AddAccountState::Cancel;
-				}
-			}
-			AddAccountState::GeneratePasswordRequest => {
+			AddAccountState::GenerateSshKeyRequest => {
 				if let Some(confirm) = evaluate_yes_no_answer(key_code) {
 					if confirm {
-						self.generate_password();
-						self.password_widget.update_password(self.password_buffer.clone());
-						self.internal_state = AddAccountState::PasswordGenerated;
-						self.finalize_account_creation();
+						let ssh_key = SshKey::generate(&self.account.account_name);
+						self.commit_ssh_key(ssh_key);
 					} else {
-						self.internal_state = AddAccountState::EnterPassword
+						self.internal_state = AddAccountState::EnterField
 					}
 				}
-				if key_code == KeyCode::Esc {
-					self.internal_state = AddAccountState::Cancel;
-				}
-			}
-			AddAccountState::EnterPassword => {
-				self.password_widget.update_password(self.password_buffer.clone());
-				if get_text_input(key_code, &mut self.password_buffer) {
-					self.internal_state = AddAccountState::PasswordSet;
-					self.finalize_account_creation();
-				}
-				if key_code == KeyCode::Esc {
-					self.internal_state = AddAccountState::Cancel;
-				}
 			}
-			AddAccountState::PasswordGenerated => {}
-			AddAccountState::PasswordSet => {}
+			AddAccountState::AccountSaved => {}
 			AddAccountState::Cancel => {
 				if let Some(confirm) = evaluate_yes_no_answer(key_code) {
 					if confirm {
 						self.switch_to_main_menu_state(0);
 					} else {
-						self.internal_state = AddAccountState::SetAccount;
+						self.internal_state = self.state_before_cancel;
 					}
 				}
 			}
 		}
+
+		let can_cancel = !matches!(self.internal_state, AddAccountState::AccountSaved | AddAccountState::Cancel);
+		if key_code == KeyCode::Esc && can_cancel {
+			self.state_before_cancel = self.internal_state;
+			self.internal_state = AddAccountState::Cancel;
+		}
 	}
 
 	fn next_state(&self) -> Option<Transition> {
-		if *self.switch_state.lock().unwrap() {
+		if self.next_state.is_some() {
+			self.next_state.clone()
+		} else if *self.switch_state.lock().unwrap() {
 			Some(Transition::ToMainMenu)
 		} else {
 			None
 		}
 	}
-}
\ No newline at end of file
+}
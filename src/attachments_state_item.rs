@@ -0,0 +1,256 @@
+use std::sync::{Arc, Mutex};
+use crossterm::event::KeyCode;
+use crate::database_context::{Account, Attachment, DatabaseManager};
+use crate::file_accesssor::{expand_user_path, read_file_from_disk, write_private_file_to_disk};
+use crate::input_handler::{evaluate_yes_no_answer, get_text_input};
+use crate::page_list_view::PageView;
+use crate::state_item::StateItem;
+use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
+use crate::transition::Transition;
+
+pub const MAX_ATTACHMENT_SIZE: usize = 10 * 1024 * 1024;
+
+enum AttachmentsState {
+	List,
+	EnterAddPath,
+	EnterExtractPath(i32),
+	DeleteAttachment(i32),
+	Message(String),
+}
+
+pub struct AttachmentsStateItem {
+	account: Account,
+	attachments: Vec<Attachment>,
+	page_view: PageView,
+	input_buffer: String,
+	internal_state: AttachmentsState,
+	next_state: Option<Transition>,
+	db_manager: Arc<Mutex<DatabaseManager>>,
+}
+
+impl AttachmentsStateItem {
+	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>, account: Account) -> Self {
+		let mut state_item = Self {
+			account,
+			attachments: Vec::new(),
+			page_view: PageView::new_empty(),
+			input_buffer: String::new(),
+			internal_state: AttachmentsState::List,
+			next_state: None,
+			db_manager,
+		};
+		state_item.load_attachments();
+		state_item
+	}
+
+	fn load_attachments(&mut self) {
+		let db_manager = self.db_manager.lock().unwrap();
+		let db_context = match db_manager.get_database_context() {
+			Some(context) => context,
+			None => panic!("Database not initialized"),
+		};
+
+		self.attachments = db_context.list_attachments(self.account.id).unwrap();
+		let items = self.attachments.iter().map(|attachment| {
+			(attachment.id, format!("{} ({})", attachment.file_name, format_file_size(attachment.size)))
+		}).collect();
+		self.page_view = PageView::new_from_items(items);
+	}
+
+	fn add_attachment(&mut self) {
+		let path = expand_user_path(&self.input_buffer);
+		let file_name = match path.file_name() {
+			Some(name) => name.to_string_lossy().to_string(),
+			None => {
+				self.show_message(format!("{} {}", get_texts().attachments.read_failed, path.display()));
+				return;
+			}
+		};
+
+		let data = match read_file_from_disk(&path) {
+			Ok(data) => data,
+			Err(e) => {
+				self.show_message(format!("{} {}", get_texts().attachments.read_failed, e));
+				return;
+			}
+		};
+
+		if data.len() > MAX_ATTACHMENT_SIZE {
+			self.show_message(format!("{} {}", get_texts().attachments.too_large, format_file_size(MAX_ATTACHMENT_SIZE as i64)));
+			return;
+		}
+
+		{
+			let db_manager = self.db_manager.lock().unwrap();
+			let db_context = match db_manager.get_database_context() {
+				Some(context) => context,
+				None => return,
+			};
+			db_context.add_attachment(self.account.id, &file_name, &data).unwrap();
+			db_manager.safe_database();
+		}
+
+		self.load_attachments();
+		self.show_message(format!("{} {}", get_texts().attachments.added_msg, file_name));
+	}
+
+	fn extract_attachment(&mut self, attachment_id: i32) {
+		let attachment = match self.attachments.iter().find(|attachment| attachment.id == attachment_id) {
+			Some(attachment) => attachment.clone(),
+			None => return,
+		};
+
+		let mut path = expand_user_path(&self.input_buffer);
+		if self.input_buffer.trim().is_empty() || path.is_dir() {
+			path = path.join(&attachment.file_name);
+		}
+
+		let data = {
+			let db_manager = self.db_manager.lock().unwrap();
+			let db_context = match db_manager.get_database_context() {
+				Some(context) => context,
+				None => return,
+			};
+			db_context.get_attachment_data(attachment_id).unwrap()
+		};
+
+		match write_private_file_to_disk(&path, &data) {
+			Ok(()) => self.show_message(format!("{} {}", get_texts().attachments.extracted_msg, path.display())),
+			Err(e) => self.show_message(format!("{} {}", get_texts().attachments.write_failed, e)),
+		}
+	}
+
+	fn delete_attachment(&mut self, attachment_id: i32) {
+		{
+			let db_manager = self.db_manager.lock().unwrap();
+			let db_context = match db_manager.get_database_context() {
+				Some(context) => context,
+				None => return,
+			};
+			db_context.remove_attachment(attachment_id).unwrap();
+			db_manager.safe_database();
+		}
+		self.load_attachments();
+		self.internal_state = AttachmentsState::List;
+	}
+
+	fn show_message(&mut self, message: String) {
+		self.input_buffer.clear();
+		self.internal_state = AttachmentsState::Message(message);
+	}
+
+	fn input_list_state(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Down => self.page_view.next_account(),
+			KeyCode::Up => self.page_view.prev_account(),
+			KeyCode::Left => self.page_view.prev_page(),
+			KeyCode::Right => self.page_view.next_page(),
+			KeyCode::Char('a') => {
+				self.input_buffer.clear();
+				self.internal_state = AttachmentsState::EnterAddPath;
+			}
+			KeyCode::Char('x') => {
+				if let Some(attachment_id) = self.page_view.get_selected_account_id() {
+					self.input_buffer.clear();
+					self.internal_state = AttachmentsState::EnterExtractPath(attachment_id);
+				}
+			}
+			KeyCode::Char('d') => {
+				if let Some(attachment_id) = self.page_view.get_selected_account_id() {
+					self.internal_state = AttachmentsState::DeleteAttachment(attachment_id);
+				}
+			}
+			KeyCode::Char('q') => {
+				self.next_state = Some(Transition::ToShowAccount(self.account.clone()));
+			}
+			_ => {}
+		}
+	}
+
+	fn show_list(&self, context: &mut TerminalContext) {
+		if self.attachments.is_empty() {
+			context.print_at_position(0, 2, &get_texts().attachments.empty_msg);
+		}
+		self.page_view.display_page(context, 0, 2);
+	}
+}
+
+impl StateItem for AttachmentsStateItem {
+	fn display(&self, context: &mut TerminalContext) {
+		let heading = format!("{} - {}", get_texts().attachments.heading, self.account.account_name);
+		context.print_styled_at_position(0, 0, &heading, StyleAttribute::Underline);
+		self.show_list(context);
+
+		let texts = &get_texts().attachments;
+		match &self.internal_state {
+			AttachmentsState::List => {
+				context.draw_control_footer(vec![&texts.add_input, &texts.extract_input, &texts.delete_input, &texts.quit_input, &get_texts().input.down_arrow, &get_texts().input.up_arrow]);
+			}
+			AttachmentsState::EnterAddPath => {
+				context.draw_input_footer(&texts.add_path_prompt, self.input_buffer.clone());
+			}
+			AttachmentsState::EnterExtractPath(_) => {
+				context.draw_input_footer(&texts.extract_path_prompt, self.input_buffer.clone());
+			}
+			AttachmentsState::DeleteAttachment(_) => {
+				context.draw_request_footer(&texts.delete_question);
+			}
+			AttachmentsState::Message(message) => {
+				context.draw_control_footer(vec![message, &get_texts().input.enter]);
+			}
+		}
+	}
+
+	fn update_display(&self) -> bool {
+		false
+	}
+
+	fn register_input(&mut self, key_code: KeyCode) {
+		match self.internal_state {
+			AttachmentsState::List => self.input_list_state(key_code),
+			AttachmentsState::EnterAddPath => {
+				if key_code == KeyCode::Esc {
+					self.internal_state = AttachmentsState::List;
+				} else if get_text_input(key_code, &mut self.input_buffer) {
+					self.add_attachment();
+				}
+			}
+			AttachmentsState::EnterExtractPath(attachment_id) => {
+				if key_code == KeyCode::Esc {
+					self.internal_state = AttachmentsState::List;
+				} else if get_text_input(key_code, &mut self.input_buffer) {
+					self.extract_attachment(attachment_id);
+				}
+			}
+			AttachmentsState::DeleteAttachment(attachment_id) => {
+				if let Some(confirm) = evaluate_yes_no_answer(key_code) {
+					if confirm {
+						self.delete_attachment(attachment_id);
+					} else {
+						self.internal_state = AttachmentsState::List;
+					}
+				}
+			}
+			AttachmentsState::Message(_) => {
+				if key_code == KeyCode::Enter || key_code == KeyCode::Esc {
+					self.internal_state = AttachmentsState::List;
+				}
+			}
+		}
+	}
+
+	fn next_state(&self) -> Option<Transition> {
+		self.next_state.clone()
+	}
+}
+
+fn format_file_size(size: i64) -> String {
+	if size >= 1024 * 1024 {
+		format!("{:.1} MB", size as f64 / (1024.0 * 1024.0))
+	} else if size >= 1024 {
+		format!("{:.1} KB", size as f64 / 1024.0)
+	} else {
+		format!("{} B", size)
+	}
+}
@@ -8,6 +8,7 @@ use crate::transition::Transition;
 use crossterm::event::KeyCode;
 use std::sync::{Arc, Mutex};
 use crate::password_widget::PasswordWidget;
+use crate::rotation_reminder_state_item::ROTATION_WARNING_DAYS;
 use crate::texts::get_texts;
 use crate::widget::Widget;
 
@@ -19,6 +20,7 @@ enum LockState {
 
 pub struct AuthenticationStateItem {
 	next_state_ready: Arc<Mutex<bool>>,
+	next_state: Transition,
 	master_password: PasswordEncryption,
 	lock_state: LockState,
 	input_buffer: String,
@@ -35,6 +37,7 @@ impl AuthenticationStateItem {
 		db_manager.lock().unwrap().load_database_from_disk();
 		AuthenticationStateItem {
 			next_state_ready: Arc::new(Mutex::new(false)),
+			next_state: Transition::ToMainMenu,
 			master_password,
 			password_widget: PasswordWidget::new(input_buffer.clone()),
 			lock_state: LockState::Locked,
@@ -53,8 +56,20 @@ impl AuthenticationStateItem {
 		}
 	}
 
-	fn unlock_database(&self) {
+	fn unlock_database(&mut self) {
 		self.db_manager.lock().unwrap().unlock(&self.master_password.get_encrypted_string());
+		self.check_due_accounts();
+	}
+
+	fn check_due_accounts(&mut self) {
+		let db_manager = self.db_manager.lock().unwrap();
+		let has_due_accounts = match db_manager.get_database_context() {
+			Some(context) => !context.list_accounts_due_for_rotation(ROTATION_WARNING_DAYS).unwrap().is_empty(),
+			None => false,
+		};
+		if has_due_accounts {
+			self.next_state = Transition::ToRotationReminder;
+		}
 	}
 }
 
@@ -107,7 +122,7 @@ impl StateItem for AuthenticationStateItem {
 	}
 	fn next_state(&self) -> Option<Transition> {
 		if *self.next_state_ready.lock().unwrap() {
-			Some(Transition::ToMainMenu)
+			Some(self.next_state.clone())
 		} else {
 			None
 		}
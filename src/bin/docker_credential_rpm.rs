@@ -0,0 +1,59 @@
+use std::io::{stdin, Read};
+use rusty_password_manager::docker_credential::{erase_credential, get_credential, list_credentials, store_credential, DockerCredential, CREDENTIALS_NOT_FOUND};
+use rusty_password_manager::texts;
+use rusty_password_manager::vault_unlock::prompt_and_unlock_vault;
+
+/// A docker credential helper backed by the vault, enable it with
+/// `"credsStore": "rpm"` in ~/.docker/config.json once the binary is on the PATH.
+fn main() {
+	texts::init_texts();
+	let action = std::env::args().nth(1).unwrap_or_default();
+	if !matches!(action.as_str(), "get" | "store" | "erase" | "list") {
+		exit_with_error("Usage: docker-credential-rpm <get|store|erase|list>");
+	}
+
+	let mut input = String::new();
+	if action != "list" && stdin().read_to_string(&mut input).is_err() {
+		exit_with_error("Could not read the request");
+	}
+
+	let db_manager = match prompt_and_unlock_vault() {
+		Ok(db_manager) => db_manager,
+		Err(e) => exit_with_error(&e.get_message()),
+	};
+	let db_context = match db_manager.get_database_context() {
+		Some(context) => context,
+		None => panic!("Database not initialized"),
+	};
+
+	match action.as_str() {
+		"get" => match get_credential(db_context, &input) {
+			Some(credential) => println!("{}", serde_json::to_string(&credential).expect("Could not serialize the credential")),
+			None => exit_with_error(CREDENTIALS_NOT_FOUND),
+		},
+		"store" => {
+			let credential: DockerCredential = match serde_json::from_str(&input) {
+				Ok(credential) => credential,
+				Err(e) => exit_with_error(&format!("Invalid credential: {}", e)),
+			};
+			store_credential(db_context, &credential);
+			if let Err(e) = db_manager.try_safe_database() {
+				exit_with_error(&e);
+			}
+		}
+		"erase" => {
+			if erase_credential(db_context, &input) {
+				if let Err(e) = db_manager.try_safe_database() {
+					exit_with_error(&e);
+				}
+			}
+		}
+		_ => println!("{}", serde_json::to_string(&list_credentials(db_context)).expect("Could not serialize the list")),
+	}
+}
+
+/// Docker shows what a helper prints to stdout as the error message.
+fn exit_with_error(message: &str) -> ! {
+	println!("{}", message);
+	std::process::exit(1);
+}
@@ -0,0 +1,67 @@
+use std::io::{stdin, stdout, Write};
+use rusty_password_manager::database_context::DatabaseManager;
+use rusty_password_manager::git_credential::{erase_credential, get_credential, store_credential, CredentialRequest};
+use rusty_password_manager::texts;
+use rusty_password_manager::vault_unlock::prompt_and_unlock_vault;
+
+/// A git credential helper backed by the vault, enable it with
+/// `git config --global credential.helper rpm` once the binary is on the PATH.
+fn main() {
+	texts::init_texts();
+	let action = match std::env::args().nth(1) {
+		Some(action) => action,
+		None => {
+			eprintln!("Usage: git-credential-rpm <get|store|erase>");
+			std::process::exit(1);
+		}
+	};
+	// Git may add new actions in the future, helpers are expected to ignore those
+	if !matches!(action.as_str(), "get" | "store" | "erase") {
+		return;
+	}
+
+	let request = CredentialRequest::read_from(stdin().lock());
+	if request.protocol.is_none() || request.host.is_none() {
+		return;
+	}
+
+	let db_manager = match prompt_and_unlock_vault() {
+		Ok(db_manager) => db_manager,
+		Err(e) => {
+			// Git falls back to its other helpers or asks the user itself
+			eprintln!("git-credential-rpm: {}", e.get_message());
+			return;
+		}
+	};
+	let db_context = match db_manager.get_database_context() {
+		Some(context) => context,
+		None => panic!("Database not initialized"),
+	};
+
+	match action.as_str() {
+		"get" => {
+			if let Some(response) = get_credential(db_context, &request) {
+				let mut stdout = stdout();
+				stdout.write_all(response.as_bytes()).expect("Could not write the credential");
+				stdout.flush().expect("Could not write the credential");
+			}
+		}
+		"store" => {
+			if store_credential(db_context, &request) {
+				save_vault(&db_manager);
+			}
+		}
+		_ => {
+			if erase_credential(db_context, &request) {
+				save_vault(&db_manager);
+			}
+		}
+	}
+}
+
+/// Git does not stop because a helper failed, so the reason is only reported.
+fn save_vault(db_manager: &DatabaseManager) {
+	if let Err(e) = db_manager.try_safe_database() {
+		eprintln!("git-credential-rpm: {}", e);
+	}
+}
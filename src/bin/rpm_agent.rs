@@ -0,0 +1,117 @@
+use std::io::{stdin, BufRead, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rusty_password_manager::database_context::DatabaseManager;
+use rusty_password_manager::file_accesssor::get_agent_socket_path;
+use rusty_password_manager::texts::{self, get_texts};
+use rusty_password_manager::vault_agent::{send_agent_request, AgentRequest, VaultAgent, DEFAULT_IDLE_TIMEOUT};
+use rusty_password_manager::vault_unlock::{derive_vault_key, prompt_master_password};
+
+const STARTUP_WAIT: Duration = Duration::from_secs(5);
+
+/// Keeps the vault unlocked in the background, so the command line tools and the
+/// TUI do not ask for the master password again until the agent locks.
+fn main() {
+	texts::init_texts();
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	match args.first().map(String::as_str) {
+		Some("start") => start(&args[1..]),
+		Some("serve") => serve(&args[1..]),
+		Some("status") => status(),
+		Some("lock") => lock(),
+		_ => exit_with_error(&get_texts().cli.agent_usage),
+	}
+}
+
+fn exit_with_error(message: &str) -> ! {
+	eprintln!("{}", message);
+	std::process::exit(1);
+}
+
+fn parse_timeout(args: &[String]) -> Duration {
+	match args {
+		[] => DEFAULT_IDLE_TIMEOUT,
+		[flag, minutes] if flag == "--timeout" => match minutes.parse::<u64>() {
+			Ok(minutes) if minutes > 0 => Duration::from_secs(minutes * 60),
+			_ => exit_with_error(&get_texts().cli.agent_invalid_timeout),
+		},
+		_ => exit_with_error(&get_texts().cli.agent_usage),
+	}
+}
+
+/// Asks for the master password and hands the derived key to a detached copy of this program.
+/// The copy keeps running after this process exits, so it is not waited for.
+#[allow(clippy::zombie_processes)]
+fn start(args: &[String]) {
+	let timeout = parse_timeout(args);
+	if send_agent_request(&AgentRequest::Status).is_some() {
+		exit_with_error(&get_texts().cli.agent_already_running);
+	}
+
+	let passkey = match prompt_master_password().and_then(|password| derive_vault_key(&password)) {
+		Ok(passkey) => passkey,
+		Err(e) => exit_with_error(&e.get_message()),
+	};
+
+	let executable = std::env::current_exe().expect("Could not locate the agent executable");
+	let mut command = Command::new(executable);
+	command.arg("serve").arg("--timeout").arg((timeout.as_secs() / 60).to_string())
+		.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null());
+	#[cfg(unix)]
+	{
+		// Keeps the agent alive when the terminal it was started from closes
+		use std::os::unix::process::CommandExt;
+		command.process_group(0);
+	}
+	let mut child = command.spawn().expect("Could not start the agent");
+	{
+		let mut child_stdin = child.stdin.take().expect("Could not pass the key to the agent");
+		writeln!(child_stdin, "{}", STANDARD.encode(passkey)).expect("Could not pass the key to the agent");
+	}
+
+	let started_at = std::time::Instant::now();
+	while started_at.elapsed() < STARTUP_WAIT {
+		if send_agent_request(&AgentRequest::Status).is_some() {
+			println!("{}", get_texts().cli.agent_started.replace("{}", &(timeout.as_secs() / 60).to_string()));
+			return;
+		}
+		if let Ok(Some(_)) = child.try_wait() {
+			break;
+		}
+		std::thread::sleep(Duration::from_millis(100));
+	}
+	exit_with_error(&get_texts().cli.agent_start_failed);
+}
+
+fn serve(args: &[String]) {
+	let timeout = parse_timeout(args);
+	let mut line = String::new();
+	stdin().lock().read_line(&mut line).expect("Could not read the key");
+	let passkey: [u8; 32] = match STANDARD.decode(line.trim()).ok().and_then(|key| key.try_into().ok()) {
+		Some(passkey) => passkey,
+		None => exit_with_error(&get_texts().cli.agent_invalid_key),
+	};
+
+	let mut db_manager = DatabaseManager::new();
+	db_manager.load_database_from_disk();
+	db_manager.unlock(&passkey);
+	if let Err(e) = VaultAgent::new(db_manager, timeout).run(&get_agent_socket_path()) {
+		exit_with_error(&e.to_string());
+	}
+}
+
+fn status() {
+	match send_agent_request(&AgentRequest::Status) {
+		Some(response) => println!("{}", get_texts().cli.agent_unlocked.replace("{}", &response.locks_in.unwrap_or_default().to_string())),
+		None => println!("{}", get_texts().cli.agent_not_running),
+	}
+}
+
+fn lock() {
+	match send_agent_request(&AgentRequest::Lock) {
+		Some(_) => println!("{}", get_texts().cli.agent_locked),
+		None => println!("{}", get_texts().cli.agent_nothing_to_lock),
+	}
+}
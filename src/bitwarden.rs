@@ -0,0 +1,476 @@
+use std::num::NonZeroU32;
+use std::time::SystemTime;
+use aes::Aes256;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::{digest, hkdf, hmac, pbkdf2};
+use serde::Deserialize;
+use crate::database_context::{Account, EMAIL_FIELD_KEY, GROUP_FIELD_KEY, NOTES_FIELD_KEY, URL_FIELD_KEY, USERNAME_FIELD_KEY};
+use crate::date_util::parse_timestamp;
+use crate::otpauth::parse_totp;
+use crate::ssh_key::{SshKey, PRIVATE_KEY_FIELD_KEY, PUBLIC_KEY_FIELD_KEY};
+use crate::texts::get_texts;
+use crate::vault_import::{ImportResult, ImportedEntry};
+
+const KDF_PBKDF2: u32 = 0;
+const KDF_ARGON2ID: u32 = 1;
+/// Encrypted strings of the form "2.iv|data|mac", AES-256-CBC with HMAC-SHA256 over iv and data.
+const ENC_TYPE_AES_CBC_HMAC: &str = "2";
+
+const ITEM_LOGIN: u32 = 1;
+const ITEM_SECURE_NOTE: u32 = 2;
+const ITEM_CARD: u32 = 3;
+const ITEM_IDENTITY: u32 = 4;
+const ITEM_SSH_KEY: u32 = 5;
+/// Custom fields which only point to the username or password of the item.
+const FIELD_LINKED: u32 = 3;
+
+const LOGIN_ENTRY_TYPE: &str = "login";
+const CARD_ENTRY_TYPE: &str = "credit_card";
+const NOTE_ENTRY_TYPE: &str = "secure_note";
+const SSH_KEY_ENTRY_TYPE: &str = "ssh_key";
+const OTP_FIELD_KEY: &str = "otp";
+const CARDHOLDER_FIELD_KEY: &str = "cardholder";
+const EXPIRY_FIELD_KEY: &str = "expiry";
+const CVV_FIELD_KEY: &str = "cvv";
+const BRAND_FIELD_KEY: &str = "brand";
+
+pub enum BitwardenError {
+	NotBitwarden,
+	AccountEncrypted,
+	UnsupportedKdf,
+	WrongPassword,
+	Corrupted,
+}
+
+impl BitwardenError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().import;
+		match self {
+			BitwardenError::NotBitwarden => texts.not_bitwarden.clone(),
+			BitwardenError::AccountEncrypted => texts.account_encrypted.clone(),
+			BitwardenError::UnsupportedKdf => texts.unsupported_kdf.clone(),
+			BitwardenError::WrongPassword => texts.wrong_password.clone(),
+			BitwardenError::Corrupted => texts.corrupted_file.clone(),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenExport {
+	#[serde(default)]
+	encrypted: bool,
+	#[serde(default)]
+	password_protected: bool,
+	salt: Option<String>,
+	kdf_type: Option<u32>,
+	kdf_iterations: Option<u32>,
+	kdf_memory: Option<u32>,
+	kdf_parallelism: Option<u32>,
+	#[serde(rename = "encKeyValidation_DO_NOT_EDIT")]
+	key_validation: Option<String>,
+	data: Option<String>,
+	#[serde(default)]
+	folders: Vec<Folder>,
+	#[serde(default)]
+	collections: Vec<Folder>,
+	items: Option<Vec<Item>>,
+}
+
+#[derive(Deserialize)]
+struct Folder {
+	id: String,
+	name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+	#[serde(rename = "type")]
+	item_type: u32,
+	name: Option<String>,
+	notes: Option<String>,
+	#[serde(default)]
+	favorite: bool,
+	folder_id: Option<String>,
+	collection_ids: Option<Vec<String>>,
+	fields: Option<Vec<Field>>,
+	login: Option<Login>,
+	card: Option<Card>,
+	identity: Option<Identity>,
+	ssh_key: Option<SshKeyItem>,
+	password_history: Option<Vec<serde_json::Value>>,
+	creation_date: Option<String>,
+	revision_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Field {
+	name: Option<String>,
+	value: Option<String>,
+	#[serde(rename = "type")]
+	field_type: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Login {
+	username: Option<String>,
+	password: Option<String>,
+	totp: Option<String>,
+	uris: Option<Vec<LoginUri>>,
+	password_revision_date: Option<String>,
+	fido2_credentials: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Deserialize)]
+struct LoginUri {
+	uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Card {
+	cardholder_name: Option<String>,
+	brand: Option<String>,
+	number: Option<String>,
+	exp_month: Option<String>,
+	exp_year: Option<String>,
+	code: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Identity {
+	title: Option<String>,
+	first_name: Option<String>,
+	middle_name: Option<String>,
+	last_name: Option<String>,
+	address1: Option<String>,
+	address2: Option<String>,
+	address3: Option<String>,
+	city: Option<String>,
+	state: Option<String>,
+	postal_code: Option<String>,
+	country: Option<String>,
+	company: Option<String>,
+	email: Option<String>,
+	phone: Option<String>,
+	ssn: Option<String>,
+	username: Option<String>,
+	passport_number: Option<String>,
+	license_number: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SshKeyItem {
+	private_key: Option<String>,
+	public_key: Option<String>,
+}
+
+fn parse_export(data: &[u8]) -> Result<BitwardenExport, BitwardenError> {
+	let export: BitwardenExport = serde_json::from_slice(data).map_err(|_| BitwardenError::NotBitwarden)?;
+	if export.items.is_none() && export.data.is_none() {
+		return Err(BitwardenError::NotBitwarden);
+	}
+	Ok(export)
+}
+
+/// Tells whether the export has to be decrypted with the password it was exported with.
+pub fn is_password_protected(data: &[u8]) -> Result<bool, BitwardenError> {
+	Ok(parse_export(data)?.password_protected)
+}
+
+/// Reads a Bitwarden JSON export, unencrypted or protected with a password, and maps its items
+/// onto the entry type closest to them. Folders become the group field.
+pub fn import_bitwarden(data: &[u8], password: Option<&str>) -> Result<ImportResult, BitwardenError> {
+	let mut export = parse_export(data)?;
+	if export.password_protected {
+		let decrypted = decrypt_export(&export, password.unwrap_or_default())?;
+		export = parse_export(&decrypted).map_err(|_| BitwardenError::Corrupted)?;
+	}
+	// Exports encrypted with the account key can only be read by Bitwarden itself
+	if export.encrypted {
+		return Err(BitwardenError::AccountEncrypted);
+	}
+
+	let mut importer = BitwardenImporter::default();
+	for item in export.items.as_deref().unwrap_or_default() {
+		let group = item.folder_id.as_ref().and_then(|id| export.folders.iter().find(|folder| &folder.id == id))
+			.or_else(|| item.collection_ids.iter().flatten().find_map(|id| export.collections.iter().find(|collection| &collection.id == id)))
+			.map(|folder| folder.name.trim())
+			.unwrap_or_default();
+		if let Some(imported) = importer.import_item(item, group) {
+			importer.result.entries.push(imported);
+		}
+	}
+	Ok(importer.finish())
+}
+
+/// The export password is stretched like a Bitwarden PIN: the KDF result is expanded with HKDF
+/// into an encryption and a MAC key. The validation string tells a wrong password from a damaged file.
+fn decrypt_export(export: &BitwardenExport, password: &str) -> Result<Vec<u8>, BitwardenError> {
+	let salt = export.salt.as_deref().ok_or(BitwardenError::Corrupted)?;
+	let iterations = export.kdf_iterations.unwrap_or_default();
+	let mut key = [0u8; 32];
+	match export.kdf_type.unwrap_or(KDF_PBKDF2) {
+		KDF_PBKDF2 => {
+			let iterations = NonZeroU32::new(iterations).ok_or(BitwardenError::UnsupportedKdf)?;
+			pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt.as_bytes(), password.as_bytes(), &mut key);
+		}
+		KDF_ARGON2ID => {
+			let memory = export.kdf_memory.and_then(|memory| memory.checked_mul(1024)).ok_or(BitwardenError::UnsupportedKdf)?;
+			let params = Params::new(memory, iterations, export.kdf_parallelism.unwrap_or(1), Some(key.len())).map_err(|_| BitwardenError::UnsupportedKdf)?;
+			let salt = digest::digest(&digest::SHA256, salt.as_bytes());
+			Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(password.as_bytes(), salt.as_ref(), &mut key)
+				.map_err(|_| BitwardenError::UnsupportedKdf)?;
+		}
+		_ => return Err(BitwardenError::UnsupportedKdf),
+	}
+
+	let prk = hkdf::Prk::new_less_safe(hkdf::HKDF_SHA256, &key);
+	let mut encryption_key = [0u8; 32];
+	let mut mac_key = [0u8; 32];
+	prk.expand(&[b"enc"], hkdf::HKDF_SHA256).unwrap().fill(&mut encryption_key).unwrap();
+	prk.expand(&[b"mac"], hkdf::HKDF_SHA256).unwrap().fill(&mut mac_key).unwrap();
+
+	if let Some(validation) = &export.key_validation {
+		decrypt_string(validation, &encryption_key, &mac_key).map_err(|_| BitwardenError::WrongPassword)?;
+	}
+	decrypt_string(export.data.as_deref().ok_or(BitwardenError::Corrupted)?, &encryption_key, &mac_key)
+		.map_err(|_| if export.key_validation.is_some() { BitwardenError::Corrupted } else { BitwardenError::WrongPassword })
+}
+
+fn decrypt_string(encrypted: &str, encryption_key: &[u8], mac_key: &[u8]) -> Result<Vec<u8>, BitwardenError> {
+	let (enc_type, parts) = encrypted.split_once('.').ok_or(BitwardenError::Corrupted)?;
+	let parts: Vec<Vec<u8>> = parts.split('|').map(|part| BASE64.decode(part)).collect::<Result<_, _>>().map_err(|_| BitwardenError::Corrupted)?;
+	if enc_type != ENC_TYPE_AES_CBC_HMAC || parts.len() != 3 {
+		return Err(BitwardenError::Corrupted);
+	}
+	let (iv, mut data, mac) = (&parts[0], parts[1].clone(), &parts[2]);
+	hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, mac_key), &[iv.as_slice(), &data].concat(), mac).map_err(|_| BitwardenError::WrongPassword)?;
+	let length = cbc::Decryptor::<Aes256>::new_from_slices(encryption_key, iv).map_err(|_| BitwardenError::Corrupted)?
+		.decrypt_padded_mut::<Pkcs7>(&mut data).map_err(|_| BitwardenError::Corrupted)?.len();
+	data.truncate(length);
+	Ok(data)
+}
+
+#[derive(Default)]
+struct BitwardenImporter {
+	result: ImportResult,
+	history_count: usize,
+}
+
+impl BitwardenImporter {
+	fn import_item(&mut self, item: &Item, group: &str) -> Option<ImportedEntry> {
+		let texts = &get_texts().import;
+		let entry_type = match item.item_type {
+			ITEM_LOGIN => LOGIN_ENTRY_TYPE,
+			ITEM_CARD => CARD_ENTRY_TYPE,
+			ITEM_SSH_KEY => SSH_KEY_ENTRY_TYPE,
+			ITEM_SECURE_NOTE | ITEM_IDENTITY => NOTE_ENTRY_TYPE,
+			_ => {
+				self.result.unmapped.push(texts.unknown_item_type.replacen("{}", item.name.as_deref().unwrap_or_default(), 1));
+				return None;
+			}
+		};
+		let mut account = Account::new(entry_type);
+		let name = item.name.as_deref().unwrap_or_default().trim();
+		account.account_name = if name.is_empty() { texts.untitled.clone() } else { name.to_string() };
+		let display_name = if group.is_empty() { account.account_name.clone() } else { format!("{}/{}", group, account.account_name) };
+		set_optional_field(&mut account, NOTES_FIELD_KEY, &item.notes);
+		if !group.is_empty() {
+			account.set_field(GROUP_FIELD_KEY, group.to_string());
+		}
+		account.pinned = item.favorite;
+
+		if let Some(login) = &item.login {
+			self.import_login(&mut account, login, &display_name);
+		}
+		if let Some(card) = &item.card {
+			self.import_card(&mut account, card, &display_name);
+		}
+		if let Some(identity) = &item.identity {
+			import_identity(&mut account, identity);
+		}
+		if let Some(ssh_key) = &item.ssh_key {
+			self.import_ssh_key(&mut account, ssh_key, &display_name);
+		}
+
+		for field in item.fields.iter().flatten().filter(|field| field.field_type != FIELD_LINKED) {
+			let value = field.value.as_deref().unwrap_or_default();
+			if value.is_empty() {
+				continue;
+			}
+			let key = field.name.as_deref().map(str::trim).filter(|name| !name.is_empty()).unwrap_or(texts.untitled.as_str());
+			if account.get_field(key).is_some_and(|value| !value.is_empty()) {
+				self.result.unmapped.push(texts.field_conflict.replacen("{}", &display_name, 1).replacen("{}", key, 1));
+				continue;
+			}
+			account.set_field(key, value.to_string());
+		}
+
+		let now = SystemTime::now();
+		account.created_at = item.creation_date.as_deref().and_then(parse_timestamp).unwrap_or(now);
+		account.updated_at = item.revision_date.as_deref().and_then(parse_timestamp).unwrap_or(account.created_at);
+		account.password_changed_at = item.login.as_ref().and_then(|login| login.password_revision_date.as_deref()).and_then(parse_timestamp)
+			.unwrap_or(account.created_at);
+		self.history_count += item.password_history.as_ref().map_or(0, Vec::len);
+		Some(ImportedEntry { account, attachments: Vec::new() })
+	}
+
+	fn import_login(&mut self, account: &mut Account, login: &Login, display_name: &str) {
+		let texts = &get_texts().import;
+		set_optional_field(account, USERNAME_FIELD_KEY, &login.username);
+		account.password = login.password.clone().unwrap_or_default();
+		let uris: Vec<&str> = login.uris.iter().flatten().filter_map(|uri| uri.uri.as_deref()).filter(|uri| !uri.is_empty()).collect();
+		for (idx, uri) in uris.iter().enumerate() {
+			let key = if idx == 0 { URL_FIELD_KEY.to_string() } else { format!("{}_{}", URL_FIELD_KEY, idx + 1) };
+			account.set_field(&key, uri.to_string());
+		}
+
+		if let Some(totp) = login.totp.as_deref().map(str::trim).filter(|totp| !totp.is_empty()) {
+			match parse_totp(totp) {
+				Some(config) => account.totp = Some(config),
+				None => {
+					self.result.unmapped.push(texts.invalid_otp.replacen("{}", display_name, 1));
+					account.set_field(OTP_FIELD_KEY, totp.to_string());
+				}
+			}
+		}
+		if login.fido2_credentials.as_ref().is_some_and(|credentials| !credentials.is_empty()) {
+			self.result.unmapped.push(texts.passkey_skipped.replacen("{}", display_name, 1));
+		}
+	}
+
+	fn import_card(&mut self, account: &mut Account, card: &Card, display_name: &str) {
+		set_optional_field(account, CARDHOLDER_FIELD_KEY, &card.cardholder_name);
+		account.password = card.number.clone().unwrap_or_default();
+		set_optional_field(account, CVV_FIELD_KEY, &card.code);
+		set_optional_field(account, BRAND_FIELD_KEY, &card.brand);
+		let month = card.exp_month.as_deref().and_then(|month| month.trim().parse::<u32>().ok()).filter(|month| (1..=12).contains(month));
+		let year = card.exp_year.as_deref().and_then(|year| year.trim().parse::<u32>().ok());
+		match (month, year) {
+			(Some(month), Some(year)) => account.set_field(EXPIRY_FIELD_KEY, format!("{:02}/{:02}", month, year % 100)),
+			(None, None) => {}
+			_ => self.result.unmapped.push(get_texts().import.incomplete_expiry.replacen("{}", display_name, 1)),
+		}
+	}
+
+	/// Only ed25519 keys can be stored, the public key is kept in any case.
+	fn import_ssh_key(&mut self, account: &mut Account, ssh_key: &SshKeyItem, display_name: &str) {
+		let private_key = ssh_key.private_key.as_deref().unwrap_or_default();
+		match SshKey::from_openssh_pem(private_key) {
+			Ok(key) => {
+				account.set_field(PRIVATE_KEY_FIELD_KEY, key.to_stored());
+				account.set_field(PUBLIC_KEY_FIELD_KEY, key.get_public_key_line());
+			}
+			Err(_) => {
+				set_optional_field(account, PUBLIC_KEY_FIELD_KEY, &ssh_key.public_key);
+				if !private_key.is_empty() {
+					self.result.unmapped.push(get_texts().import.unsupported_ssh_key.replacen("{}", display_name, 1));
+				}
+			}
+		}
+	}
+
+	fn finish(mut self) -> ImportResult {
+		if self.history_count > 0 {
+			self.result.unmapped.insert(0, get_texts().import.password_history_skipped.replacen("{}", &self.history_count.to_string(), 1));
+		}
+		self.result
+	}
+}
+
+/// Identities have no entry type of their own, their details become fields of a note.
+fn import_identity(account: &mut Account, identity: &Identity) {
+	let fields = [
+		("title", &identity.title), ("first_name", &identity.first_name), ("middle_name", &identity.middle_name), ("last_name", &identity.last_name),
+		(USERNAME_FIELD_KEY, &identity.username), (EMAIL_FIELD_KEY, &identity.email), ("phone", &identity.phone), ("company", &identity.company),
+		("address_1", &identity.address1), ("address_2", &identity.address2), ("address_3", &identity.address3), ("city", &identity.city),
+		("state", &identity.state), ("postal_code", &identity.postal_code), ("country", &identity.country), ("ssn", &identity.ssn),
+		("passport_number", &identity.passport_number), ("license_number", &identity.license_number),
+	];
+	for (key, value) in fields {
+		set_optional_field(account, key, value);
+	}
+}
+
+fn set_optional_field(account: &mut Account, key: &str, value: &Option<String>) {
+	if let Some(value) = value.as_deref().filter(|value| !value.is_empty()) {
+		account.set_field(key, value.to_string());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Written with the Python cryptography package, the way Bitwarden protects exports with a password
+	const PBKDF2_EXPORT: &str = r#"{"encrypted":true,"passwordProtected":true,"salt":"c2FsdHNhbHRzYWx0","kdfType":0,"kdfIterations":1000,"encKeyValidation_DO_NOT_EDIT":"2.w2UrSnGo2PxY+2UxrrjqzA==|z8fOcgMGN0a9HIKGJkgRwQ==|GeBJdoe1MkBF7UMWl17n0wFDOdelpW8J68U5eTsPA5Q=","data":"2.UASVzQCAQxuSgUxp3Ck6Cg==|OTpmY/lMSW8qMmDYQCma9v+mB1pV+1ObFmEZ8Ua5NJXWNpsHih9fzRMKaSp607CffEDz3AN4Uv72zn8SYA9QoMwHVunTKd6LdggVr9SZmaiQxSmWUBdN0LJmTIjZX8L0jJA3NmnbwVjCbh6WoC65PS0iFoeOoKSFMr2YURJHQf7uXPbbC+O3w/Yx1zxxAnpKRPqFFpnMbdWEN2S3MxemrMNo+Cv0qDJDW0So1l3sHASdVzKnIzcvf66Q7ovRE+NWo5+voFDjsaks1I/YrP48ngkba3WI9sPpw9E/4MpNl7CFM9SwmN/ssUBM7xNE6UVLdkkikiuC2FtPgJEKJ0ucQfG0avB7mEkK7Gb1JCci94HSF2j7Q3FnrDWHfXHHJNYx|oYA8ME9RZJrGsYL9KksI3LX3yOefd95jK5kvQBtZlQA="}"#;
+	const ARGON2ID_EXPORT: &str = r#"{"encrypted":true,"passwordProtected":true,"salt":"c2FsdHNhbHRzYWx0","kdfType":1,"kdfIterations":2,"kdfMemory":16,"kdfParallelism":2,"encKeyValidation_DO_NOT_EDIT":"2.4O6JIxTkv/TjpbViu6olrQ==|uDyNezTNZcebWUkWACVzxg==|YFpw6tZLoeDFAV7JxBGHRwHwNlRwcORNtB5RQdBYxZg=","data":"2.+0w1Roe4DuSP8mPhN8peBg==|ItjAjf/FDTdje2x0JSZFHQPwRbsHJtGTZ1DsYU3lXfPl0pz9q6ePre9AEGAiYDrp8HX6YhOuaT1VFRSwvCp6wCkf5nMSXBBQiIwBlMseYv4xG11rgbRN7RtWqxXSMbhmKeD0NN2/9cON4z/LglrTJx5qyPKlevxbbzjG393ov9F92DcaKfTodGdo2ZF6aC8worAoxY7RKz2MxjBSIxKPf24PJgrmtKQ/EN+m4nrw3VPPXA19Hnz+cirBuYN//tiE/eVe/t5mq9Xxb9/KQV5/pECG2KMSCq6jjMG3RQYJ2SXsQwYA4TwNU0SO81l8ebED8YpBb/Ag5VveZli8cnP917GWW0AwXTcerQho6QvQ+/4f2S9O7KKLZml/EQvxSWIM|Lx9Us19DSUnYMZLwueMyUKjCh9rt8223nUezXG1xD/Y="}"#;
+
+	fn assert_github_entry(result: &ImportResult) {
+		assert_eq!(result.entries.len(), 1);
+		let account = &result.entries[0].account;
+		assert_eq!(account.account_name, "GitHub");
+		assert_eq!(account.password, "gh-pw");
+		assert_eq!(account.get_field(USERNAME_FIELD_KEY), Some("octo"));
+		assert_eq!(account.get_field(URL_FIELD_KEY), Some("https://github.com"));
+		assert_eq!(account.get_field(GROUP_FIELD_KEY), Some("Work"));
+	}
+
+	#[test]
+	fn decrypts_pbkdf2_export() {
+		assert!(is_password_protected(PBKDF2_EXPORT.as_bytes()).ok().unwrap());
+		assert_github_entry(&import_bitwarden(PBKDF2_EXPORT.as_bytes(), Some("export-pw")).ok().unwrap());
+	}
+
+	#[test]
+	fn decrypts_argon2id_export() {
+		assert_github_entry(&import_bitwarden(ARGON2ID_EXPORT.as_bytes(), Some("export-pw")).ok().unwrap());
+	}
+
+	#[test]
+	fn rejects_wrong_password() {
+		assert!(matches!(import_bitwarden(PBKDF2_EXPORT.as_bytes(), Some("wrong")), Err(BitwardenError::WrongPassword)));
+		assert!(matches!(import_bitwarden(PBKDF2_EXPORT.as_bytes(), None), Err(BitwardenError::WrongPassword)));
+	}
+
+	#[test]
+	fn tells_damaged_data_from_wrong_password() {
+		let mut export: serde_json::Value = serde_json::from_str(PBKDF2_EXPORT).unwrap();
+		let data = export["data"].as_str().unwrap().to_string();
+		let (encrypted, mac) = data.rsplit_once('|').unwrap();
+		let mut mac = BASE64.decode(mac).unwrap();
+		mac[0] ^= 1;
+		export["data"] = serde_json::Value::from(format!("{}|{}", encrypted, BASE64.encode(mac)));
+		let damaged = serde_json::to_vec(&export).unwrap();
+		assert!(matches!(import_bitwarden(&damaged, Some("export-pw")), Err(BitwardenError::Corrupted)));
+	}
+
+	#[test]
+	fn rejects_unsupported_kdf() {
+		let export = PBKDF2_EXPORT.replace("\"kdfIterations\":1000", "\"kdfIterations\":0");
+		assert!(matches!(import_bitwarden(export.as_bytes(), Some("export-pw")), Err(BitwardenError::UnsupportedKdf)));
+		let export = PBKDF2_EXPORT.replace("\"kdfType\":0", "\"kdfType\":7");
+		assert!(matches!(import_bitwarden(export.as_bytes(), Some("export-pw")), Err(BitwardenError::UnsupportedKdf)));
+	}
+
+	#[test]
+	fn rejects_account_encrypted_export() {
+		let export = r#"{"encrypted":true,"encKeyValidation_DO_NOT_EDIT":"2.x|y|z","folders":[],"items":[{"type":1,"name":"2.a|b|c"}]}"#;
+		assert!(!is_password_protected(export.as_bytes()).ok().unwrap());
+		assert!(matches!(import_bitwarden(export.as_bytes(), None), Err(BitwardenError::AccountEncrypted)));
+	}
+
+	#[test]
+	fn rejects_other_json() {
+		assert!(matches!(import_bitwarden(b"{\"name\":\"x\"}", None), Err(BitwardenError::NotBitwarden)));
+		assert!(matches!(import_bitwarden(b"no json", None), Err(BitwardenError::NotBitwarden)));
+	}
+}
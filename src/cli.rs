@@ -0,0 +1,481 @@
+use std::io::{stdin, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use crate::cloud_credential::{get_aws_credential, get_kubernetes_credential};
+use crate::database_context::{Account, DatabaseContext, DatabaseManager, NAME_FIELD_KEY, PASSWORD_FIELD_KEY};
+use crate::entry_templates::{find_template, FieldKind, FieldTemplate, ValidationError};
+use crate::entry_view::EntryView;
+use crate::file_accesssor::{read_file_from_disk, write_private_file_to_disk};
+use crate::password_generator::{generate_password, DEFAULT_PASSWORD_LENGTH};
+use crate::secret_env::run_with_secrets;
+use crate::secret_template::{render_template, TemplateError};
+use crate::ssh_key::{SshKey, PUBLIC_KEY_FIELD_KEY};
+use crate::texts::get_texts;
+use crate::vault_agent::{lookup_through_agent, send_agent_request, AgentRequest};
+use crate::vault_unlock::{prompt_and_unlock_vault, prompt_master_password, unlock_vault, UnlockError};
+
+/// Not a stored field, `get --field totp` prints the current one-time code.
+pub const TOTP_FIELD_KEY: &str = "totp";
+
+const DEFAULT_ENTRY_TYPE: &str = "login";
+
+#[derive(Parser)]
+#[command(name = "rpm", version, about = "Rusty Password Manager, starts the full-screen interface when no command is given")]
+pub struct Cli {
+	#[command(subcommand)]
+	pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+	/// List the entries, optionally only those whose name contains the filter
+	Ls {
+		filter: Option<String>,
+		/// Only list entries of this type, e.g. login
+		#[arg(long = "type")]
+		entry_type: Option<String>,
+	},
+	/// Print all fields of an entry, hidden fields are masked unless --reveal is given
+	Show {
+		name: String,
+		#[arg(long)]
+		user: Option<String>,
+		#[arg(long)]
+		reveal: bool,
+	},
+	/// Print a single field of an entry, the password by default
+	Get {
+		name: String,
+		#[arg(long)]
+		user: Option<String>,
+		#[arg(long, default_value = PASSWORD_FIELD_KEY)]
+		field: String,
+	},
+	/// Add an entry, required hidden fields which are not given are asked for on the terminal
+	Add {
+		name: String,
+		#[arg(long = "type", default_value = DEFAULT_ENTRY_TYPE)]
+		entry_type: String,
+		#[command(flatten)]
+		changes: FieldChanges,
+		/// Add the entry even if there already is one with the same name and username
+		#[arg(long)]
+		force: bool,
+	},
+	/// Change fields of an entry
+	Edit {
+		name: String,
+		#[arg(long)]
+		user: Option<String>,
+		#[arg(long, value_name = "NEW_NAME")]
+		rename: Option<String>,
+		#[command(flatten)]
+		changes: FieldChanges,
+	},
+	/// Remove an entry
+	Rm {
+		name: String,
+		#[arg(long)]
+		user: Option<String>,
+		/// Do not ask for confirmation
+		#[arg(long, short)]
+		yes: bool,
+	},
+	/// Run a command with secrets of the vault in its environment, their values are masked in its output
+	Run {
+		/// Set a variable, e.g. --env DB_PASSWORD=prod-db/password, without a field the password is used
+		#[arg(long = "env", value_name = "VAR=ENTRY/FIELD", value_parser = parse_env_reference, required = true)]
+		variables: Vec<(String, String)>,
+		/// The command and its arguments, after --
+		#[arg(last = true, required = true, value_name = "COMMAND")]
+		command: Vec<String>,
+	},
+	/// Render a file with {{ rpm://ENTRY/FIELD }} placeholders, to stdout unless an output file is given
+	Template {
+		/// The template file, - reads it from stdin
+		input: PathBuf,
+		/// Write the rendered file, only readable by the current user
+		#[arg(long, short)]
+		output: Option<PathBuf>,
+	},
+	/// Print the credentials of an entry for the credential_process setting of AWS tools or as a kubectl exec plugin
+	CredentialProcess {
+		name: String,
+		#[arg(long)]
+		user: Option<String>,
+		#[arg(long, value_enum, default_value_t = CredentialFormat::Aws)]
+		format: CredentialFormat,
+	},
+	/// Print a random password
+	Generate {
+		#[arg(long, short, default_value_t = DEFAULT_PASSWORD_LENGTH)]
+		length: usize,
+		/// Only use letters and digits
+		#[arg(long)]
+		no_special_chars: bool,
+	},
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum CredentialFormat {
+	Aws,
+	Kubernetes,
+}
+
+#[derive(Args)]
+pub struct FieldChanges {
+	/// Set a field, e.g. --field username=alice
+	#[arg(long = "field", value_name = "KEY=VALUE", value_parser = parse_field_assignment)]
+	fields: Vec<(String, String)>,
+	/// Read a field from the terminal without echo, which keeps secrets out of the shell history
+	#[arg(long = "prompt", value_name = "KEY")]
+	prompts: Vec<String>,
+	/// Generate the password, and the key for entries holding an SSH key
+	#[arg(long)]
+	generate: bool,
+}
+
+fn parse_env_reference(value: &str) -> Result<(String, String), String> {
+	match value.split_once('=') {
+		Some((variable, reference)) if !variable.is_empty() && !reference.is_empty() => Ok((variable.to_string(), reference.to_string())),
+		_ => Err(get_texts().cli.invalid_env_reference.clone()),
+	}
+}
+
+fn parse_field_assignment(value: &str) -> Result<(String, String), String> {
+	match value.split_once('=') {
+		Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+		_ => Err(get_texts().cli.invalid_assignment.clone()),
+	}
+}
+
+pub enum CliError {
+	Unlock(UnlockError),
+	/// The running agent could not look up a value, with its message.
+	Agent(String),
+	NotFound(String),
+	Ambiguous(String, Vec<Account>),
+	UnknownType(String),
+	UnknownField(String),
+	EmptyField(String),
+	InvalidField(String, ValidationError),
+	AlreadyExists(String),
+	PromptFailed,
+	CommandFailed(String, String),
+	ReadFailed(PathBuf, String),
+	WriteFailed(PathBuf, String),
+	UnresolvedReferences(Vec<TemplateError>),
+	Aborted,
+}
+
+impl CliError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().cli;
+		match self {
+			CliError::Unlock(e) => e.get_message(),
+			CliError::Agent(message) => message.clone(),
+			CliError::NotFound(name) => format!("{} {}", texts.not_found, name),
+			CliError::Ambiguous(name, accounts) => {
+				let usernames: Vec<&str> = accounts.iter().map(|account| account.get_username()).collect();
+				format!("{} {}", texts.ambiguous.replace("{}", name), usernames.join(", "))
+			}
+			CliError::UnknownType(entry_type) => format!("{} {}", texts.unknown_type, entry_type),
+			CliError::UnknownField(key) => format!("{} {}", texts.unknown_field, key),
+			CliError::EmptyField(key) => format!("{} {}", texts.empty_field, key),
+			CliError::InvalidField(label, e) => format!("{} {}", label, e.get_message()),
+			CliError::AlreadyExists(name) => texts.already_exists.replace("{}", name),
+			CliError::PromptFailed => texts.prompt_failed.clone(),
+			CliError::CommandFailed(program, e) => format!("{} {}: {}", texts.command_failed, program, e),
+			CliError::ReadFailed(path, e) => format!("{} {}: {}", texts.read_failed, path.display(), e),
+			CliError::WriteFailed(path, e) => format!("{} {}: {}", texts.write_failed, path.display(), e),
+			CliError::UnresolvedReferences(errors) => {
+				let lines: Vec<String> = errors.iter().map(|error| format!("  {} {}: {}: {}", texts.line_label, error.line, error.reference, error.message)).collect();
+				format!("{}\n{}", texts.unresolved_references, lines.join("\n"))
+			}
+			CliError::Aborted => texts.aborted.clone(),
+		}
+	}
+}
+
+impl From<UnlockError> for CliError {
+	fn from(error: UnlockError) -> Self {
+		CliError::Unlock(error)
+	}
+}
+
+/// Runs a subcommand and returns the exit code of the program.
+pub fn run_command(command: Command) -> Result<i32, CliError> {
+	match command {
+		Command::Generate { length, no_special_chars } => {
+			println!("{}", generate_password(length, !no_special_chars));
+			Ok(0)
+		}
+		Command::Get { name, user, field } => {
+			println!("{}", ValueSource::open()?.get_value(&name, user.as_deref(), &field)?);
+			Ok(0)
+		}
+		Command::Run { variables, command } => {
+			let variables = resolve_env_references(&variables)?;
+			let (program, args) = command.split_first().expect("The command is required");
+			run_with_secrets(program, args, &variables, &get_texts().cli.hidden_value)
+				.map_err(|e| CliError::CommandFailed(program.clone(), e.to_string()))
+		}
+		Command::Template { input, output } => {
+			render_template_file(&input, output.as_deref())?;
+			Ok(0)
+		}
+		command => {
+			let db_manager = prompt_and_unlock_vault()?;
+			run_vault_command(command, &db_manager)?;
+			Ok(0)
+		}
+	}
+}
+
+/// Where single values are looked up. The running agent answers lookups itself,
+/// so the vault is only opened in this process when there is no agent.
+enum ValueSource {
+	Agent,
+	Vault(DatabaseManager),
+}
+
+impl ValueSource {
+	fn open() -> Result<Self, CliError> {
+		if send_agent_request(&AgentRequest::Status).is_some() {
+			return Ok(ValueSource::Agent);
+		}
+		Ok(ValueSource::Vault(unlock_vault(&prompt_master_password()?)?))
+	}
+
+	fn get_value(&self, name: &str, user: Option<&str>, field: &str) -> Result<String, CliError> {
+		match self {
+			ValueSource::Agent => match lookup_through_agent(name, user, field) {
+				Some(result) => result.map_err(CliError::Agent),
+				None => Err(CliError::Unlock(UnlockError::AgentLocked)),
+			},
+			ValueSource::Vault(db_manager) => {
+				let db_context = match db_manager.get_database_context() {
+					Some(context) => context,
+					None => panic!("Database not initialized"),
+				};
+				get_field_value(&find_account(db_context, name, user)?, field)
+			}
+		}
+	}
+
+	/// Resolves an ENTRY/FIELD reference, without a field the password of the entry is used.
+	fn resolve_reference(&self, reference: &str) -> Result<String, CliError> {
+		let (name, field) = reference.rsplit_once('/').unwrap_or((reference, PASSWORD_FIELD_KEY));
+		self.get_value(name, None, field)
+	}
+}
+
+/// Looks up the values of ENTRY/FIELD references. The vault is closed again before the command starts.
+fn resolve_env_references(references: &[(String, String)]) -> Result<Vec<(String, String)>, CliError> {
+	let value_source = ValueSource::open()?;
+	references.iter().map(|(variable, reference)| Ok((variable.clone(), value_source.resolve_reference(reference)?))).collect()
+}
+
+fn render_template_file(input: &Path, output: Option<&Path>) -> Result<(), CliError> {
+	let texts = &get_texts().cli;
+	let content = read_template(input).map_err(|e| CliError::ReadFailed(input.to_path_buf(), e.to_string()))?;
+	let value_source = ValueSource::open()?;
+	let rendered = render_template(&content, &texts.unterminated_reference, |reference| {
+		value_source.resolve_reference(reference).map_err(|e| e.get_message())
+	}).map_err(CliError::UnresolvedReferences)?;
+	match output {
+		Some(path) => {
+			write_private_file_to_disk(path, rendered.as_bytes()).map_err(|e| CliError::WriteFailed(path.to_path_buf(), e.to_string()))?;
+			println!("{} {}", texts.rendered_msg, path.display());
+		}
+		None => print!("{}", rendered),
+	}
+	Ok(())
+}
+
+fn run_vault_command(command: Command, db_manager: &DatabaseManager) -> Result<(), CliError> {
+	let db_context = match db_manager.get_database_context() {
+		Some(context) => context,
+		None => panic!("Database not initialized"),
+	};
+	let texts = &get_texts().cli;
+
+	match command {
+		Command::Ls { filter, entry_type } => {
+			let accounts = match filter {
+				Some(filter) => db_context.search_accounts_by_name(&filter).unwrap(),
+				None => db_context.list_all_accounts().unwrap(),
+			};
+			for account in accounts.iter().filter(|account| entry_type.as_ref().is_none_or(|entry_type| &account.entry_type == entry_type)) {
+				println!("{}\t{}\t{}", account.account_name, account.get_username(), account.entry_type);
+			}
+		}
+		Command::Show { name, user, reveal } => {
+			let account = find_account(db_context, &name, user.as_deref())?;
+			print_account(&account, reveal);
+		}
+		Command::Add { name, entry_type, changes, force } => {
+			let template = find_template(&entry_type).ok_or(CliError::UnknownType(entry_type.clone()))?;
+			let mut account = Account::new(&template.key);
+			account.rotation_days = template.rotation_days;
+			account.account_name = name;
+			apply_changes(&mut account, &changes, true)?;
+
+			if !force && !db_context.find_accounts_by_identity(&account.account_name, account.get_username()).unwrap().is_empty() {
+				return Err(CliError::AlreadyExists(account.account_name));
+			}
+			db_context.add_account(&account).unwrap();
+			db_manager.try_safe_database().map_err(CliError::Agent)?;
+			println!("{} {}", texts.added_msg, account.account_name);
+		}
+		Command::Edit { name, user, rename, changes } => {
+			let mut account = find_account(db_context, &name, user.as_deref())?;
+			if let Some(new_name) = rename {
+				account.account_name = new_name;
+			}
+			apply_changes(&mut account, &changes, false)?;
+			db_context.update_account(&account);
+			db_manager.try_safe_database().map_err(CliError::Agent)?;
+			println!("{} {}", texts.updated_msg, account.account_name);
+		}
+		Command::Rm { name, user, yes } => {
+			let account = find_account(db_context, &name, user.as_deref())?;
+			if !yes && !ask_confirmation(&texts.remove_question.replace("{}", &account.account_name)) {
+				return Err(CliError::Aborted);
+			}
+			db_context.remove_account(account.id).unwrap();
+			db_manager.try_safe_database().map_err(CliError::Agent)?;
+			println!("{} {}", texts.removed_msg, account.account_name);
+		}
+		Command::CredentialProcess { name, user, format } => {
+			let account = find_account(db_context, &name, user.as_deref())?;
+			let missing_field = |key: &str| CliError::EmptyField(key.to_string());
+			let output = match format {
+				CredentialFormat::Aws => serde_json::to_string(&get_aws_credential(&account).map_err(missing_field)?),
+				CredentialFormat::Kubernetes => serde_json::to_string(&get_kubernetes_credential(&account).map_err(missing_field)?),
+			};
+			println!("{}", output.expect("Credentials are serializable"));
+		}
+		Command::Generate { .. } | Command::Get { .. } | Command::Run { .. } | Command::Template { .. } => {}
+	}
+	Ok(())
+}
+
+/// Resolves an entry by its exact name, the username tells apart entries of the same service.
+pub fn find_account(db_context: &DatabaseContext, name: &str, user: Option<&str>) -> Result<Account, CliError> {
+	let mut accounts = db_context.find_accounts_by_name(name).unwrap();
+	if let Some(user) = user {
+		accounts.retain(|account| account.get_username().eq_ignore_ascii_case(user.trim()));
+	}
+	match accounts.len() {
+		0 => Err(CliError::NotFound(name.to_string())),
+		1 => Ok(accounts.remove(0)),
+		_ => Err(CliError::Ambiguous(name.to_string(), accounts)),
+	}
+}
+
+/// Reads a field by its key, including the current one-time code of the entry.
+pub fn get_field_value(account: &Account, key: &str) -> Result<String, CliError> {
+	if key == TOTP_FIELD_KEY {
+		return account.totp.as_ref().and_then(|totp| totp.generate_code(SystemTime::now())).ok_or(CliError::EmptyField(key.to_string()));
+	}
+	match account.get_field(key) {
+		Some(value) if !value.is_empty() => Ok(value.to_string()),
+		_ => Err(CliError::EmptyField(key.to_string())),
+	}
+}
+
+fn print_account(account: &Account, reveal: bool) {
+	let texts = &get_texts().cli;
+	let entry_view = EntryView::new(account);
+	let label_width = entry_view.get_rows().iter().map(|row| row.label.chars().count()).max().unwrap_or(0).max(texts.type_label.chars().count());
+
+	let type_name = find_template(&account.entry_type).map(|template| template.name.as_str()).unwrap_or(&account.entry_type);
+	println!("{:width$} {}", texts.type_label, type_name, width = label_width);
+	for row in entry_view.get_rows() {
+		let value = account.get_field(&row.key).unwrap_or_default();
+		if value.is_empty() {
+			continue;
+		}
+		let value = if row.is_hidden() && !reveal { texts.hidden_value.as_str() } else { value };
+		println!("{:width$} {}", row.label, value, width = label_width);
+	}
+	if let Some(code) = account.totp.as_ref().and_then(|totp| totp.generate_code(SystemTime::now())) {
+		println!("{:width$} {}", texts.totp_label, code, width = label_width);
+	}
+}
+
+/// Sets the given fields, reads the prompted ones from the terminal and validates every changed field.
+/// New entries also get their empty required fields filled, generated or asked for.
+/// Given values are checked before anything is asked for, so a typo does not cost the typed secrets.
+fn apply_changes(account: &mut Account, changes: &FieldChanges, is_new: bool) -> Result<(), CliError> {
+	let rows = EntryView::new(account).get_rows().clone();
+	let find_row = |key: &str| rows.iter().find(|row| row.key == key).ok_or(CliError::UnknownField(key.to_string()));
+
+	validate_field(find_row(NAME_FIELD_KEY)?, account)?;
+	for (key, value) in &changes.fields {
+		let row = find_row(key)?;
+		account.set_field(key, value.clone());
+		validate_field(row, account)?;
+	}
+	for key in &changes.prompts {
+		let row = find_row(key)?;
+		account.set_field(key, prompt_field(row)?);
+		validate_field(row, account)?;
+	}
+	if changes.generate {
+		let prompted = |key: &str| changes.prompts.iter().any(|prompt| prompt == key) || changes.fields.iter().any(|(field, _)| field == key);
+		for row in &rows {
+			match row.kind {
+				FieldKind::Secret if row.key == PASSWORD_FIELD_KEY && !prompted(&row.key) => {
+					account.set_field(&row.key, generate_password(DEFAULT_PASSWORD_LENGTH, true));
+				}
+				FieldKind::SshKey if account.get_field(&row.key).unwrap_or_default().is_empty() => {
+					let ssh_key = SshKey::generate(&account.account_name);
+					account.set_field(PUBLIC_KEY_FIELD_KEY, ssh_key.get_public_key_line());
+					account.set_field(&row.key, ssh_key.to_stored());
+				}
+				_ => {}
+			}
+		}
+	}
+	if is_new {
+		for row in &rows {
+			let is_empty = account.get_field(&row.key).unwrap_or_default().is_empty();
+			if is_empty && row.required && row.is_hidden() && row.kind != FieldKind::SshKey {
+				account.set_field(&row.key, prompt_field(row)?);
+			}
+			validate_field(row, account)?;
+		}
+	}
+	Ok(())
+}
+
+fn validate_field(row: &FieldTemplate, account: &Account) -> Result<(), CliError> {
+	row.validate(account.get_field(&row.key).unwrap_or_default()).map_err(|e| CliError::InvalidField(row.label.clone(), e))
+}
+
+fn read_template(path: &Path) -> std::io::Result<String> {
+	if path.as_os_str() == "-" {
+		let mut content = String::new();
+		stdin().read_to_string(&mut content)?;
+		return Ok(content);
+	}
+	let data = read_file_from_disk(path)?;
+	String::from_utf8(data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn prompt_field(row: &FieldTemplate) -> Result<String, CliError> {
+	rpassword::prompt_password(format!("{} ", row.label)).map_err(|_| CliError::PromptFailed)
+}
+
+fn ask_confirmation(question: &str) -> bool {
+	eprint!("{} ", question);
+	let _ = std::io::stderr().flush();
+	let mut answer = String::new();
+	if stdin().read_line(&mut answer).is_err() {
+		return false;
+	}
+	matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use crate::database_context::{Account, PASSWORD_FIELD_KEY};
+use crate::date_util::format_date;
+
+pub const ACCESS_KEY_FIELD_KEY: &str = "access_key_id";
+pub const SESSION_TOKEN_FIELD_KEY: &str = "session_token";
+
+/// kubectl describes the request in this variable, the response has to use the same api version.
+const KUBERNETES_EXEC_INFO_VARIABLE: &str = "KUBERNETES_EXEC_INFO";
+const KUBERNETES_API_VERSIONS: [&str; 2] = ["client.authentication.k8s.io/v1", "client.authentication.k8s.io/v1beta1"];
+const EXEC_CREDENTIAL_KIND: &str = "ExecCredential";
+/// The version of the credential_process output format, AWS only knows version 1.
+const AWS_OUTPUT_VERSION: u32 = 1;
+
+/// The output AWS tools expect from a credential_process command.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct AwsProcessCredential {
+	pub version: u32,
+	pub access_key_id: String,
+	pub secret_access_key: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub session_token: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub expiration: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesExecCredential {
+	pub api_version: String,
+	pub kind: String,
+	pub status: KubernetesCredentialStatus,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesCredentialStatus {
+	pub token: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub expiration_timestamp: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KubernetesExecInfo {
+	api_version: String,
+}
+
+/// Both formats expect an RFC 3339 timestamp. Expiry dates have no time, so the credentials
+/// are treated as expired from the start of that day on.
+fn get_expiration(account: &Account) -> Option<String> {
+	account.expires_at.map(|expires_at| format!("{}T00:00:00Z", format_date(expires_at)))
+}
+
+fn get_required_field(account: &Account, key: &'static str) -> Result<String, &'static str> {
+	account.get_field(key).filter(|value| !value.is_empty()).map(str::to_string).ok_or(key)
+}
+
+/// Builds the credential_process output of an entry, the secret access key is its password.
+/// Returns the key of the missing field if the entry lacks the access key or the secret.
+pub fn get_aws_credential(account: &Account) -> Result<AwsProcessCredential, &'static str> {
+	Ok(AwsProcessCredential {
+		version: AWS_OUTPUT_VERSION,
+		access_key_id: get_required_field(account, ACCESS_KEY_FIELD_KEY)?,
+		secret_access_key: get_required_field(account, PASSWORD_FIELD_KEY)?,
+		session_token: account.get_field(SESSION_TOKEN_FIELD_KEY).filter(|token| !token.is_empty()).map(str::to_string),
+		expiration: get_expiration(account),
+	})
+}
+
+/// Builds the ExecCredential kubectl expects from an exec plugin, the token is the password of the entry.
+/// The api version is taken from the request kubectl passes in the environment, v1 if there is none.
+pub fn get_kubernetes_credential(account: &Account) -> Result<KubernetesExecCredential, &'static str> {
+	let requested_version = std::env::var(KUBERNETES_EXEC_INFO_VARIABLE).ok()
+		.and_then(|exec_info| serde_json::from_str::<KubernetesExecInfo>(&exec_info).ok())
+		.map(|exec_info| exec_info.api_version);
+	let api_version = match requested_version {
+		Some(version) if KUBERNETES_API_VERSIONS.contains(&version.as_str()) => version,
+		_ => KUBERNETES_API_VERSIONS[0].to_string(),
+	};
+	Ok(KubernetesExecCredential {
+		api_version,
+		kind: EXEC_CREDENTIAL_KIND.to_string(),
+		status: KubernetesCredentialStatus {
+			token: get_required_field(account, PASSWORD_FIELD_KEY)?,
+			expiration_timestamp: get_expiration(account),
+		},
+	})
+}
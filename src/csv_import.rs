@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use crate::database_context::{Account, EMAIL_FIELD_KEY, NOTES_FIELD_KEY, URL_FIELD_KEY, USERNAME_FIELD_KEY};
+use crate::file_accesssor::{read_csv_mappings_from_disk, write_csv_mappings_to_disk};
+use crate::texts::get_texts;
+use crate::vault_import::{ImportResult, ImportedEntry};
+
+const IMPORTED_ENTRY_TYPE: &str = "login";
+const DELIMITERS: [u8; 3] = [b',', b';', b'\t'];
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// What the values of a CSV column are imported as.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnTarget {
+	Skip,
+	Name,
+	Username,
+	Email,
+	Password,
+	Url,
+	Notes,
+	/// A custom field named after the column header.
+	Field,
+}
+
+impl ColumnTarget {
+	pub const ALL: [ColumnTarget; 8] = [ColumnTarget::Skip, ColumnTarget::Name, ColumnTarget::Username, ColumnTarget::Email,
+		ColumnTarget::Password, ColumnTarget::Url, ColumnTarget::Notes, ColumnTarget::Field];
+
+	pub fn get_name(&self) -> &'static str {
+		let texts = &get_texts().import;
+		match self {
+			ColumnTarget::Skip => &texts.csv_skip,
+			ColumnTarget::Name => &texts.csv_name,
+			ColumnTarget::Username => &texts.csv_username,
+			ColumnTarget::Email => &texts.csv_email,
+			ColumnTarget::Password => &texts.csv_password,
+			ColumnTarget::Url => &texts.csv_url,
+			ColumnTarget::Notes => &texts.csv_notes,
+			ColumnTarget::Field => &texts.csv_field,
+		}
+	}
+
+	/// Recognizes the column names used by browsers and common password managers.
+	fn guess(header: &str) -> ColumnTarget {
+		let normalized: String = header.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+		match normalized.as_str() {
+			"name" | "title" | "account" | "accountname" | "itemname" => ColumnTarget::Name,
+			"username" | "user" | "login" | "loginname" | "loginusername" | "userid" => ColumnTarget::Username,
+			"email" | "emailaddress" | "mail" => ColumnTarget::Email,
+			"password" | "pass" | "pwd" | "loginpassword" => ColumnTarget::Password,
+			"url" | "uri" | "website" | "site" | "weburl" | "loginuri" => ColumnTarget::Url,
+			"notes" | "note" | "extra" | "comment" | "comments" | "description" => ColumnTarget::Notes,
+			_ => ColumnTarget::Skip,
+		}
+	}
+}
+
+pub enum CsvImportError {
+	Invalid,
+	Empty,
+	NoPasswordColumn,
+}
+
+impl CsvImportError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().import;
+		match self {
+			CsvImportError::Invalid => texts.invalid_csv.clone(),
+			CsvImportError::Empty => texts.empty_csv.clone(),
+			CsvImportError::NoPasswordColumn => texts.no_password_column.clone(),
+		}
+	}
+}
+
+pub struct CsvFile {
+	pub headers: Vec<String>,
+	pub rows: Vec<Vec<String>>,
+}
+
+impl CsvFile {
+	/// Reads the header line and all records. Spreadsheet programs in some regions separate
+	/// the values with semicolons or tabs, whichever occurs most in the header line is used.
+	pub fn read(data: &[u8]) -> Result<Self, CsvImportError> {
+		let data = data.strip_prefix(UTF8_BOM).unwrap_or(data);
+		let header_line = data.split(|byte| *byte == b'\n').next().unwrap_or_default();
+		// The last of equally frequent delimiters wins, so without any the comma is used
+		let delimiter = DELIMITERS.into_iter().rev().max_by_key(|delimiter| count_unquoted(header_line, *delimiter)).unwrap();
+
+		let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).flexible(true).from_reader(data);
+		let headers: Vec<String> = reader.headers().map_err(|_| CsvImportError::Invalid)?.iter().map(|header| header.trim().to_string()).collect();
+		let mut rows = Vec::new();
+		for record in reader.records() {
+			let record = record.map_err(|_| CsvImportError::Invalid)?;
+			if record.iter().any(|value| !value.trim().is_empty()) {
+				rows.push(record.iter().map(str::to_string).collect());
+			}
+		}
+		if headers.iter().all(String::is_empty) || rows.is_empty() {
+			return Err(CsvImportError::Empty);
+		}
+		Ok(Self { headers, rows })
+	}
+
+	pub fn get_value(&self, row: usize, column: usize) -> &str {
+		self.rows[row].get(column).map(String::as_str).unwrap_or_default()
+	}
+}
+
+/// Counts the byte outside of quoted values, a quoted header may contain any of the delimiters.
+fn count_unquoted(line: &[u8], byte: u8) -> usize {
+	let mut quoted = false;
+	let mut count = 0;
+	for current in line {
+		if *current == b'"' {
+			quoted = !quoted;
+		} else if *current == byte && !quoted {
+			count += 1;
+		}
+	}
+	count
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct MappingFile {
+	#[serde(default)]
+	mappings: Vec<ColumnMapping>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ColumnMapping {
+	headers: Vec<String>,
+	targets: Vec<ColumnTarget>,
+}
+
+fn read_mapping_file() -> MappingFile {
+	read_csv_mappings_from_disk().and_then(|content| toml::from_str(&content).ok()).unwrap_or_default()
+}
+
+/// Returns the mapping last used for files with the same header line, or a guess based on the column names.
+pub fn get_column_targets(headers: &[String]) -> Vec<ColumnTarget> {
+	let remembered = read_mapping_file().mappings.into_iter().find(|mapping| mapping.headers == headers && mapping.targets.len() == headers.len());
+	match remembered {
+		Some(mapping) => mapping.targets,
+		None => headers.iter().map(|header| ColumnTarget::guess(header)).collect(),
+	}
+}
+
+/// Stores the mapping, so the next export of the same application is mapped the same way.
+pub fn remember_column_targets(headers: &[String], targets: &[ColumnTarget]) {
+	let mut file = read_mapping_file();
+	file.mappings.retain(|mapping| mapping.headers != headers);
+	file.mappings.push(ColumnMapping { headers: headers.to_vec(), targets: targets.to_vec() });
+	write_csv_mappings_to_disk(toml::to_string(&file).expect("Could not serialize CSV mappings"));
+}
+
+/// Turns every row into a login entry. Entries without a name column are named after their URL.
+pub fn import_csv(file: &CsvFile, targets: &[ColumnTarget]) -> Result<ImportResult, CsvImportError> {
+	if !targets.contains(&ColumnTarget::Password) {
+		return Err(CsvImportError::NoPasswordColumn);
+	}
+
+	let texts = &get_texts().import;
+	let mut result = ImportResult::default();
+	for row in 0..file.rows.len() {
+		let mut account = Account::new(IMPORTED_ENTRY_TYPE);
+		let mut notes: Vec<&str> = Vec::new();
+		for (column, target) in targets.iter().enumerate() {
+			let value = file.get_value(row, column).trim();
+			if value.is_empty() {
+				continue;
+			}
+			let key = match target {
+				ColumnTarget::Skip => continue,
+				ColumnTarget::Notes => {
+					notes.push(value);
+					continue;
+				}
+				ColumnTarget::Name if account.account_name.is_empty() => {
+					account.account_name = value.to_string();
+					continue;
+				}
+				ColumnTarget::Password if account.password.is_empty() => {
+					account.password = value.to_string();
+					continue;
+				}
+				ColumnTarget::Name | ColumnTarget::Password => &file.headers[column],
+				ColumnTarget::Username => USERNAME_FIELD_KEY,
+				ColumnTarget::Email => EMAIL_FIELD_KEY,
+				ColumnTarget::Url => URL_FIELD_KEY,
+				ColumnTarget::Field => &file.headers[column],
+			};
+			let key = if key.is_empty() { texts.untitled.as_str() } else { key };
+			if account.get_field(key).is_some_and(|value| !value.is_empty()) {
+				let display_name = texts.csv_row.replacen("{}", &(row + 1).to_string(), 1);
+				result.unmapped.push(texts.field_conflict.replacen("{}", &display_name, 1).replacen("{}", &file.headers[column], 1));
+				continue;
+			}
+			account.set_field(key, value.to_string());
+		}
+		if !notes.is_empty() {
+			account.set_field(NOTES_FIELD_KEY, notes.join("\n"));
+		}
+		if account.account_name.is_empty() {
+			account.account_name = account.get_field(URL_FIELD_KEY).map(get_host).filter(|host| !host.is_empty())
+				.unwrap_or(&texts.untitled).to_string();
+		}
+		result.entries.push(ImportedEntry { account, attachments: Vec::new() });
+	}
+	Ok(result)
+}
+
+/// The host of a URL, e.g. "example.com" for "https://user@example.com:8080/login".
+fn get_host(url: &str) -> &str {
+	let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+	let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+	let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+	host.split(':').next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn read(content: &str) -> CsvFile {
+		CsvFile::read(content.as_bytes()).ok().unwrap()
+	}
+
+	#[test]
+	fn detects_comma_semicolon_and_tab() {
+		for content in ["name,url,password\nMail,https://mail.example.com,secret\n", "name;url;password\nMail;https://mail.example.com;secret\n",
+			"name\turl\tpassword\nMail\thttps://mail.example.com\tsecret\n"] {
+			let file = read(content);
+			assert_eq!(file.headers, vec!["name", "url", "password"]);
+			assert_eq!(file.rows, vec![vec!["Mail", "https://mail.example.com", "secret"]]);
+		}
+	}
+
+	#[test]
+	fn ignores_delimiters_inside_quoted_headers() {
+		let file = read("\"Name, full\";\"Login, user\";Password\nMail;bob;\"a,b,c\"\n");
+		assert_eq!(file.headers, vec!["Name, full", "Login, user", "Password"]);
+		assert_eq!(file.rows, vec![vec!["Mail", "bob", "a,b,c"]]);
+	}
+
+	#[test]
+	fn prefers_comma_on_equal_counts() {
+		assert_eq!(read("name;x,password\nMail;y,secret\n").headers, vec!["name;x", "password"]);
+		assert_eq!(read("password\nsecret\n").headers, vec!["password"]);
+	}
+
+	#[test]
+	fn skips_bom_crlf_and_empty_rows() {
+		let file = read("\u{FEFF}name,password\r\nMail,secret\r\n,\r\n\r\nBank,pin\r\n");
+		assert_eq!(file.headers, vec!["name", "password"]);
+		assert_eq!(file.rows, vec![vec!["Mail", "secret"], vec!["Bank", "pin"]]);
+	}
+
+	#[test]
+	fn rejects_files_without_entries() {
+		assert!(matches!(CsvFile::read(b"name,password\n"), Err(CsvImportError::Empty)));
+		assert!(matches!(CsvFile::read(b""), Err(CsvImportError::Empty)));
+	}
+
+	#[test]
+	fn guesses_column_targets() {
+		let targets: Vec<ColumnTarget> = ["Title", "Login Username", "E-Mail", "login_password", "Website", "Comments", "Folder"].iter()
+			.map(|header| ColumnTarget::guess(header)).collect();
+		assert!(targets == [ColumnTarget::Name, ColumnTarget::Username, ColumnTarget::Email, ColumnTarget::Password,
+			ColumnTarget::Url, ColumnTarget::Notes, ColumnTarget::Skip]);
+	}
+
+	#[test]
+	fn names_entries_after_their_host() {
+		assert_eq!(get_host("https://user@example.com:8080/login?next=1"), "example.com");
+		assert_eq!(get_host("example.org/path"), "example.org");
+	}
+}
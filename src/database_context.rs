@@ -1,18 +1,141 @@
 use rusqlite::types::Value;
-use rusqlite::{params, Connection, Result, Row};
+use std::cell::Cell;
+use rusqlite::{params, Connection, Params, Result, Row};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::date_util::days_to_duration;
+use crate::totp::{TotpAlgorithm, TotpConfig, DEFAULT_DIGITS, DEFAULT_PERIOD};
+use ring::aead::NONCE_LEN;
 use crate::encryption_controller::{encrypt_database, load_encrypted_db, PasswordEncryption};
 use crate::file_accesssor::{create_directory_and_files, read_db_from_disk, write_db_to_disk};
 
+const ACCOUNT_COLUMNS: &str = "id, account_name, password, email, created_at, updated_at, entry_type, \
+	password_changed_at, expires_at, rotation_days, pinned, last_used_at, \
+	totp_secret, totp_digits, totp_period, totp_algorithm, ssh_confirm";
+const ACCOUNT_ORDER: &str = "ORDER BY pinned DESC, last_used_at IS NULL, last_used_at DESC, account_name COLLATE NOCASE";
+
+pub const NAME_FIELD_KEY: &str = "name";
+pub const PASSWORD_FIELD_KEY: &str = "password";
+pub const EMAIL_FIELD_KEY: &str = "email";
+pub const USERNAME_FIELD_KEY: &str = "username";
+pub const URL_FIELD_KEY: &str = "url";
+pub const NOTES_FIELD_KEY: &str = "notes";
+/// The folder of an imported entry, nested folders are separated by "/".
+pub const GROUP_FIELD_KEY: &str = "group";
+pub const TAGS_FIELD_KEY: &str = "tags";
+/// Passwords an imported entry had before, the newest first and one per line.
+pub const PASSWORD_HISTORY_FIELD_KEY: &str = "password_history";
+
+#[derive(Clone)]
+pub struct EntryField {
+	pub key: String,
+	pub value: String,
+	/// Shown hidden like a password, for custom fields which were protected where they were imported from.
+	pub hidden: bool,
+}
+
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct Account {
 	pub id: i32,
+	pub entry_type: String,
 	pub account_name: String,
 	pub password: String,
 	pub email: Option<String>,
+	pub fields: Vec<EntryField>,
 	pub created_at: SystemTime,
 	pub updated_at: SystemTime,
+	pub password_changed_at: SystemTime,
+	pub expires_at: Option<SystemTime>,
+	pub rotation_days: Option<u32>,
+	pub pinned: bool,
+	pub last_used_at: Option<SystemTime>,
+	pub totp: Option<TotpConfig>,
+	pub ssh_confirm: bool,
+}
+
+impl Account {
+	pub fn new(entry_type: &str) -> Self {
+		Account {
+			id: 0,
+			entry_type: entry_type.to_string(),
+			account_name: String::new(),
+			password: String::new(),
+			email: None,
+			fields: Vec::new(),
+			created_at: SystemTime::now(),
+			updated_at: SystemTime::now(),
+			password_changed_at: SystemTime::now(),
+			expires_at: None,
+			rotation_days: None,
+			pinned: false,
+			last_used_at: None,
+			totp: None,
+			ssh_confirm: true,
+		}
+	}
+
+	/// Returns the date at which the password has to be changed, either because the entry expires
+	/// or because its rotation interval has passed since the last password change.
+	pub fn get_rotation_due_date(&self) -> Option<SystemTime> {
+		let rotation_date = self.rotation_days.map(|days| self.password_changed_at + days_to_duration(days));
+		match (self.expires_at, rotation_date) {
+			(Some(expires_at), Some(rotation_date)) => Some(expires_at.min(rotation_date)),
+			(expires_at, rotation_date) => expires_at.or(rotation_date),
+		}
+	}
+
+	/// Reads a field by its template key. Name, password and email are stored in their own columns,
+	/// every other key is looked up in the custom fields of the entry.
+	pub fn get_field(&self, key: &str) -> Option<&str> {
+		match key {
+			NAME_FIELD_KEY => Some(&self.account_name),
+			PASSWORD_FIELD_KEY => Some(&self.password),
+			EMAIL_FIELD_KEY => self.email.as_deref(),
+			_ => self.fields.iter().find(|field| field.key == key).map(|field| field.value.as_str()),
+		}
+	}
+
+	/// The user an entry belongs to, which is the username field or the email if there is none.
+	pub fn get_username(&self) -> &str {
+		match self.get_field(USERNAME_FIELD_KEY) {
+			Some(username) if !username.is_empty() => username,
+			_ => self.email.as_deref().unwrap_or_default(),
+		}
+	}
+
+	pub fn set_field(&mut self, key: &str, value: String) {
+		match key {
+			NAME_FIELD_KEY => self.account_name = value,
+			PASSWORD_FIELD_KEY => self.password = value,
+			EMAIL_FIELD_KEY => self.email = if value.is_empty() { None } else { Some(value) },
+			_ => {
+				if let Some(field) = self.fields.iter_mut().find(|field| field.key == key) {
+					field.value = value;
+				} else {
+					self.fields.push(EntryField { key: key.to_string(), value, hidden: false });
+				}
+			}
+		}
+	}
+
+	/// Sets a custom field which is shown hidden, like a password.
+	pub fn set_hidden_field(&mut self, key: &str, value: String) {
+		self.set_field(key, value);
+		if let Some(field) = self.fields.iter_mut().find(|field| field.key == key) {
+			field.hidden = true;
+		}
+	}
+}
+
+/// A file stored encrypted inside the vault. The content is only loaded on demand.
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct Attachment {
+	pub id: i32,
+	pub account_id: i32,
+	pub file_name: String,
+	pub size: i64,
+	pub created_at: SystemTime,
 }
 
 pub enum DatabaseState {
@@ -24,6 +147,19 @@ pub enum DatabaseState {
 pub struct DatabaseManager {
 	state: DatabaseState,
 	passkey: [u8; 32],
+	/// Changes which are not worth writing the vault for on their own, like the last use of an entry.
+	pending_changes: Cell<bool>,
+	/// Saves the vault instead of encrypting it here, for a vault whose key is kept by another process.
+	save_handler: Option<SaveHandler>,
+}
+
+/// Hands the vault to the process which keeps the key, an error is the message why it was not saved.
+pub type SaveHandler = Box<dyn Fn(&DatabaseContext) -> std::result::Result<(), String> + Send>;
+
+impl Default for DatabaseManager {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl DatabaseManager {
@@ -31,6 +167,8 @@ impl DatabaseManager {
 		DatabaseManager {
 			state: DatabaseState::Empty,
 			passkey: [0; 32],
+			pending_changes: Cell::new(false),
+			save_handler: None,
 		}
 	}
 
@@ -62,18 +200,79 @@ impl DatabaseManager {
 		self.state = DatabaseState::Unlocked(context);
 	}
 
+	/// Takes over a vault another process already decrypted and keeps the key of, see vault_agent.
+	pub fn unlock_from_dump(&mut self, dump: &str, save_handler: SaveHandler) {
+		let context = DatabaseContext::restore_db(dump.as_bytes().to_vec()).expect("Failed to restore db");
+		self.save_handler = Some(save_handler);
+		self.state = DatabaseState::Unlocked(context);
+	}
+
+	/// Replaces the unlocked vault with one changed by another process, false if the dump cannot be restored.
+	pub fn restore_from_dump(&mut self, dump: &str) -> bool {
+		if !matches!(self.state, DatabaseState::Unlocked(_)) {
+			return false;
+		}
+		match DatabaseContext::restore_db(dump.as_bytes().to_vec()) {
+			Ok(context) => {
+				self.state = DatabaseState::Unlocked(context);
+				true
+			}
+			Err(_) => false,
+		}
+	}
+
+	/// Decrypts the vault on disk again with the current key. Fails if the file is gone
+	/// or was encrypted with another key, because the master password was changed meanwhile.
+	pub fn reload_from_disk(&mut self) -> bool {
+		let encrypted_db = match read_db_from_disk() {
+			Some(encrypted_db) if encrypted_db.len() > NONCE_LEN => encrypted_db,
+			_ => return false,
+		};
+		match load_encrypted_db(encrypted_db, &self.passkey) {
+			Ok(db_content) => {
+				self.state = DatabaseState::Unlocked(DatabaseContext::restore_db(db_content).expect("Failed to restore db"));
+				true
+			}
+			Err(_) => false,
+		}
+	}
+
 	pub fn set_new_passkey(&mut self, key: &PasswordEncryption){
 		self.passkey = key.get_encrypted_string().clone()
 	}
 
 	pub fn safe_database(&self) {
+		self.try_safe_database().expect("Failed to save db")
+	}
+
+	/// Like safe_database, but a vault saved through its save handler may fail, e.g. when the agent locked meanwhile.
+	pub fn try_safe_database(&self) -> std::result::Result<(), String> {
 		let context = match &self.state {
-			DatabaseState::Empty => return,
-			DatabaseState::Locked(_) => return,
+			DatabaseState::Empty => return Ok(()),
+			DatabaseState::Locked(_) => return Ok(()),
 			DatabaseState::Unlocked(context) => context,
 		};
-		let encrypted_db = encrypt_database(context, &self.passkey).expect("Failed to encrypt db");
-		write_db_to_disk(encrypted_db);
+		match &self.save_handler {
+			Some(save_database) => save_database(context)?,
+			None => write_db_to_disk(encrypt_database(context, &self.passkey).expect("Failed to encrypt db")),
+		}
+		self.pending_changes.set(false);
+		Ok(())
+	}
+
+	/// Remembers the use of an entry in memory only, it is written with the next save.
+	pub fn mark_account_used(&self, id: i32) {
+		if let Some(context) = self.get_database_context() {
+			context.mark_account_used(id).unwrap();
+			self.pending_changes.set(true);
+		}
+	}
+
+	/// Writes changes which were kept in memory so far, if there are any.
+	pub fn save_pending_changes(&self) {
+		if self.pending_changes.get() {
+			self.safe_database();
+		}
 	}
 
 	pub fn get_database_context(&self) -> Option<&DatabaseContext> {
@@ -105,16 +304,67 @@ impl DatabaseContext {
 			)", [],
 		)?;
 
-		Ok(DatabaseContext { conn })
+		let context = DatabaseContext { conn };
+		context.upgrade_schema()?;
+		Ok(context)
 	}
 
 	pub fn restore_db(plain_data: Vec<u8>) -> Result<Self> {
 		let conn = Connection::open_in_memory()?;
 		let sql_dump = String::from_utf8(plain_data).expect("Failed to parse db dump");
-		match conn.execute_batch(&sql_dump) {
-			Ok(()) => Ok(DatabaseContext { conn }),
+		let context = match conn.execute_batch(&sql_dump) {
+			Ok(()) => DatabaseContext { conn },
 			Err(e) => panic!("Failed to restore db: {}, dump => \n{}", e.to_string(), sql_dump)
+		};
+		context.upgrade_schema()?;
+		Ok(context)
+	}
+
+	/// Brings databases created by older versions up to the current schema.
+	/// New columns are appended to the existing tables, so the dump stays restorable.
+	fn upgrade_schema(&self) -> Result<()> {
+		self.conn.execute(
+			"CREATE TABLE IF NOT EXISTS entry_fields (\
+				id              INTEGER PRIMARY KEY AUTOINCREMENT,
+				account_id      INTEGER NOT NULL,
+				field_key       TEXT NOT NULL,
+				field_value     TEXT NOT NULL
+			)", [],
+		)?;
+
+		self.conn.execute(
+			"CREATE TABLE IF NOT EXISTS attachments (\
+				id              INTEGER PRIMARY KEY AUTOINCREMENT,
+				account_id      INTEGER NOT NULL,
+				file_name       TEXT NOT NULL,
+				data            BLOB NOT NULL,
+				created_at      INTEGER DEFAULT CURRENT_TIMESTAMP
+			)", [],
+		)?;
+
+		self.add_column_if_missing("accounts", "entry_type", "TEXT NOT NULL DEFAULT 'login'")?;
+		self.add_column_if_missing("accounts", "password_changed_at", "INTEGER")?;
+		self.add_column_if_missing("accounts", "expires_at", "INTEGER")?;
+		self.add_column_if_missing("accounts", "rotation_days", "INTEGER")?;
+		self.add_column_if_missing("accounts", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+		self.add_column_if_missing("accounts", "last_used_at", "INTEGER")?;
+		self.add_column_if_missing("accounts", "totp_secret", "TEXT")?;
+		self.add_column_if_missing("accounts", "totp_digits", "INTEGER")?;
+		self.add_column_if_missing("accounts", "totp_period", "INTEGER")?;
+		self.add_column_if_missing("accounts", "totp_algorithm", "TEXT")?;
+		self.add_column_if_missing("accounts", "ssh_confirm", "INTEGER NOT NULL DEFAULT 1")?;
+		self.add_column_if_missing("entry_fields", "hidden", "INTEGER NOT NULL DEFAULT 0")?;
+		Ok(())
+	}
+
+	fn add_column_if_missing(&self, table_name: &str, column_name: &str, definition: &str) -> Result<()> {
+		let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table_name))?;
+		let column_names = stmt.query_map([], |row| row.get::<_, String>(1))?.collect::<Result<Vec<String>>>()?;
+
+		if !column_names.iter().any(|name| name == column_name) {
+			self.conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table_name, column_name, definition), [])?;
 		}
+		Ok(())
 	}
 
 	pub fn dump_db(&self) -> Result<String> {
@@ -147,7 +397,7 @@ impl DatabaseContext {
 			let mut data_rows = data_stmt.query([])?;
 
 			while let Some(data_row) = data_rows.next()? {
-				let column_count = 6;
+				let column_count = data_row.as_ref().column_count();
 				let mut values = Vec::new();
 
 				for i in 0..column_count {
@@ -157,9 +407,7 @@ impl DatabaseContext {
 						Value::Integer(val) => val.to_string(),
 						Value::Real(val) => val.to_string(),
 						Value::Text(val) => format!("'{}'", val.replace("'", "''")),
-						Value::Blob(_) => {
-							"X''".to_string()
-						}
+						Value::Blob(val) => format!("X'{}'", bytes_to_hex(&val)),
 					});
 				}
 				let insert_statement = format!("INSERT INTO {} VALUES ({});", table_name, values.join(", "));
@@ -171,99 +419,231 @@ impl DatabaseContext {
 	}
 
 
-	pub fn add_account(&self, account_name: &str, password: &str, email: Option<String>) -> Result<()> {
-		let current_time = system_time_to_timestamp(SystemTime::now());
-		match self.conn.execute(
-			"INSERT INTO accounts (account_name, password, email, created_at, updated_at)\
-			VALUES (?1, ?2, ?3, ?4, ?5)",
-			params![account_name, password, email, current_time, current_time],
-		) {
-			Ok(_) => Ok(()),
-			Err(e) => panic!("SQL Query error: {}", e.to_string())
-		}
-	}
-
-	pub fn get_account_by_id(&self, id: i32) -> Result<Option<Account>> {
-		let mut stmt = self.conn.prepare(
-			"SELECT id, account_name, password, email, created_at, updated_at FROM accounts WHERE id = ?1",
+	/// Inserts a new entry. Its timestamps are stored as they are, so imported entries keep their dates.
+	pub fn add_account(&self, account: &Account) -> Result<i32> {
+		self.conn.execute(
+			"INSERT INTO accounts (account_name, password, email, created_at, updated_at, entry_type, \
+				password_changed_at, expires_at, rotation_days, pinned, ssh_confirm, totp_secret, totp_digits, totp_period, totp_algorithm)\
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+			params![account.account_name, account.password, account.email, system_time_to_timestamp(account.created_at),
+				system_time_to_timestamp(account.updated_at), account.entry_type,
+				system_time_to_timestamp(account.password_changed_at), account.expires_at.map(system_time_to_timestamp), account.rotation_days,
+				account.pinned, account.ssh_confirm, account.totp.as_ref().map(|totp| &totp.secret), account.totp.as_ref().map(|totp| totp.digits),
+				account.totp.as_ref().map(|totp| totp.period as i64), account.totp.as_ref().map(|totp| totp.algorithm.get_name())],
 		)?;
 
-		let mut account_iter = stmt.query_map(params![id], |row| {
-			self.create_account_from_row(row)
-		})?;
-
-		if let Some(account) = account_iter.next() {
-			return Ok(Some(account?));
-		}
+		let id = self.conn.last_insert_rowid() as i32;
+		self.write_entry_fields(id, &account.fields)?;
+		Ok(id)
+	}
 
-		Ok(None)
+	pub fn get_account_by_id(&self, id: i32) -> Result<Option<Account>> {
+		let accounts = self.query_accounts("WHERE id = ?1", params![id])?;
+		Ok(accounts.into_iter().next())
 	}
 
 	pub fn update_account(&self, account: &Account) {
 		let current_time = system_time_to_timestamp(SystemTime::now());
 		if self.conn.execute(
 			"UPDATE accounts SET \
+				password_changed_at = CASE WHEN password = ?2 THEN password_changed_at ELSE ?4 END,\
 				account_name = ?1,\
 				password = ?2,\
 				email = ?3,\
-				updated_at = ?4\
-				WHERE id = ?5",
-			params![account.account_name, account.password, account.email, current_time, account.id],
+				updated_at = ?4,\
+				entry_type = ?5,\
+				expires_at = ?6,\
+				rotation_days = ?7,\
+				totp_secret = ?8,\
+				totp_digits = ?9,\
+				totp_period = ?10,\
+				totp_algorithm = ?11\
+				WHERE id = ?12",
+			params![account.account_name, account.password, account.email, current_time, account.entry_type,
+				account.expires_at.map(system_time_to_timestamp), account.rotation_days,
+				account.totp.as_ref().map(|totp| &totp.secret), account.totp.as_ref().map(|totp| totp.digits),
+				account.totp.as_ref().map(|totp| totp.period as i64), account.totp.as_ref().map(|totp| totp.algorithm.get_name()),
+				account.id],
 		).is_err() {
 			panic!("Updating account failed!");
 		}
+
+		if self.write_entry_fields(account.id, &account.fields).is_err() {
+			panic!("Updating account fields failed!");
+		}
 	}
 
 	pub fn remove_account(&self, id: i32) -> Result<()> {
+		self.conn.execute("DELETE FROM entry_fields WHERE account_id = ?1", params![id])?;
+		self.conn.execute("DELETE FROM attachments WHERE account_id = ?1", params![id])?;
 		self.conn.execute("DELETE FROM accounts WHERE id = ?1", params![id])?;
 		Ok(())
 	}
 
 	pub fn search_accounts_by_name(&self, name_part: &str) -> Result<Vec<Account>> {
-		let mut stmt = self.conn.prepare(
-			"SELECT id, account_name, password, email, created_at, updated_at FROM accounts WHERE account_name LIKE ?1",
-		)?;
+		self.query_accounts(&format!("WHERE account_name LIKE ?1 {}", ACCOUNT_ORDER), params![format!("%{}%",name_part)])
+	}
 
-		let account_iter = stmt.query_map(params![format!("%{}%",name_part)], |row| {
-			self.create_account_from_row(row)
-		})?;
 
-		let mut accounts = Vec::new();
-		for account in account_iter {
-			accounts.push(account?);
-		}
-		Ok(accounts)
+	/// Finds the entries with exactly this name, ignoring case.
+	pub fn find_accounts_by_name(&self, name: &str) -> Result<Vec<Account>> {
+		self.query_accounts(&format!("WHERE account_name = ?1 COLLATE NOCASE {}", ACCOUNT_ORDER), params![name.trim()])
 	}
 
+	/// Finds the entries of a service for the given user, both compared exactly but ignoring case.
+	pub fn find_accounts_by_identity(&self, name: &str, username: &str) -> Result<Vec<Account>> {
+		let accounts = self.find_accounts_by_name(name)?;
+		let username = username.trim().to_lowercase();
+		Ok(accounts.into_iter().filter(|account| account.get_username().trim().to_lowercase() == username).collect())
+	}
 
+	/// Lists all accounts, pinned ones first and then by their most recent use.
 	pub fn list_all_accounts(&self) -> Result<Vec<Account>> {
+		self.query_accounts(ACCOUNT_ORDER, [])
+	}
+
+	pub fn mark_account_used(&self, id: i32) -> Result<()> {
+		let current_time = system_time_to_timestamp(SystemTime::now());
+		self.conn.execute("UPDATE accounts SET last_used_at = ?1 WHERE id = ?2", params![current_time, id])?;
+		Ok(())
+	}
+
+	pub fn set_account_pinned(&self, id: i32, pinned: bool) -> Result<()> {
+		self.conn.execute("UPDATE accounts SET pinned = ?1 WHERE id = ?2", params![pinned, id])?;
+		Ok(())
+	}
+
+	/// Sets whether the ssh agent asks before it signs with the key of this entry.
+	pub fn set_ssh_confirm(&self, id: i32, confirm: bool) -> Result<()> {
+		self.conn.execute("UPDATE accounts SET ssh_confirm = ?1 WHERE id = ?2", params![confirm, id])?;
+		Ok(())
+	}
+
+	/// Lists all accounts whose password expires or has to be rotated within the given number of days,
+	/// the most urgent ones first.
+	pub fn list_accounts_due_for_rotation(&self, within_days: u32) -> Result<Vec<Account>> {
+		let deadline = SystemTime::now() + days_to_duration(within_days);
+		let mut accounts: Vec<Account> = self.list_all_accounts()?.into_iter()
+			.filter(|account| account.get_rotation_due_date().is_some_and(|due_date| due_date <= deadline))
+			.collect();
+		accounts.sort_by_key(|account| account.get_rotation_due_date());
+		Ok(accounts)
+	}
+
+	pub fn add_attachment(&self, account_id: i32, file_name: &str, data: &[u8]) -> Result<i32> {
+		let current_time = system_time_to_timestamp(SystemTime::now());
+		self.conn.execute(
+			"INSERT INTO attachments (account_id, file_name, data, created_at) VALUES (?1, ?2, ?3, ?4)",
+			params![account_id, file_name, data, current_time],
+		)?;
+		Ok(self.conn.last_insert_rowid() as i32)
+	}
+
+	pub fn list_attachments(&self, account_id: i32) -> Result<Vec<Attachment>> {
 		let mut stmt = self.conn.prepare(
-			"SELECT id, account_name, password, email, created_at, updated_at FROM accounts ",
+			"SELECT id, account_id, file_name, length(data), created_at FROM attachments WHERE account_id = ?1 ORDER BY file_name",
 		)?;
+		let attachment_iter = stmt.query_map(params![account_id], |row| {
+			Ok(Attachment {
+				id: row.get(0)?,
+				account_id: row.get(1)?,
+				file_name: row.get(2)?,
+				size: row.get(3)?,
+				created_at: timestamp_to_system_time(row.get(4)?),
+			})
+		})?;
+		attachment_iter.collect()
+	}
 
-		let account_iter = stmt.query_map([], |row| {
+	pub fn get_attachment_data(&self, id: i32) -> Result<Vec<u8>> {
+		self.conn.query_row("SELECT data FROM attachments WHERE id = ?1", params![id], |row| row.get(0))
+	}
+
+	pub fn remove_attachment(&self, id: i32) -> Result<()> {
+		self.conn.execute("DELETE FROM attachments WHERE id = ?1", params![id])?;
+		Ok(())
+	}
+
+	fn query_accounts<P: Params>(&self, condition: &str, query_params: P) -> Result<Vec<Account>> {
+		let mut stmt = self.conn.prepare(&format!("SELECT {} FROM accounts {}", ACCOUNT_COLUMNS, condition))?;
+
+		let account_iter = stmt.query_map(query_params, |row| {
 			self.create_account_from_row(row)
 		})?;
 
 		let mut accounts = Vec::new();
 		for account in account_iter {
-			accounts.push(account?);
+			let mut account = account?;
+			account.fields = self.read_entry_fields(account.id)?;
+			accounts.push(account);
 		}
 		Ok(accounts)
 	}
 
+	fn read_entry_fields(&self, account_id: i32) -> Result<Vec<EntryField>> {
+		let mut stmt = self.conn.prepare("SELECT field_key, field_value, hidden FROM entry_fields WHERE account_id = ?1 ORDER BY id")?;
+		let field_iter = stmt.query_map(params![account_id], |row| {
+			Ok(EntryField {
+				key: row.get(0)?,
+				value: row.get(1)?,
+				hidden: row.get(2)?,
+			})
+		})?;
+		field_iter.collect()
+	}
+
+	fn write_entry_fields(&self, account_id: i32, fields: &[EntryField]) -> Result<()> {
+		self.conn.execute("DELETE FROM entry_fields WHERE account_id = ?1", params![account_id])?;
+		for field in fields.iter().filter(|field| !field.value.is_empty()) {
+			self.conn.execute(
+				"INSERT INTO entry_fields (account_id, field_key, field_value, hidden) VALUES (?1, ?2, ?3, ?4)",
+				params![account_id, field.key, field.value, field.hidden],
+			)?;
+		}
+		Ok(())
+	}
+
 	fn create_account_from_row(&self, row: &Row) -> Result<Account> {
+		let updated_at = timestamp_to_system_time(row.get(5)?);
+		let totp = match row.get::<_, Option<String>>(12)? {
+			Some(secret) => Some(TotpConfig {
+				secret,
+				digits: row.get::<_, Option<u32>>(13)?.unwrap_or(DEFAULT_DIGITS),
+				period: row.get::<_, Option<i64>>(14)?.map(|period| period as u64).unwrap_or(DEFAULT_PERIOD),
+				algorithm: row.get::<_, Option<String>>(15)?.and_then(|name| TotpAlgorithm::from_name(&name)).unwrap_or_default(),
+			}),
+			None => None,
+		};
 		Ok(Account {
 			id: row.get(0)?,
 			account_name: row.get(1)?,
 			password: row.get(2)?,
 			email: row.get(3)?,
 			created_at: timestamp_to_system_time(row.get(4)?),
-			updated_at: timestamp_to_system_time(row.get(5)?),
+			updated_at,
+			entry_type: row.get(6)?,
+			fields: Vec::new(),
+			password_changed_at: row.get::<_, Option<i64>>(7)?.map(timestamp_to_system_time).unwrap_or(updated_at),
+			expires_at: row.get::<_, Option<i64>>(8)?.map(timestamp_to_system_time),
+			rotation_days: row.get(9)?,
+			pinned: row.get(10)?,
+			last_used_at: row.get::<_, Option<i64>>(11)?.map(timestamp_to_system_time),
+			totp,
+			ssh_confirm: row.get(16)?,
 		})
 	}
 }
 
+fn bytes_to_hex(bytes: &[u8]) -> String {
+	const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+	let mut hex = String::with_capacity(bytes.len() * 2);
+	for byte in bytes {
+		hex.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+		hex.push(HEX_DIGITS[(byte & 0x0F) as usize] as char);
+	}
+	hex
+}
+
 fn system_time_to_timestamp(time: SystemTime) -> i64 {
 	time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
 }
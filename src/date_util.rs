@@ -0,0 +1,98 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Formats the date of the given time as YYYY-MM-DD in UTC.
+pub fn format_date(time: SystemTime) -> String {
+	let days = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() / SECONDS_PER_DAY).unwrap_or(0);
+	let (year, month, day) = civil_from_days(days as i64);
+	format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Parses a date in the format YYYY-MM-DD, the result is midnight of that day in UTC.
+pub fn parse_date(date: &str) -> Option<SystemTime> {
+	let mut parts = date.trim().split('-');
+	let year: i64 = parts.next()?.parse().ok()?;
+	let month: u32 = parts.next()?.parse().ok()?;
+	let day: u32 = parts.next()?.parse().ok()?;
+	if parts.next().is_some() || !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+		return None;
+	}
+
+	let days = days_from_civil(year, month, day);
+	Some(UNIX_EPOCH + Duration::from_secs(days as u64 * SECONDS_PER_DAY))
+}
+
+/// Parses a UTC timestamp like 2024-01-15T10:20:30.123Z, fractions of a second are dropped.
+pub fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+	let (date, time) = timestamp.trim().split_once('T')?;
+	let time = time.strip_suffix('Z').or_else(|| time.strip_suffix("+00:00"))?;
+	let mut parts = time.split('.').next()?.split(':');
+	let hours: u64 = parts.next()?.parse().ok()?;
+	let minutes: u64 = parts.next()?.parse().ok()?;
+	let seconds: u64 = parts.next()?.parse().ok()?;
+	if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+		return None;
+	}
+	Some(parse_date(date)? + Duration::from_secs(hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Seconds since the unix epoch, times before it become 0.
+pub fn to_timestamp(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+/// The time the given seconds after the unix epoch, None if it is too far in the future to be represented.
+pub fn from_timestamp(timestamp: u64) -> Option<SystemTime> {
+	UNIX_EPOCH.checked_add(Duration::from_secs(timestamp))
+}
+
+pub fn days_to_duration(days: u32) -> Duration {
+	Duration::from_secs(days as u64 * SECONDS_PER_DAY)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+	match month {
+		2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31,
+	}
+}
+
+// Conversions between days since 1970-01-01 and the proleptic gregorian calendar,
+// see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let month = month as i64;
+	let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let days = days + 719468;
+	let era = days.div_euclid(146097);
+	let day_of_era = days - era * 146097;
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+	let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+	(year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn converts_timestamps() {
+		assert_eq!(from_timestamp(1_700_000_000).map(to_timestamp), Some(1_700_000_000));
+		assert_eq!(from_timestamp(u64::MAX), None);
+		assert_eq!(format_date(from_timestamp(951_782_400).unwrap()), "2000-02-29");
+	}
+}
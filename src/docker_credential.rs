@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::database_context::{Account, DatabaseContext, URL_FIELD_KEY, USERNAME_FIELD_KEY};
+
+/// The entry type registry logins are stored as, see the container_registry template.
+pub const REGISTRY_ENTRY_TYPE: &str = "container_registry";
+
+/// Docker only treats a failed get as "no credentials" when it prints exactly this message.
+pub const CREDENTIALS_NOT_FOUND: &str = "credentials not found in native keychain";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DockerCredential {
+	#[serde(rename = "ServerURL")]
+	pub server_url: String,
+	#[serde(rename = "Username")]
+	pub username: String,
+	#[serde(rename = "Secret")]
+	pub secret: String,
+}
+
+/// Docker passes the registry as it appears in its config, with or without scheme and trailing slash.
+fn normalize_server_url(server_url: &str) -> String {
+	let server_url = server_url.trim().to_lowercase();
+	let server_url = server_url.strip_prefix("https://").or(server_url.strip_prefix("http://")).unwrap_or(&server_url);
+	server_url.trim_end_matches('/').to_string()
+}
+
+fn get_registry_name(server_url: &str) -> String {
+	normalize_server_url(server_url).split('/').next().unwrap_or_default().to_string()
+}
+
+fn list_registry_accounts(db_context: &DatabaseContext) -> Vec<Account> {
+	db_context.list_all_accounts().unwrap().into_iter().filter(|account| account.entry_type == REGISTRY_ENTRY_TYPE).collect()
+}
+
+fn find_registry_accounts(db_context: &DatabaseContext, server_url: &str) -> Vec<Account> {
+	let server_url = normalize_server_url(server_url);
+	list_registry_accounts(db_context).into_iter()
+		.filter(|account| account.get_field(URL_FIELD_KEY).is_some_and(|url| normalize_server_url(url) == server_url))
+		.collect()
+}
+
+pub fn get_credential(db_context: &DatabaseContext, server_url: &str) -> Option<DockerCredential> {
+	let account = find_registry_accounts(db_context, server_url).into_iter().next()?;
+	Some(DockerCredential {
+		server_url: server_url.trim().to_string(),
+		username: account.get_username().to_string(),
+		secret: account.password,
+	})
+}
+
+/// Docker keeps a single login per registry, so an existing entry is overwritten.
+pub fn store_credential(db_context: &DatabaseContext, credential: &DockerCredential) {
+	match find_registry_accounts(db_context, &credential.server_url).into_iter().next() {
+		Some(mut account) => {
+			account.set_field(USERNAME_FIELD_KEY, credential.username.clone());
+			account.password = credential.secret.clone();
+			db_context.update_account(&account);
+		}
+		None => {
+			let mut account = Account::new(REGISTRY_ENTRY_TYPE);
+			account.account_name = get_registry_name(&credential.server_url);
+			account.password = credential.secret.clone();
+			account.set_field(URL_FIELD_KEY, credential.server_url.trim().to_string());
+			account.set_field(USERNAME_FIELD_KEY, credential.username.clone());
+			db_context.add_account(&account).unwrap();
+		}
+	}
+}
+
+/// Returns whether an entry was removed.
+pub fn erase_credential(db_context: &DatabaseContext, server_url: &str) -> bool {
+	let accounts = find_registry_accounts(db_context, server_url);
+	for account in &accounts {
+		db_context.remove_account(account.id).unwrap();
+	}
+	!accounts.is_empty()
+}
+
+/// Maps every stored registry to its username.
+pub fn list_credentials(db_context: &DatabaseContext) -> BTreeMap<String, String> {
+	list_registry_accounts(db_context).into_iter()
+		.filter_map(|account| Some((account.get_field(URL_FIELD_KEY)?.to_string(), account.get_username().to_string())))
+		.collect()
+}
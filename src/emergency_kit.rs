@@ -0,0 +1,222 @@
+use std::time::SystemTime;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crate::date_util::format_date;
+use crate::encryption_controller::{PasswordEncryption, DATABASE_FORMAT_VERSION, PBKDF2_ITERATIONS};
+use crate::file_accesssor::{get_base_dir, get_db_file_path, get_password_file_path, read_password_from_disk};
+use crate::qr_code::{render_printable_qr_code, render_qr_code_svg};
+use crate::texts::get_texts;
+use crate::xml_util::escape_xml;
+
+const KDF_NAME: &str = "PBKDF2-HMAC-SHA256";
+const KEY_LENGTH: usize = 32;
+/// Characters of the recovery key printed together, to make copying it by hand easier.
+const KEY_GROUP_SIZE: usize = 4;
+/// Lines left blank on the text kit to write down the master password.
+const PASSWORD_LINE: &str = "________________________________________________";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum KitFormat {
+	Html,
+	Text,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmergencyKitError {
+	/// The salt printed on the kit is read from the key file of the vault.
+	KeyFileUnreadable,
+}
+
+impl EmergencyKitError {
+	pub fn get_message(&self) -> String {
+		match self {
+			EmergencyKitError::KeyFileUnreadable => get_texts().export.key_file_unreadable.clone(),
+		}
+	}
+}
+
+/// Everything printed on the kit, labels are taken from the texts.
+struct KitSection {
+	heading: &'static str,
+	rows: Vec<(&'static str, String)>,
+}
+
+fn get_file_name(path: std::path::PathBuf) -> String {
+	path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn read_salt() -> Result<[u8; 16], EmergencyKitError> {
+	let pwd_string = read_password_from_disk().ok_or(EmergencyKitError::KeyFileUnreadable)?;
+	let master_password = PasswordEncryption::create_from_string(pwd_string).map_err(|_| EmergencyKitError::KeyFileUnreadable)?;
+	Ok(master_password.get_salt())
+}
+
+fn get_sections(salt: &[u8]) -> Vec<KitSection> {
+	let texts = &get_texts().emergency_kit;
+	vec![
+		KitSection {
+			heading: &texts.vault_heading,
+			rows: vec![
+				(&texts.location, get_base_dir().display().to_string()),
+				(&texts.database_file, get_file_name(get_db_file_path())),
+				(&texts.key_file, get_file_name(get_password_file_path())),
+				(&texts.format, texts.format_value.replacen("{}", &DATABASE_FORMAT_VERSION.to_string(), 1)),
+			],
+		},
+		KitSection {
+			heading: &texts.kdf_heading,
+			rows: vec![
+				(&texts.kdf_function, KDF_NAME.to_string()),
+				(&texts.kdf_iterations, PBKDF2_ITERATIONS.to_string()),
+				(&texts.kdf_salt, BASE64.encode(salt)),
+				(&texts.kdf_key_length, texts.key_length_value.replacen("{}", &KEY_LENGTH.to_string(), 1)),
+			],
+		},
+	]
+}
+
+fn format_recovery_key(key: &[u8; KEY_LENGTH]) -> String {
+	key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn group_recovery_key(key: &str) -> String {
+	key.as_bytes().chunks(KEY_GROUP_SIZE).map(|group| String::from_utf8_lossy(group).to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn push_text_heading(kit: &mut String, heading: &str) {
+	kit.push_str(&format!("\n{}\n{}\n", heading, "-".repeat(heading.chars().count())));
+}
+
+/// Writes the emergency kit to print. The recovery key is the key the database is encrypted with,
+/// it is only included if given.
+pub fn create_emergency_kit(format: KitFormat, recovery_key: Option<&[u8; KEY_LENGTH]>) -> Result<Vec<u8>, EmergencyKitError> {
+	let sections = get_sections(&read_salt()?);
+	let recovery_key = recovery_key.map(format_recovery_key);
+	let kit = match format {
+		KitFormat::Html => create_html_kit(&sections, recovery_key.as_deref()),
+		KitFormat::Text => create_text_kit(&sections, recovery_key.as_deref()),
+	};
+	Ok(kit.into_bytes())
+}
+
+fn create_text_kit(sections: &[KitSection], recovery_key: Option<&str>) -> String {
+	let texts = &get_texts().emergency_kit;
+	let mut kit = format!("{}\n{}\n\n{}\n\n{} {}\n", texts.title, "=".repeat(texts.title.chars().count()), texts.intro,
+		texts.created, format_date(SystemTime::now()));
+	for section in sections {
+		push_text_heading(&mut kit, section.heading);
+		let label_width = section.rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or_default();
+		for (label, value) in &section.rows {
+			kit.push_str(&format!("{:width$}  {}\n", label, value, width = label_width));
+		}
+	}
+
+	push_text_heading(&mut kit, &texts.master_password_heading);
+	kit.push_str(&format!("{}\n\n{}\n\n{}\n", texts.master_password_hint, PASSWORD_LINE, PASSWORD_LINE));
+
+	if let Some(key) = recovery_key {
+		push_text_heading(&mut kit, &texts.recovery_key_heading);
+		kit.push_str(&format!("{}\n\n", texts.recovery_key_hint));
+		kit.push_str(&format!("{}\n\n", group_recovery_key(key)));
+		for line in render_printable_qr_code(key).unwrap_or_default() {
+			kit.push_str(&format!("{}\n", line));
+		}
+	}
+
+	push_text_heading(&mut kit, &texts.instructions_heading);
+	for (idx, instruction) in texts.instructions.iter().enumerate() {
+		kit.push_str(&format!("{}. {}\n", idx + 1, instruction));
+	}
+	if recovery_key.is_some() {
+		kit.push_str(&format!("{}. {}\n", texts.instructions.len() + 1, texts.recovery_key_instruction));
+	}
+	kit
+}
+
+fn create_html_kit(sections: &[KitSection], recovery_key: Option<&str>) -> String {
+	let texts = &get_texts().emergency_kit;
+	let mut kit = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+	kit.push_str(&format!("<title>{}</title>\n", escape_xml(&texts.title)));
+	kit.push_str("<style>\n\
+		body { font-family: sans-serif; max-width: 48em; margin: 2em auto; }\n\
+		th { text-align: left; padding-right: 2em; font-weight: normal; color: #555; }\n\
+		td, .key { font-family: monospace; }\n\
+		.blank { border: 1px solid #000; height: 4em; margin: 1em 0; }\n\
+		.key { font-size: 1.3em; word-spacing: 0.5em; }\n\
+		</style>\n</head>\n<body>\n");
+	kit.push_str(&format!("<h1>{}</h1>\n<p>{}</p>\n<p>{} {}</p>\n", escape_xml(&texts.title), escape_xml(&texts.intro),
+		escape_xml(&texts.created), format_date(SystemTime::now())));
+
+	for section in sections {
+		kit.push_str(&format!("<h2>{}</h2>\n<table>\n", escape_xml(section.heading)));
+		for (label, value) in &section.rows {
+			kit.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", escape_xml(label), escape_xml(value)));
+		}
+		kit.push_str("</table>\n");
+	}
+
+	kit.push_str(&format!("<h2>{}</h2>\n<p>{}</p>\n<div class=\"blank\"></div>\n",
+		escape_xml(&texts.master_password_heading), escape_xml(&texts.master_password_hint)));
+
+	if let Some(key) = recovery_key {
+		kit.push_str(&format!("<h2>{}</h2>\n<p>{}</p>\n", escape_xml(&texts.recovery_key_heading), escape_xml(&texts.recovery_key_hint)));
+		kit.push_str(&format!("<p class=\"key\">{}</p>\n", group_recovery_key(key)));
+		if let Some(svg) = render_qr_code_svg(key) {
+			// Drop the XML declaration, the image is embedded into the document
+			let svg = svg.find("<svg").map_or(svg.as_str(), |start| &svg[start..]);
+			kit.push_str(&format!("{}\n", svg));
+		}
+	}
+
+	kit.push_str(&format!("<h2>{}</h2>\n<ol>\n", escape_xml(&texts.instructions_heading)));
+	for instruction in &texts.instructions {
+		kit.push_str(&format!("<li>{}</li>\n", escape_xml(instruction)));
+	}
+	if recovery_key.is_some() {
+		kit.push_str(&format!("<li>{}</li>\n", escape_xml(&texts.recovery_key_instruction)));
+	}
+	kit.push_str("</ol>\n</body>\n</html>\n");
+	kit
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SALT: [u8; 16] = [7; 16];
+
+	fn recovery_key() -> String {
+		format_recovery_key(&std::array::from_fn(|idx| idx as u8))
+	}
+
+	#[test]
+	fn text_kit_describes_vault_and_key() {
+		let texts = &get_texts().emergency_kit;
+		let kit = create_text_kit(&get_sections(&SALT), Some(&recovery_key()));
+		assert!(kit.contains(&texts.format_value.replacen("{}", &DATABASE_FORMAT_VERSION.to_string(), 1)));
+		assert!(kit.contains(KDF_NAME));
+		assert!(kit.lines().any(|line| line.starts_with(&texts.kdf_iterations) && line.ends_with(&format!(" {}", PBKDF2_ITERATIONS))));
+		assert!(kit.contains(&BASE64.encode(SALT)));
+		assert!(kit.contains(&texts.key_length_value.replacen("{}", "32", 1)));
+		assert!(kit.contains("0001 0203 0405 0607 0809 0a0b 0c0d 0e0f 1011 1213 1415 1617 1819 1a1b 1c1d 1e1f\n"));
+		assert!(kit.contains(&texts.recovery_key_instruction));
+	}
+
+	#[test]
+	fn text_kit_leaves_out_missing_key() {
+		let texts = &get_texts().emergency_kit;
+		let kit = create_text_kit(&get_sections(&SALT), None);
+		assert!(!kit.contains(&texts.recovery_key_heading));
+		assert!(!kit.contains(&texts.recovery_key_instruction));
+	}
+
+	#[test]
+	fn html_kit_escapes_values() {
+		let sections = [KitSection { heading: "Vault & <key>", rows: vec![("Location:", "/home/<me>/\"R&D\"".to_string())] }];
+		let kit = create_html_kit(&sections, Some(&recovery_key()));
+		assert!(kit.contains("<h2>Vault &amp; &lt;key&gt;</h2>"));
+		assert!(kit.contains("<tr><th>Location:</th><td>/home/&lt;me&gt;/&quot;R&amp;D&quot;</td></tr>"));
+		assert!(kit.contains("<p class=\"key\">0001 0203"));
+		assert!(!kit.contains("<?xml"));
+	}
+}
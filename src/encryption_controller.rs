@@ -8,6 +8,10 @@ use ring::rand::SecureRandom;
 use std::num::NonZeroU32;
 
 const CREDENTIAL_LEN: usize = 32;
+pub const PBKDF2_ITERATIONS: u32 = 100_000;
+/// The layout of the database file: the nonce followed by the AES-256-GCM ciphertext of an SQL dump of the vault.
+/// Has to be increased whenever the layout changes, as emergency kits describe the file by it.
+pub const DATABASE_FORMAT_VERSION: u32 = 1;
 
 pub struct PasswordEncryption {
 	salt: [u8; 16],
@@ -59,6 +63,10 @@ impl PasswordEncryption {
 		self.encrypted_string
 	}
 
+	pub fn get_salt(&self) -> [u8; 16] {
+		self.salt
+	}
+
 	pub fn create_string(&self) -> String {
 		let salt_encoded = general_purpose::STANDARD.encode(self.salt);
 		let pwd_encoded = general_purpose::STANDARD.encode(self.encrypted_string);
@@ -68,7 +76,7 @@ impl PasswordEncryption {
 	pub fn verify_string(&self, string: &str) -> bool {
 		pbkdf2::verify(
 			pbkdf2::PBKDF2_HMAC_SHA256,
-			NonZeroU32::new(100_000).unwrap(),
+			NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
 			&self.salt,
 			string.as_bytes(),
 			&self.encrypted_string,
@@ -87,7 +95,7 @@ impl PasswordEncryption {
 		let mut key = [0u8; CREDENTIAL_LEN];
 		pbkdf2::derive(
 			pbkdf2::PBKDF2_HMAC_SHA256,
-			NonZeroU32::new(100_000).unwrap(),
+			NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
 			salt,
 			string.as_bytes(),
 			&mut key,
@@ -101,7 +109,8 @@ pub fn load_encrypted_db(encrypted_db: Vec<u8>, encryption_key: &[u8]) -> std::i
 
 	let aes_key = Key::<Aes256Gcm>::from_slice(encryption_key);
 	let cipher = Aes256Gcm::new(aes_key);
-	let plaintext_db = cipher.decrypt(Nonce::from_slice(nonce), ciphertext).expect("Decryption of db failed");
+	let plaintext_db = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Decryption of db failed"))?;
 
 	Ok(plaintext_db)
 }
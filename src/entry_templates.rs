@@ -0,0 +1,252 @@
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::fs;
+use toml::from_str;
+use crate::date_util::parse_date;
+use crate::file_accesssor::read_user_templates_from_disk;
+use crate::ssh_key::{SshKey, SshKeyError};
+use crate::texts::get_texts;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+	#[default]
+	Text,
+	Secret,
+	Email,
+	Url,
+	Number,
+	CardNumber,
+	Expiry,
+	Port,
+	Date,
+	SshKey,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+	Required,
+	TooShort(usize),
+	TooLong(usize),
+	InvalidEmail,
+	InvalidUrl,
+	NotANumber,
+	InvalidCardNumber,
+	InvalidExpiry,
+	InvalidPort,
+	InvalidDate,
+	InvalidSecret,
+	InvalidDigits,
+	InvalidAlgorithm,
+	InvalidSshKey,
+	EncryptedSshKey,
+	UnreadableFile,
+}
+
+impl ValidationError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().validation;
+		match self {
+			ValidationError::Required => texts.required.clone(),
+			ValidationError::TooShort(min) => format!("{} {}", texts.too_short, min),
+			ValidationError::TooLong(max) => format!("{} {}", texts.too_long, max),
+			ValidationError::InvalidEmail => texts.invalid_email.clone(),
+			ValidationError::InvalidUrl => texts.invalid_url.clone(),
+			ValidationError::NotANumber => texts.not_a_number.clone(),
+			ValidationError::InvalidCardNumber => texts.invalid_card_number.clone(),
+			ValidationError::InvalidExpiry => texts.invalid_expiry.clone(),
+			ValidationError::InvalidPort => texts.invalid_port.clone(),
+			ValidationError::InvalidDate => texts.invalid_date.clone(),
+			ValidationError::InvalidSecret => texts.invalid_secret.clone(),
+			ValidationError::InvalidDigits => texts.invalid_digits.clone(),
+			ValidationError::InvalidAlgorithm => texts.invalid_algorithm.clone(),
+			ValidationError::InvalidSshKey => texts.invalid_ssh_key.clone(),
+			ValidationError::EncryptedSshKey => texts.encrypted_ssh_key.clone(),
+			ValidationError::UnreadableFile => texts.unreadable_file.clone(),
+		}
+	}
+}
+
+impl From<SshKeyError> for ValidationError {
+	fn from(error: SshKeyError) -> Self {
+		match error {
+			SshKeyError::Encrypted => ValidationError::EncryptedSshKey,
+			SshKeyError::InvalidFormat | SshKeyError::UnsupportedType => ValidationError::InvalidSshKey,
+		}
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FieldTemplate {
+	pub key: String,
+	pub label: String,
+	#[serde(default)]
+	pub kind: FieldKind,
+	#[serde(default)]
+	pub required: bool,
+	#[serde(default)]
+	hidden: bool,
+	min_length: Option<usize>,
+	max_length: Option<usize>,
+}
+
+impl FieldTemplate {
+	pub fn new(key: &str, label: &str, kind: FieldKind, required: bool) -> Self {
+		FieldTemplate {
+			key: key.to_string(),
+			label: label.to_string(),
+			kind,
+			required,
+			hidden: false,
+			min_length: None,
+			max_length: None,
+		}
+	}
+
+	pub fn is_hidden(&self) -> bool {
+		self.hidden || self.kind == FieldKind::Secret || self.kind == FieldKind::SshKey
+	}
+
+	/// Returns the label without its trailing colon, e.g. to use it inside a question.
+	pub fn get_name(&self) -> &str {
+		self.label.trim_end_matches(':')
+	}
+
+	pub fn validate(&self, value: &str) -> Result<(), ValidationError> {
+		if value.is_empty() {
+			return if self.required { Err(ValidationError::Required) } else { Ok(()) };
+		}
+
+		let length = value.chars().count();
+		if let Some(min_length) = self.min_length {
+			if length < min_length {
+				return Err(ValidationError::TooShort(min_length));
+			}
+		}
+		if let Some(max_length) = self.max_length {
+			if length > max_length {
+				return Err(ValidationError::TooLong(max_length));
+			}
+		}
+
+		match self.kind {
+			FieldKind::Text | FieldKind::Secret => Ok(()),
+			FieldKind::Email => validate_email(value),
+			FieldKind::Url => validate_url(value),
+			FieldKind::Number => {
+				if value.chars().all(|c| c.is_ascii_digit()) {
+					Ok(())
+				} else {
+					Err(ValidationError::NotANumber)
+				}
+			}
+			FieldKind::CardNumber => validate_card_number(value),
+			FieldKind::Expiry => validate_expiry(value),
+			FieldKind::Port => match value.parse::<u16>() {
+				Ok(port) if port > 0 => Ok(()),
+				_ => Err(ValidationError::InvalidPort),
+			},
+			FieldKind::Date => parse_date(value).map(|_| ()).ok_or(ValidationError::InvalidDate),
+			FieldKind::SshKey => SshKey::from_stored(value).map(|_| ()).map_err(ValidationError::from),
+		}
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EntryTemplate {
+	pub key: String,
+	pub name: String,
+	pub rotation_days: Option<u32>,
+	#[serde(default)]
+	pub fields: Vec<FieldTemplate>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TemplateFile {
+	#[serde(default)]
+	templates: Vec<EntryTemplate>,
+}
+
+fn validate_email(value: &str) -> Result<(), ValidationError> {
+	let (local, domain) = match value.split_once('@') {
+		Some(parts) => parts,
+		None => return Err(ValidationError::InvalidEmail),
+	};
+	if local.is_empty() || !domain.contains('.') || domain.starts_with('.') || domain.ends_with('.') || value.contains(char::is_whitespace) {
+		return Err(ValidationError::InvalidEmail);
+	}
+	Ok(())
+}
+
+fn validate_url(value: &str) -> Result<(), ValidationError> {
+	let host = match value.split_once("://") {
+		Some((scheme, rest)) if !scheme.is_empty() => rest,
+		_ => value,
+	};
+	if host.is_empty() || host.starts_with('/') || value.contains(char::is_whitespace) {
+		return Err(ValidationError::InvalidUrl);
+	}
+	Ok(())
+}
+
+fn validate_card_number(value: &str) -> Result<(), ValidationError> {
+	let digits: Vec<u32> = value.chars().filter(|c| *c != ' ' && *c != '-').map(|c| c.to_digit(10)).collect::<Option<Vec<u32>>>().ok_or(ValidationError::InvalidCardNumber)?;
+	if digits.len() < 12 || digits.len() > 19 {
+		return Err(ValidationError::InvalidCardNumber);
+	}
+
+	// Luhn checksum
+	let sum: u32 = digits.iter().rev().enumerate().map(|(idx, digit)| {
+		if idx % 2 == 1 {
+			let doubled = digit * 2;
+			if doubled > 9 { doubled - 9 } else { doubled }
+		} else {
+			*digit
+		}
+	}).sum();
+
+	if sum.is_multiple_of(10) { Ok(()) } else { Err(ValidationError::InvalidCardNumber) }
+}
+
+fn validate_expiry(value: &str) -> Result<(), ValidationError> {
+	let (month, year) = value.split_once('/').ok_or(ValidationError::InvalidExpiry)?;
+	let month: u8 = month.parse().map_err(|_| ValidationError::InvalidExpiry)?;
+	if !(1..=12).contains(&month) || year.len() != 2 || !year.chars().all(|c| c.is_ascii_digit()) {
+		return Err(ValidationError::InvalidExpiry);
+	}
+	Ok(())
+}
+
+fn load_templates() -> Vec<EntryTemplate> {
+	let file_content = fs::read_to_string("entry_templates.toml").unwrap_or_else(|_| include_str!("../entry_templates.toml").to_string());
+	let mut templates = from_str::<TemplateFile>(&file_content).expect("Error parsing entry templates").templates;
+
+	if let Some(user_content) = read_user_templates_from_disk() {
+		let user_templates = match from_str::<TemplateFile>(&user_content) {
+			Ok(file) => file.templates,
+			Err(e) => panic!("Error parsing user templates: {}", e),
+		};
+		for user_template in user_templates {
+			match templates.iter_mut().find(|template| template.key == user_template.key) {
+				Some(template) => *template = user_template,
+				None => templates.push(user_template),
+			}
+		}
+	}
+
+	templates
+}
+
+static TEMPLATES: OnceCell<Vec<EntryTemplate>> = OnceCell::new();
+
+pub fn init_templates() {
+	TEMPLATES.set(load_templates()).unwrap()
+}
+
+pub fn get_templates() -> &'static Vec<EntryTemplate> {
+	TEMPLATES.get().expect("Templates not initialized!")
+}
+
+pub fn find_template(key: &str) -> Option<&'static EntryTemplate> {
+	get_templates().iter().find(|template| template.key == key)
+}
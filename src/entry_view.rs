@@ -0,0 +1,135 @@
+use crate::database_context::{Account, EMAIL_FIELD_KEY, NAME_FIELD_KEY, PASSWORD_FIELD_KEY, PASSWORD_HISTORY_FIELD_KEY};
+use crate::entry_templates::{find_template, FieldKind, FieldTemplate};
+use crate::password_widget::PasswordWidget;
+use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
+use crate::widget::Widget;
+
+/// Displays the fields of an entry as rows of label and value, based on the template of its type.
+/// Fields which are not part of the template, e.g. from an older template version, are shown below.
+pub struct EntryView {
+	rows: Vec<FieldTemplate>,
+	password_widgets: Vec<Option<PasswordWidget>>,
+	label_width: u16,
+}
+
+impl EntryView {
+	pub fn new(account: &Account) -> Self {
+		let mut rows = vec![FieldTemplate::new(NAME_FIELD_KEY, &get_texts().account.account_name, FieldKind::Text, true)];
+		if let Some(template) = find_template(&account.entry_type) {
+			rows.extend(template.fields.iter().cloned());
+		}
+
+		let core_rows = vec![
+			FieldTemplate::new(EMAIL_FIELD_KEY, &get_texts().account.email, FieldKind::Email, false),
+			FieldTemplate::new(PASSWORD_FIELD_KEY, &get_texts().account.password, FieldKind::Secret, false),
+			FieldTemplate::new(PASSWORD_HISTORY_FIELD_KEY, &get_texts().account.password_history, FieldKind::Secret, false),
+		];
+		for core_row in core_rows {
+			let has_value = account.get_field(&core_row.key).is_some_and(|value| !value.is_empty());
+			if has_value && !rows.iter().any(|row| row.key == core_row.key) {
+				rows.push(core_row);
+			}
+		}
+
+		for field in &account.fields {
+			if !rows.iter().any(|row| row.key == field.key) {
+				let kind = if field.hidden { FieldKind::Secret } else { FieldKind::Text };
+				rows.push(FieldTemplate::new(&field.key, &format!("{}:", field.key), kind, false));
+			}
+		}
+
+		let password_widgets = rows.iter().map(|row| {
+			if row.is_hidden() {
+				Some(PasswordWidget::new(account.get_field(&row.key).unwrap_or_default().to_string()))
+			} else {
+				None
+			}
+		}).collect();
+
+		let label_width = rows.iter().map(|row| row.label.chars().count()).max().unwrap_or(0) as u16;
+
+		EntryView {
+			rows,
+			password_widgets,
+			label_width,
+		}
+	}
+
+	pub fn get_rows(&self) -> &Vec<FieldTemplate> {
+		&self.rows
+	}
+
+	pub fn update_value(&mut self, row_idx: usize, value: &str) {
+		if let Some(Some(widget)) = self.password_widgets.get_mut(row_idx) {
+			widget.update_password(value.to_string());
+		}
+	}
+
+	/// Reveals the hidden value of the given row, all other rows are hidden again.
+	pub fn reveal_row(&mut self, row_idx: Option<usize>) {
+		for (idx, widget) in self.password_widgets.iter_mut().enumerate() {
+			if let Some(widget) = widget {
+				widget.change_visibility(row_idx == Some(idx));
+			}
+		}
+	}
+
+	pub fn display(&self, context: &mut TerminalContext, account: &Account, pos_y: u16, highlighted: Option<usize>) {
+		let value_x = self.get_value_x(context);
+		let max_value_len = (context.get_width() - 1).saturating_sub(value_x) as usize;
+		let visible_rows = self.get_visible_rows(context, pos_y);
+		let first_row = self.get_first_row(visible_rows, highlighted);
+
+		for (line, row_idx) in (first_row..self.rows.len()).take(visible_rows).enumerate() {
+			let row = &self.rows[row_idx];
+			let row_y = pos_y + line as u16;
+			let is_highlighted = highlighted == Some(row_idx);
+
+			if is_highlighted {
+				context.print_styled_at_position(0, row_y, &row.label, StyleAttribute::Bold);
+			} else {
+				context.print_at_position(0, row_y, &row.label);
+			}
+
+			if let Some(Some(widget)) = self.password_widgets.get(row_idx) {
+				widget.display(context, value_x, row_y);
+				continue;
+			}
+
+			// Imported notes can span several lines, they are shown on one
+			let value: String = account.get_field(&row.key).unwrap_or_default().chars()
+				.map(|c| if c == '\n' { ' ' } else { c })
+				.take(max_value_len).collect();
+			if is_highlighted {
+				context.print_styled_at_position(value_x, row_y, &value, StyleAttribute::InverseColor);
+			} else {
+				context.print_at_position(value_x, row_y, &value);
+			}
+		}
+	}
+
+	/// Returns the position behind the value of the given row, e.g. to place the cursor while editing.
+	pub fn get_value_end_position(&self, context: &TerminalContext, account: &Account, pos_y: u16, row_idx: usize) -> (u16, u16) {
+		let value_x = self.get_value_x(context);
+		let first_row = self.get_first_row(self.get_visible_rows(context, pos_y), Some(row_idx));
+		let value_len = account.get_field(&self.rows[row_idx].key).unwrap_or_default().chars().count() as u16;
+		((value_x + value_len).min(context.get_width() - 1), pos_y + (row_idx - first_row) as u16)
+	}
+
+	fn get_value_x(&self, context: &TerminalContext) -> u16 {
+		(self.label_width + 1).min(context.get_width() / 2)
+	}
+
+	fn get_visible_rows(&self, context: &TerminalContext, pos_y: u16) -> usize {
+		let footer_height = 3;
+		context.get_height().saturating_sub(pos_y + footer_height).max(1) as usize
+	}
+
+	fn get_first_row(&self, visible_rows: usize, highlighted: Option<usize>) -> usize {
+		match highlighted {
+			Some(idx) if idx >= visible_rows => idx + 1 - visible_rows,
+			_ => 0,
+		}
+	}
+}
@@ -0,0 +1,385 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use crossterm::event::KeyCode;
+use crate::database_context::{Account, DatabaseManager};
+use crate::emergency_kit::create_emergency_kit;
+use crate::encryption_controller::PasswordEncryption;
+use crate::file_accesssor::{expand_user_path, read_password_from_disk, shred_file, write_private_file_to_disk};
+use crate::input_handler::{evaluate_yes_no_answer, get_text_input};
+use crate::kdbx::KdbxKey;
+use crate::keepass::export_keepass;
+use crate::password_widget::PasswordWidget;
+use crate::plain_export::{export_plain_csv, export_plain_json};
+use crate::portable_export::export_portable;
+use crate::state_item::StateItem;
+use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
+use crate::transition::Transition;
+use crate::vault_export::{filter_accounts, ExportFormat};
+
+enum ExportState {
+	SelectFormat,
+	EnterFilter,
+	EnterPath,
+	ConfirmOverwrite,
+	EnterPassword,
+	ConfirmPassword,
+	ConfirmPlaintext,
+	ConfirmRecoveryKey,
+	EnterMasterPassword,
+	/// A file with secrets in plain text was written, holds the summary shown above the question.
+	ConfirmShred(String),
+	Summary(String),
+}
+
+pub struct ExportStateItem {
+	selected_format: usize,
+	accounts: Vec<Account>,
+	path: PathBuf,
+	input_buffer: String,
+	password: String,
+	password_buffer: String,
+	password_widget: PasswordWidget,
+	recovery_key: Option<[u8; 32]>,
+	export_error: Option<String>,
+	internal_state: ExportState,
+	next_state: Option<Transition>,
+	db_manager: Arc<Mutex<DatabaseManager>>,
+}
+
+impl ExportStateItem {
+	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>) -> Self {
+		Self {
+			selected_format: 0,
+			accounts: Vec::new(),
+			path: PathBuf::new(),
+			input_buffer: String::new(),
+			password: String::new(),
+			password_buffer: String::new(),
+			password_widget: PasswordWidget::new(String::new()),
+			recovery_key: None,
+			export_error: None,
+			internal_state: ExportState::SelectFormat,
+			next_state: None,
+			db_manager,
+		}
+	}
+
+	fn get_format(&self) -> ExportFormat {
+		ExportFormat::ALL[self.selected_format]
+	}
+
+	fn apply_filter(&mut self) {
+		let accounts = {
+			let database_manager = self.db_manager.lock().unwrap();
+			match database_manager.get_database_context() {
+				Some(context) => filter_accounts(context, &self.input_buffer),
+				None => panic!("Database not initialized"),
+			}
+		};
+		if accounts.is_empty() {
+			self.export_error = Some(get_texts().export.no_matches.clone());
+			return;
+		}
+		self.accounts = accounts;
+		self.export_error = None;
+		self.input_buffer.clear();
+		self.internal_state = ExportState::EnterPath;
+	}
+
+	fn set_path(&mut self) {
+		if self.input_buffer.trim().is_empty() {
+			return;
+		}
+		self.path = expand_user_path(self.input_buffer.trim());
+		self.input_buffer.clear();
+		self.internal_state = if self.path.exists() { ExportState::ConfirmOverwrite } else { self.get_protection_state() };
+	}
+
+	/// Encrypted exports ask for a password to protect the file with, unencrypted ones warn first.
+	fn get_protection_state(&self) -> ExportState {
+		match self.get_format() {
+			ExportFormat::EmergencyKit(_) => ExportState::ConfirmRecoveryKey,
+			format if format.is_plaintext() => ExportState::ConfirmPlaintext,
+			_ => ExportState::EnterPassword,
+		}
+	}
+
+	fn clear_password_input(&mut self) {
+		self.password_buffer.clear();
+		self.password_widget.update_password(String::new());
+	}
+
+	fn set_password(&mut self) {
+		if self.password_buffer.is_empty() {
+			self.export_error = Some(get_texts().export.empty_password.clone());
+			return;
+		}
+		self.password = self.password_buffer.clone();
+		self.export_error = None;
+		self.clear_password_input();
+		self.internal_state = ExportState::ConfirmPassword;
+	}
+
+	fn confirm_password(&mut self) {
+		let matches = self.password_buffer == self.password;
+		self.clear_password_input();
+		if !matches {
+			self.password.clear();
+			self.export_error = Some(get_texts().export.password_mismatch.clone());
+			self.internal_state = ExportState::EnterPassword;
+			return;
+		}
+		self.write_export();
+	}
+
+	/// Unencrypted exports and emergency kits with the recovery key are only written after the master password
+	/// was typed again, like wiping the vault.
+	fn verify_master_password(&mut self) {
+		let master_password = match read_password_from_disk().and_then(|pwd_string| PasswordEncryption::create_from_string(pwd_string).ok()) {
+			Some(master_password) => master_password,
+			None => {
+				self.clear_password_input();
+				self.export_error = Some(get_texts().export.key_file_unreadable.clone());
+				return;
+			}
+		};
+		let verified = master_password.verify_string(self.password_buffer.trim());
+		self.clear_password_input();
+		if !verified {
+			self.export_error = Some(get_texts().export.wrong_master_password.clone());
+			return;
+		}
+		self.export_error = None;
+		if matches!(self.get_format(), ExportFormat::EmergencyKit(_)) {
+			self.recovery_key = Some(master_password.get_encrypted_string());
+		}
+		self.write_export();
+	}
+
+	fn write_export(&mut self) {
+		let texts = &get_texts().export;
+		let password = std::mem::take(&mut self.password);
+		let data = {
+			let database_manager = self.db_manager.lock().unwrap();
+			let db_context = match database_manager.get_database_context() {
+				Some(context) => context,
+				None => panic!("Database not initialized"),
+			};
+			match self.get_format() {
+				ExportFormat::Portable => Ok(export_portable(db_context, &self.accounts, &password)),
+				ExportFormat::KeePass => export_keepass(db_context, &self.accounts, &KdbxKey::new(Some(password), None)).map_err(|e| e.get_message()),
+				ExportFormat::PlainCsv => Ok(export_plain_csv(&self.accounts)),
+				ExportFormat::PlainJson => Ok(export_plain_json(db_context, &self.accounts)),
+				ExportFormat::EmergencyKit(format) => create_emergency_kit(format, self.recovery_key.as_ref()).map_err(|e| e.get_message()),
+			}
+		};
+
+		self.internal_state = match data.and_then(|data| {
+			write_private_file_to_disk(&self.path, &data).map_err(|e| format!("{} {}", texts.write_failed, e))
+		}) {
+			Ok(()) => {
+				let path = self.path.display().to_string();
+				let message = match self.get_format() {
+					ExportFormat::EmergencyKit(_) => texts.kit_summary.replacen("{}", &path, 1),
+					_ => texts.summary.replacen("{}", &self.accounts.len().to_string(), 1).replacen("{}", &path, 1),
+				};
+				if self.get_format().is_plaintext() || self.recovery_key.is_some() { ExportState::ConfirmShred(message) } else { ExportState::Summary(message) }
+			}
+			Err(message) => ExportState::Summary(message),
+		};
+		self.accounts.clear();
+		self.recovery_key = None;
+	}
+
+	fn shred_export(&mut self) {
+		let texts = &get_texts().export;
+		let message = match shred_file(&self.path) {
+			Ok(()) => texts.shredded.replacen("{}", &self.path.display().to_string(), 1),
+			Err(e) => format!("{} {}", texts.shred_failed, e),
+		};
+		self.internal_state = ExportState::Summary(message);
+	}
+
+	fn get_prompt(&self, prompt: &String) -> String {
+		match &self.export_error {
+			Some(e) => format!("{} {}", prompt, e),
+			None => prompt.clone(),
+		}
+	}
+
+	fn show_formats(&self, context: &mut TerminalContext) {
+		context.print_at_position(0, 2, &get_texts().export.format_prompt);
+		for (idx, format) in ExportFormat::ALL.iter().enumerate() {
+			if idx == self.selected_format {
+				context.print_styled_at_position(0, 4 + idx as u16, format.get_name(), StyleAttribute::InverseColor);
+			} else {
+				context.print_at_position(0, 4 + idx as u16, format.get_name());
+			}
+		}
+	}
+
+	fn show_selection(&self, context: &mut TerminalContext) {
+		context.print_at_position(0, 2, self.get_format().get_name());
+		if !self.accounts.is_empty() {
+			context.print_at_position(0, 3, &get_texts().export.selected_count.replacen("{}", &self.accounts.len().to_string(), 1));
+		}
+	}
+
+	fn select_format_input(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Up => self.selected_format = self.selected_format.checked_sub(1).unwrap_or(ExportFormat::ALL.len() - 1),
+			KeyCode::Down => self.selected_format = (self.selected_format + 1) % ExportFormat::ALL.len(),
+			// The emergency kit contains no entries, there is nothing to filter
+			KeyCode::Enter => self.internal_state = match self.get_format() {
+				ExportFormat::EmergencyKit(_) => ExportState::EnterPath,
+				_ => ExportState::EnterFilter,
+			},
+			KeyCode::Esc => self.next_state = Some(Transition::ToMainMenu),
+			_ => {}
+		}
+	}
+
+	fn password_input(&mut self, key_code: KeyCode) {
+		if key_code == KeyCode::Esc {
+			self.clear_password_input();
+			self.password.clear();
+			self.export_error = None;
+			self.internal_state = ExportState::EnterPath;
+			return;
+		}
+		if get_text_input(key_code, &mut self.password_buffer) {
+			match self.internal_state {
+				ExportState::EnterPassword => self.set_password(),
+				ExportState::EnterMasterPassword => self.verify_master_password(),
+				_ => self.confirm_password(),
+			}
+		} else {
+			self.password_widget.update_password(self.password_buffer.clone());
+		}
+	}
+}
+
+impl StateItem for ExportStateItem {
+	fn display(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().export;
+		context.print_styled_at_position(0, 0, &texts.heading, StyleAttribute::Underline);
+
+		match &self.internal_state {
+			ExportState::SelectFormat => {
+				self.show_formats(context);
+				let input = &get_texts().input;
+				context.draw_control_footer(vec![&input.down_arrow, &input.up_arrow, &input.enter, &input.escape]);
+			}
+			ExportState::EnterFilter => {
+				self.show_selection(context);
+				context.draw_input_footer(&self.get_prompt(&texts.filter_prompt), self.input_buffer.clone());
+			}
+			ExportState::EnterPath => {
+				self.show_selection(context);
+				context.draw_input_footer(&self.get_prompt(&texts.path_prompt), self.input_buffer.clone());
+			}
+			ExportState::ConfirmOverwrite => {
+				self.show_selection(context);
+				context.draw_request_footer(&texts.overwrite_question.replacen("{}", &self.path.display().to_string(), 1));
+			}
+			ExportState::ConfirmPlaintext => {
+				self.show_selection(context);
+				context.print_styled_at_position(0, 5, &texts.plaintext_warning.replacen("{}", &self.accounts.len().to_string(), 1), StyleAttribute::Bold);
+				context.print_at_position(0, 6, &texts.plaintext_hint);
+				context.draw_request_footer(&texts.plaintext_question);
+			}
+			ExportState::ConfirmRecoveryKey => {
+				self.show_selection(context);
+				context.draw_request_footer(&texts.recovery_key_question);
+			}
+			ExportState::EnterPassword | ExportState::ConfirmPassword | ExportState::EnterMasterPassword => {
+				self.show_selection(context);
+				let prompt = match self.internal_state {
+					ExportState::EnterPassword => &texts.password_prompt,
+					ExportState::EnterMasterPassword => &texts.master_password_prompt,
+					_ => &texts.confirm_password_prompt,
+				};
+				let password = self.password_widget.get_password_to_display().cloned().unwrap_or_default();
+				context.draw_input_footer(&self.get_prompt(prompt), password);
+			}
+			ExportState::ConfirmShred(message) => {
+				context.print_at_position(0, 2, message);
+				context.draw_request_footer(&texts.shred_question);
+			}
+			ExportState::Summary(message) => {
+				context.print_at_position(0, 2, message);
+				context.draw_control_footer(vec![&get_texts().input.enter]);
+			}
+		}
+	}
+
+	fn update_display(&self) -> bool {
+		false
+	}
+
+	fn register_input(&mut self, key_code: KeyCode) {
+		match self.internal_state {
+			ExportState::SelectFormat => self.select_format_input(key_code),
+			ExportState::EnterFilter => {
+				if key_code == KeyCode::Esc {
+					self.input_buffer.clear();
+					self.export_error = None;
+					self.internal_state = ExportState::SelectFormat;
+				} else if get_text_input(key_code, &mut self.input_buffer) {
+					self.apply_filter();
+				}
+			}
+			ExportState::EnterPath => {
+				if key_code == KeyCode::Esc {
+					self.input_buffer.clear();
+					self.accounts.clear();
+					self.internal_state = match self.get_format() {
+						ExportFormat::EmergencyKit(_) => ExportState::SelectFormat,
+						_ => ExportState::EnterFilter,
+					};
+				} else if get_text_input(key_code, &mut self.input_buffer) {
+					self.set_path();
+				}
+			}
+			ExportState::ConfirmOverwrite => {
+				if let Some(overwrite) = evaluate_yes_no_answer(key_code) {
+					self.internal_state = if overwrite { self.get_protection_state() } else { ExportState::EnterPath };
+				}
+			}
+			ExportState::ConfirmPlaintext => {
+				if let Some(confirmed) = evaluate_yes_no_answer(key_code) {
+					self.internal_state = if confirmed { ExportState::EnterMasterPassword } else { ExportState::EnterPath };
+				}
+			}
+			ExportState::ConfirmRecoveryKey => {
+				if let Some(include) = evaluate_yes_no_answer(key_code) {
+					if include {
+						self.internal_state = ExportState::EnterMasterPassword;
+					} else {
+						self.write_export();
+					}
+				}
+			}
+			ExportState::EnterPassword | ExportState::ConfirmPassword | ExportState::EnterMasterPassword => self.password_input(key_code),
+			ExportState::ConfirmShred(_) => {
+				if let Some(shred) = evaluate_yes_no_answer(key_code) {
+					if shred {
+						self.shred_export();
+					} else {
+						self.next_state = Some(Transition::ToMainMenu);
+					}
+				}
+			}
+			ExportState::Summary(_) => {
+				if key_code == KeyCode::Enter || key_code == KeyCode::Esc {
+					self.next_state = Some(Transition::ToMainMenu);
+				}
+			}
+		}
+	}
+
+	fn next_state(&self) -> Option<Transition> {
+		self.next_state.clone()
+	}
+}
@@ -1,20 +1,54 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use directories::BaseDirs;
 
-fn get_base_dir() -> PathBuf {
+pub fn get_base_dir() -> PathBuf {
 	let base_dirs = BaseDirs::new().expect("Could not determine home directory");
 	base_dirs.home_dir().join("RustyPasswordManager")
 }
 
-fn get_db_file_path() -> PathBuf {
+pub fn get_db_file_path() -> PathBuf {
 	get_base_dir().join("rpm.db")
 }
 
-fn get_password_file_path() -> PathBuf {
+pub fn get_password_file_path() -> PathBuf {
 	get_base_dir().join("pwd.key")
 }
 
+fn get_user_templates_file_path() -> PathBuf {
+	get_base_dir().join("templates.toml")
+}
+
+fn get_csv_mappings_file_path() -> PathBuf {
+	get_base_dir().join("csv_mappings.toml")
+}
+
+fn get_socket_dir() -> PathBuf {
+	get_base_dir().join("sockets")
+}
+
+pub fn get_ssh_agent_socket_path() -> PathBuf {
+	get_socket_dir().join("ssh-agent.sock")
+}
+
+pub fn get_agent_socket_path() -> PathBuf {
+	get_socket_dir().join("agent.sock")
+}
+
+/// Creates the directory of the agent sockets, accessible by the user only. Other users cannot connect
+/// to a socket inside it, not even in the moment between creating the socket and changing its permissions.
+#[cfg(unix)]
+pub fn create_socket_directory() -> std::io::Result<()> {
+	use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+	let socket_dir = get_socket_dir();
+	fs::DirBuilder::new().recursive(true).mode(0o700).create(&socket_dir)?;
+	// The mode is only applied to a newly created directory
+	fs::set_permissions(&socket_dir, fs::Permissions::from_mode(0o700))
+}
+
 pub fn does_directory_and_files_exist() -> bool {
 	let base_dir = get_base_dir();
 	if !base_dir.exists() {
@@ -58,6 +92,18 @@ pub fn read_password_from_disk() -> Option<String> {
 	Some(string)
 }
 
+pub fn read_user_templates_from_disk() -> Option<String> {
+	fs::read_to_string(get_user_templates_file_path()).ok()
+}
+
+pub fn read_csv_mappings_from_disk() -> Option<String> {
+	fs::read_to_string(get_csv_mappings_file_path()).ok()
+}
+
+pub fn write_csv_mappings_to_disk(content: String) {
+	fs::write(get_csv_mappings_file_path(), content).expect("Failed to write CSV mappings file");
+}
+
 pub fn read_db_from_disk() -> Option<Vec<u8>> {
 	match fs::read(&get_db_file_path()) {
 		Ok(bytes) => Some(bytes),
@@ -65,12 +111,60 @@ pub fn read_db_from_disk() -> Option<Vec<u8>> {
 	}
 }
 
+/// Lets processes holding the vault in memory notice that another one saved it.
+pub fn read_db_modified_time() -> Option<SystemTime> {
+	fs::metadata(get_db_file_path()).and_then(|metadata| metadata.modified()).ok()
+}
+
 pub fn create_directory_and_files(db_cipher: Vec<u8>, pwd_cipher: String) {
 	if does_directory_and_files_exist() {
 		delete_directory_and_files();
 	}
 
-	fs::create_dir(get_base_dir().as_path()).expect("Could not create RustyPasswordManager dir");
+	fs::create_dir_all(get_base_dir().as_path()).expect("Could not create RustyPasswordManager dir");
 	write_password_to_disk(pwd_cipher);
 	write_db_to_disk(db_cipher);
-}
\ No newline at end of file
+}
+/// Resolves a path entered by the user, a leading "~" is replaced by the home directory.
+pub fn expand_user_path(path: &str) -> PathBuf {
+	let path = path.trim();
+	if let Some(rest) = path.strip_prefix('~') {
+		if let Some(base_dirs) = BaseDirs::new() {
+			return base_dirs.home_dir().join(rest.trim_start_matches('/'));
+		}
+	}
+	PathBuf::from(path)
+}
+
+pub fn read_file_from_disk(path: &Path) -> std::io::Result<Vec<u8>> {
+	fs::read(path)
+}
+
+/// Writes a file which is only readable by the current user, as it usually contains secrets.
+pub fn write_private_file_to_disk(path: &Path, content: &[u8]) -> std::io::Result<()> {
+	let mut options = fs::OpenOptions::new();
+	options.write(true).create(true).truncate(true);
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::OpenOptionsExt;
+		options.mode(0o600);
+	}
+	let mut file = options.open(path)?;
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		file.set_permissions(fs::Permissions::from_mode(0o600))?;
+	}
+	file.write_all(content)
+}
+
+/// Overwrites the content of the file with zeros before deleting it, so it can not simply be restored.
+/// Journaling file systems and SSDs may still keep copies of the old content.
+pub fn shred_file(path: &Path) -> std::io::Result<()> {
+	let length = fs::metadata(path)?.len() as usize;
+	let mut file = fs::OpenOptions::new().write(true).open(path)?;
+	file.write_all(&vec![0; length])?;
+	file.sync_all()?;
+	drop(file);
+	fs::remove_file(path)
+}
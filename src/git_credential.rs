@@ -0,0 +1,307 @@
+use std::io::BufRead;
+use crate::database_context::{Account, DatabaseContext, URL_FIELD_KEY, USERNAME_FIELD_KEY};
+
+const STORED_ENTRY_TYPE: &str = "login";
+
+/// The attributes git passes to a credential helper, one key=value pair per line.
+#[derive(Default, Debug, PartialEq)]
+pub struct CredentialRequest {
+	pub protocol: Option<String>,
+	pub host: Option<String>,
+	pub path: Option<String>,
+	pub username: Option<String>,
+	pub password: Option<String>,
+}
+
+impl CredentialRequest {
+	/// Reads attributes until an empty line or the end of the input, unknown attributes are ignored.
+	pub fn read_from<R: BufRead>(reader: R) -> Self {
+		let mut request = Self::default();
+		for line in reader.lines() {
+			let line = match line {
+				Ok(line) => line,
+				Err(_) => break,
+			};
+			let line = line.trim_end_matches('\r');
+			if line.is_empty() {
+				break;
+			}
+			let (key, value) = match line.split_once('=') {
+				Some(pair) => pair,
+				None => continue,
+			};
+			let value = value.to_string();
+			match key {
+				"protocol" => request.protocol = Some(value),
+				"host" => request.host = Some(value),
+				"path" => request.path = Some(value),
+				"username" => request.username = Some(value),
+				"password" => request.password = Some(value),
+				"url" => {
+					if let Some(url) = EntryUrl::parse(&value) {
+						request.protocol = url.protocol.or(request.protocol.take());
+						request.host = Some(url.host);
+						request.path = Some(url.path).filter(|path| !path.is_empty());
+						request.username = url.username.or(request.username.take());
+					}
+				}
+				_ => {}
+			}
+		}
+		request
+	}
+
+	fn get_url(&self) -> String {
+		let mut url = format!("{}://{}", self.protocol.as_deref().unwrap_or("https"), self.host.as_deref().unwrap_or_default());
+		if let Some(path) = self.path.as_deref().map(normalize_path).filter(|path| !path.is_empty()) {
+			url = format!("{}/{}", url, path);
+		}
+		url
+	}
+}
+
+/// The parts of the url field of an entry which are relevant for git.
+/// A url without a scheme, like "github.com/org", matches any protocol.
+struct EntryUrl {
+	protocol: Option<String>,
+	host: String,
+	path: String,
+	username: Option<String>,
+}
+
+impl EntryUrl {
+	fn parse(url: &str) -> Option<Self> {
+		let url = url.trim();
+		let (protocol, rest) = match url.split_once("://") {
+			Some((protocol, rest)) => (Some(protocol.to_lowercase()), rest),
+			None => (None, url),
+		};
+		let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+		let (username, host) = match authority.rsplit_once('@') {
+			Some((user_info, host)) => (Some(user_info.split(':').next().unwrap_or_default().to_string()), host),
+			None => (None, authority),
+		};
+		if host.is_empty() {
+			return None;
+		}
+		let path = path.split(['?', '#']).next().unwrap_or_default();
+		Some(Self { protocol, host: host.to_lowercase(), path: normalize_path(path), username })
+	}
+}
+
+/// Compares repository paths without the surrounding slashes and the ".git" suffix.
+fn normalize_path(path: &str) -> String {
+	let path = path.trim_matches('/');
+	path.strip_suffix(".git").unwrap_or(path).to_string()
+}
+
+/// Rates how well an entry fits the request, None if it does not match at all.
+/// An entry for a repository path beats one for the whole host, an entry without a username
+/// only matches when no entry for the requested user exists.
+fn get_match_score(account: &Account, request: &CredentialRequest) -> Option<usize> {
+	let url = EntryUrl::parse(account.get_field(URL_FIELD_KEY)?)?;
+	let protocol = request.protocol.as_deref()?.to_lowercase();
+	let host = request.host.as_deref()?.to_lowercase();
+	if url.protocol.is_some_and(|entry_protocol| entry_protocol != protocol) || url.host != host {
+		return None;
+	}
+
+	let mut score = 1;
+	if !url.path.is_empty() {
+		if let Some(path) = request.path.as_deref().map(normalize_path) {
+			if path != url.path && !path.starts_with(&format!("{}/", url.path)) {
+				return None;
+			}
+			score += url.path.split('/').count() * 2;
+		} else {
+			// Without credential.useHttpPath git sends no path, entries for the whole host are preferred then
+			score = 0;
+		}
+	}
+
+	let username = url.username.as_deref().filter(|username| !username.is_empty()).unwrap_or(account.get_username());
+	match request.username.as_deref().filter(|requested| !requested.is_empty()) {
+		None => Some(score * 2 + 1),
+		Some(_) if username.is_empty() => Some(score * 2),
+		Some(requested) if requested == username => Some(score * 2 + 1),
+		Some(_) => None,
+	}
+}
+
+/// Finds the entries matching the request, the best match first.
+pub fn find_matching_accounts(db_context: &DatabaseContext, request: &CredentialRequest) -> Vec<Account> {
+	let mut matches: Vec<(usize, Account)> = db_context.list_all_accounts().unwrap().into_iter()
+		.filter_map(|account| get_match_score(&account, request).map(|score| (score, account)))
+		.collect();
+	// The sort is stable, so entries with the same score keep the pinned and recently used order
+	matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+	matches.into_iter().map(|(_, account)| account).collect()
+}
+
+/// Answers a get request with the username and password of the best matching entry.
+pub fn get_credential(db_context: &DatabaseContext, request: &CredentialRequest) -> Option<String> {
+	let account = find_matching_accounts(db_context, request).into_iter().find(|account| !account.password.is_empty())?;
+	let username = request.username.clone().unwrap_or_else(|| account.get_username().to_string());
+	let mut response = String::new();
+	if !username.is_empty() {
+		response.push_str(&format!("username={}\n", username));
+	}
+	response.push_str(&format!("password={}\n", account.password));
+	Some(response)
+}
+
+/// Saves a credential git reported as working. An existing entry of the user gets the new password,
+/// otherwise a login entry is created. Returns whether the vault changed.
+pub fn store_credential(db_context: &DatabaseContext, request: &CredentialRequest) -> bool {
+	let (username, password) = match (&request.username, &request.password) {
+		(Some(username), Some(password)) if !password.is_empty() => (username, password),
+		_ => return false,
+	};
+
+	let existing = find_matching_accounts(db_context, request).into_iter().find(|account| account.get_username() == username);
+	match existing {
+		Some(account) if &account.password == password => false,
+		Some(mut account) => {
+			account.password = password.clone();
+			db_context.update_account(&account);
+			true
+		}
+		None => {
+			let mut account = Account::new(STORED_ENTRY_TYPE);
+			account.account_name = request.host.clone().unwrap_or_default();
+			account.password = password.clone();
+			account.set_field(USERNAME_FIELD_KEY, username.clone());
+			account.set_field(URL_FIELD_KEY, request.get_url());
+			db_context.add_account(&account).unwrap();
+			true
+		}
+	}
+}
+
+/// Clears the password of the entries git reported as rejected, the entries stay with all their other fields,
+/// as git also rejects credentials for transient reasons. Only entries still holding the rejected password
+/// are changed, so a password which was changed in the meantime survives. Returns whether the vault changed.
+pub fn erase_credential(db_context: &DatabaseContext, request: &CredentialRequest) -> bool {
+	let password = match &request.password {
+		Some(password) if !password.is_empty() => password,
+		_ => return false,
+	};
+
+	let mut erased = false;
+	for mut account in find_matching_accounts(db_context, request) {
+		if &account.password == password {
+			account.password.clear();
+			db_context.update_account(&account);
+			erased = true;
+		}
+	}
+	erased
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::database_context::NOTES_FIELD_KEY;
+
+	fn account(url: &str, username: &str, password: &str) -> Account {
+		let mut account = Account::new(STORED_ENTRY_TYPE);
+		account.account_name = url.to_string();
+		account.password = password.to_string();
+		account.set_field(URL_FIELD_KEY, url.to_string());
+		if !username.is_empty() {
+			account.set_field(USERNAME_FIELD_KEY, username.to_string());
+		}
+		account
+	}
+
+	fn request(input: &str) -> CredentialRequest {
+		CredentialRequest::read_from(input.as_bytes())
+	}
+
+	#[test]
+	fn reads_request_attributes() {
+		let parsed = request("protocol=https\r\nhost=GitHub.com\nurl=https://bob@example.com/org/repo.git\nunknown=x\n\npassword=ignored\n");
+		assert_eq!(parsed, CredentialRequest {
+			protocol: Some("https".to_string()),
+			host: Some("example.com".to_string()),
+			path: Some("org/repo".to_string()),
+			username: Some("bob".to_string()),
+			password: None,
+		});
+	}
+
+	#[test]
+	fn prefers_path_entry_over_host_entry() {
+		let host_entry = account("https://github.com", "bob", "host");
+		let path_entry = account("github.com/org/repo", "bob", "path");
+		let path_request = request("protocol=https\nhost=github.com\npath=org/repo.git\n");
+		assert!(get_match_score(&path_entry, &path_request) > get_match_score(&host_entry, &path_request));
+		assert_eq!(get_match_score(&path_entry, &request("protocol=https\nhost=github.com\npath=org/other\n")), None);
+	}
+
+	#[test]
+	fn prefers_host_entry_without_path() {
+		let host_entry = account("https://github.com", "bob", "host");
+		let path_entry = account("https://github.com/org/repo", "bob", "path");
+		let host_request = request("protocol=https\nhost=github.com\n");
+		assert!(get_match_score(&host_entry, &host_request) > get_match_score(&path_entry, &host_request));
+	}
+
+	#[test]
+	fn rejects_other_username() {
+		let entry = account("https://github.com", "bob", "secret");
+		assert_eq!(get_match_score(&entry, &request("protocol=https\nhost=github.com\nusername=alice\n")), None);
+		assert!(get_match_score(&entry, &request("protocol=https\nhost=github.com\nusername=bob\n")).is_some());
+		// An entry without a username fits anyone, but an entry of the user fits better
+		let anonymous = account("https://github.com", "", "token");
+		let bob_request = request("protocol=https\nhost=github.com\nusername=bob\n");
+		assert!(get_match_score(&entry, &bob_request) > get_match_score(&anonymous, &bob_request));
+	}
+
+	#[test]
+	fn rejects_other_protocol_and_host() {
+		let entry = account("https://github.com", "bob", "secret");
+		assert_eq!(get_match_score(&entry, &request("protocol=http\nhost=github.com\n")), None);
+		assert_eq!(get_match_score(&entry, &request("protocol=https\nhost=gitlab.com\n")), None);
+		assert!(get_match_score(&account("github.com", "bob", "secret"), &request("protocol=http\nhost=github.com\n")).is_some());
+	}
+
+	#[test]
+	fn answers_with_best_match() {
+		let db_context = DatabaseContext::new().unwrap();
+		db_context.add_account(&account("https://github.com", "bob", "host")).unwrap();
+		db_context.add_account(&account("https://github.com/org/repo", "bob", "path")).unwrap();
+		let response = get_credential(&db_context, &request("protocol=https\nhost=github.com\npath=org/repo\n"));
+		assert_eq!(response.as_deref(), Some("username=bob\npassword=path\n"));
+	}
+
+	#[test]
+	fn stores_new_and_changed_passwords() {
+		let db_context = DatabaseContext::new().unwrap();
+		assert!(store_credential(&db_context, &request("protocol=https\nhost=github.com\nusername=bob\npassword=first\n")));
+		assert!(!store_credential(&db_context, &request("protocol=https\nhost=github.com\nusername=bob\npassword=first\n")));
+		assert!(store_credential(&db_context, &request("protocol=https\nhost=github.com\nusername=bob\npassword=second\n")));
+		let accounts = db_context.list_all_accounts().unwrap();
+		assert_eq!(accounts.len(), 1);
+		assert_eq!(accounts[0].password, "second");
+		assert_eq!(accounts[0].get_field(URL_FIELD_KEY), Some("https://github.com"));
+	}
+
+	#[test]
+	fn erase_keeps_entries_and_other_passwords() {
+		let db_context = DatabaseContext::new().unwrap();
+		let mut host_entry = account("https://github.com", "bob", "rejected");
+		host_entry.set_field(NOTES_FIELD_KEY, "recovery codes".to_string());
+		let host_id = db_context.add_account(&host_entry).unwrap();
+		let other_id = db_context.add_account(&account("https://github.com/org/repo", "bob", "changed")).unwrap();
+		let unrelated_id = db_context.add_account(&account("https://gitlab.com", "bob", "rejected")).unwrap();
+
+		assert!(erase_credential(&db_context, &request("protocol=https\nhost=github.com\npath=org/repo\nusername=bob\npassword=rejected\n")));
+		let host_entry = db_context.get_account_by_id(host_id).unwrap().unwrap();
+		assert_eq!(host_entry.password, "");
+		assert_eq!(host_entry.get_field(NOTES_FIELD_KEY), Some("recovery codes"));
+		assert_eq!(db_context.get_account_by_id(other_id).unwrap().unwrap().password, "changed");
+		assert_eq!(db_context.get_account_by_id(unrelated_id).unwrap().unwrap().password, "rejected");
+		assert!(!erase_credential(&db_context, &request("protocol=https\nhost=github.com\nusername=bob\npassword=rejected\n")));
+	}
+}
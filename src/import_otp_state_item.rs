@@ -0,0 +1,269 @@
+use std::sync::{Arc, Mutex};
+use crossterm::event::KeyCode;
+use crate::database_context::{Account, DatabaseManager, USERNAME_FIELD_KEY};
+use crate::input_handler::{evaluate_yes_no_answer, get_text_input};
+use crate::otpauth::{parse_otp_uri, OtpImport, OtpImportError};
+use crate::page_list_view::PageView;
+use crate::state_item::StateItem;
+use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
+use crate::transition::Transition;
+
+const IMPORTED_ENTRY_TYPE: &str = "login";
+
+enum ImportOtpState {
+	EnterUri,
+	Review,
+	SelectAccount,
+	ConfirmOverwrite(i32),
+	Summary,
+}
+
+pub struct ImportOtpStateItem {
+	imports: Vec<OtpImport>,
+	current_import: usize,
+	imported_count: usize,
+	input_buffer: String,
+	import_error: Option<OtpImportError>,
+	accounts: Vec<Account>,
+	page_view: PageView,
+	internal_state: ImportOtpState,
+	next_state: Option<Transition>,
+	db_manager: Arc<Mutex<DatabaseManager>>,
+}
+
+impl ImportOtpStateItem {
+	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>) -> Self {
+		Self {
+			imports: Vec::new(),
+			current_import: 0,
+			imported_count: 0,
+			input_buffer: String::new(),
+			import_error: None,
+			accounts: Vec::new(),
+			page_view: PageView::new_empty(),
+			internal_state: ImportOtpState::EnterUri,
+			next_state: None,
+			db_manager,
+		}
+	}
+
+	fn parse_input(&mut self) {
+		match parse_otp_uri(&self.input_buffer) {
+			Ok(imports) => {
+				self.imports = imports;
+				self.current_import = 0;
+				self.import_error = None;
+				self.input_buffer.clear();
+				self.internal_state = ImportOtpState::Review;
+			}
+			Err(e) => self.import_error = Some(e),
+		}
+	}
+
+	fn get_current_import(&self) -> &OtpImport {
+		&self.imports[self.current_import]
+	}
+
+	fn next_import(&mut self) {
+		self.current_import += 1;
+		self.internal_state = if self.current_import < self.imports.len() {
+			ImportOtpState::Review
+		} else {
+			ImportOtpState::Summary
+		};
+	}
+
+	fn create_entry(&mut self) {
+		let import = self.get_current_import().clone();
+		let mut account = Account::new(IMPORTED_ENTRY_TYPE);
+		if import.issuer.is_empty() {
+			account.account_name = import.label;
+		} else {
+			account.account_name = import.issuer;
+			account.set_field(USERNAME_FIELD_KEY, import.label);
+		}
+		account.totp = Some(import.totp);
+
+		{
+			let database_manager = self.db_manager.lock().unwrap();
+			let db_context = match database_manager.get_database_context() {
+				Some(context) => context,
+				None => panic!("Database not initialized"),
+			};
+			db_context.add_account(&account).unwrap();
+			database_manager.safe_database();
+		}
+		self.imported_count += 1;
+		self.next_import();
+	}
+
+	/// Lists the accounts matching the issuer first, as they are the most likely targets.
+	fn start_account_selection(&mut self) {
+		let issuer = self.get_current_import().issuer.to_lowercase();
+		let mut accounts = {
+			let database_manager = self.db_manager.lock().unwrap();
+			match database_manager.get_database_context() {
+				Some(context) => context.list_all_accounts().unwrap(),
+				None => panic!("Database not initialized"),
+			}
+		};
+		if !issuer.is_empty() {
+			accounts.sort_by_key(|account| !account.account_name.to_lowercase().contains(&issuer));
+		}
+
+		self.page_view = PageView::new(&accounts);
+		self.accounts = accounts;
+		self.internal_state = ImportOtpState::SelectAccount;
+	}
+
+	fn select_account(&mut self) {
+		let id = match self.page_view.get_selected_account_id() {
+			Some(id) => id,
+			None => return,
+		};
+		let has_totp = match self.accounts.iter().find(|account| account.id == id) {
+			Some(account) => account.totp.is_some(),
+			None => return,
+		};
+
+		if has_totp {
+			self.internal_state = ImportOtpState::ConfirmOverwrite(id);
+		} else {
+			self.attach_to_account(id);
+		}
+	}
+
+	fn attach_to_account(&mut self, account_id: i32) {
+		let totp = self.get_current_import().totp.clone();
+		let account = match self.accounts.iter_mut().find(|account| account.id == account_id) {
+			Some(account) => account,
+			None => return,
+		};
+		account.totp = Some(totp);
+
+		{
+			let database_manager = self.db_manager.lock().unwrap();
+			let db_context = match database_manager.get_database_context() {
+				Some(context) => context,
+				None => panic!("Database not initialized"),
+			};
+			db_context.update_account(account);
+			database_manager.safe_database();
+		}
+		self.imported_count += 1;
+		self.next_import();
+	}
+
+	fn show_review(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().otp_import;
+		let import = self.get_current_import();
+		let progress = format!("{} {}/{}", texts.review_heading, self.current_import + 1, self.imports.len());
+		context.print_styled_at_position(0, 2, &progress, StyleAttribute::Bold);
+
+		let rows = [
+			(&texts.issuer, import.issuer.clone()),
+			(&texts.label, import.label.clone()),
+			(&texts.algorithm, import.totp.algorithm.get_name().to_string()),
+			(&texts.digits, import.totp.digits.to_string()),
+			(&texts.period, format!("{}s", import.totp.period)),
+		];
+		for (idx, (label, value)) in rows.iter().enumerate() {
+			context.print_at_position(0, 4 + idx as u16, &format!("{} {}", label, value));
+		}
+	}
+
+	fn review_input(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Char('n') => self.create_entry(),
+			KeyCode::Char('a') => self.start_account_selection(),
+			KeyCode::Char('s') => self.next_import(),
+			KeyCode::Esc => self.internal_state = ImportOtpState::Summary,
+			_ => {}
+		}
+	}
+
+	fn select_account_input(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Enter => self.select_account(),
+			KeyCode::Down => self.page_view.next_account(),
+			KeyCode::Up => self.page_view.prev_account(),
+			KeyCode::Left => self.page_view.prev_page(),
+			KeyCode::Right => self.page_view.next_page(),
+			KeyCode::Esc => self.internal_state = ImportOtpState::Review,
+			_ => {}
+		}
+	}
+}
+
+impl StateItem for ImportOtpStateItem {
+	fn display(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().otp_import;
+		context.print_styled_at_position(0, 0, &texts.heading, StyleAttribute::Underline);
+
+		match &self.internal_state {
+			ImportOtpState::EnterUri => {
+				let heading = match &self.import_error {
+					Some(e) => format!("{} {}", texts.uri_prompt, e.get_message()),
+					None => texts.uri_prompt.clone(),
+				};
+				context.draw_input_footer(&heading, self.input_buffer.clone());
+			}
+			ImportOtpState::Review => {
+				self.show_review(context);
+				context.draw_control_footer(vec![&texts.new_entry_input, &texts.attach_input, &texts.skip_input, &get_texts().input.escape]);
+			}
+			ImportOtpState::SelectAccount => {
+				context.print_at_position(0, 2, &texts.select_account);
+				self.page_view.display_page(context, 0, 4);
+				let input = &get_texts().input;
+				context.draw_control_footer(vec![&input.down_arrow, &input.up_arrow, &input.left_arrow, &input.right_arrow, &input.enter, &input.escape]);
+			}
+			ImportOtpState::ConfirmOverwrite(_) => {
+				self.show_review(context);
+				context.draw_request_footer(&texts.overwrite_question);
+			}
+			ImportOtpState::Summary => {
+				let summary = texts.summary.replacen("{}", &self.imported_count.to_string(), 1).replacen("{}", &self.imports.len().to_string(), 1);
+				context.print_at_position(0, 2, &summary);
+				context.draw_control_footer(vec![&get_texts().input.enter]);
+			}
+		}
+	}
+
+	fn update_display(&self) -> bool {
+		false
+	}
+
+	fn register_input(&mut self, key_code: KeyCode) {
+		match self.internal_state {
+			ImportOtpState::EnterUri => {
+				if key_code == KeyCode::Esc {
+					self.next_state = Some(Transition::ToMainMenu);
+				} else if get_text_input(key_code, &mut self.input_buffer) {
+					self.parse_input();
+				}
+			}
+			ImportOtpState::Review => self.review_input(key_code),
+			ImportOtpState::SelectAccount => self.select_account_input(key_code),
+			ImportOtpState::ConfirmOverwrite(account_id) => {
+				if let Some(confirm) = evaluate_yes_no_answer(key_code) {
+					if confirm {
+						self.attach_to_account(account_id);
+					} else {
+						self.internal_state = ImportOtpState::SelectAccount;
+					}
+				}
+			}
+			ImportOtpState::Summary => {
+				if key_code == KeyCode::Enter || key_code == KeyCode::Esc {
+					self.next_state = Some(Transition::ToMainMenu);
+				}
+			}
+		}
+	}
+
+	fn next_state(&self) -> Option<Transition> {
+		self.next_state.clone()
+	}
+}
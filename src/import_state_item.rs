@@ -0,0 +1,537 @@
+use std::sync::{Arc, Mutex};
+use crossterm::event::KeyCode;
+use crate::bitwarden::{import_bitwarden, is_password_protected, BitwardenError};
+use crate::csv_import::{get_column_targets, import_csv, remember_column_targets, ColumnTarget, CsvFile};
+use crate::database_context::DatabaseManager;
+use crate::file_accesssor::{expand_user_path, read_file_from_disk};
+use crate::input_handler::get_text_input;
+use crate::kdbx::{KdbxError, KdbxKey};
+use crate::keepass::import_keepass;
+use crate::onepassword::import_onepassword;
+use crate::password_widget::PasswordWidget;
+use crate::portable_export::{import_portable, is_portable_export, PortableError};
+use crate::state_item::StateItem;
+use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
+use crate::transition::Transition;
+use crate::vault_import::{find_conflicts, get_field_diff, insert_entries, ConflictResolution, ImportConflict, ImportFormat, ImportResult};
+
+enum ImportState {
+	SelectFormat,
+	EnterPath,
+	EnterPassword,
+	EnterKeyFile,
+	MapColumns,
+	ReviewConflicts,
+	Summary,
+}
+
+/// Values of this many rows are shown as preview next to each CSV column.
+const PREVIEW_ROWS: usize = 3;
+
+pub struct ImportStateItem {
+	selected_format: usize,
+	file_data: Vec<u8>,
+	input_buffer: String,
+	password_buffer: String,
+	password_widget: PasswordWidget,
+	csv_file: Option<CsvFile>,
+	column_targets: Vec<ColumnTarget>,
+	selected_column: usize,
+	pending: ImportResult,
+	conflicts: Vec<ImportConflict>,
+	current_conflict: usize,
+	apply_to_all: bool,
+	import_error: Option<String>,
+	imported_count: usize,
+	unmapped: Vec<String>,
+	internal_state: ImportState,
+	next_state: Option<Transition>,
+	db_manager: Arc<Mutex<DatabaseManager>>,
+}
+
+impl ImportStateItem {
+	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>) -> Self {
+		Self {
+			selected_format: 0,
+			file_data: Vec::new(),
+			input_buffer: String::new(),
+			password_buffer: String::new(),
+			password_widget: PasswordWidget::new(String::new()),
+			csv_file: None,
+			column_targets: Vec::new(),
+			selected_column: 0,
+			pending: ImportResult::default(),
+			conflicts: Vec::new(),
+			current_conflict: 0,
+			apply_to_all: false,
+			import_error: None,
+			imported_count: 0,
+			unmapped: Vec::new(),
+			internal_state: ImportState::SelectFormat,
+			next_state: None,
+			db_manager,
+		}
+	}
+
+	fn get_format(&self) -> ImportFormat {
+		ImportFormat::ALL[self.selected_format]
+	}
+
+	fn read_file(&mut self) {
+		let path = expand_user_path(self.input_buffer.trim());
+		match read_file_from_disk(&path) {
+			Ok(data) => {
+				self.file_data = data;
+				self.import_error = None;
+				self.input_buffer.clear();
+				match self.get_format() {
+					ImportFormat::KeePass => self.internal_state = ImportState::EnterPassword,
+					ImportFormat::Bitwarden => self.check_bitwarden_protection(),
+					ImportFormat::OnePassword => self.import_onepassword_export(),
+					ImportFormat::Csv => self.read_csv_file(),
+					ImportFormat::Portable => {
+						if is_portable_export(&self.file_data) {
+							self.internal_state = ImportState::EnterPassword;
+						} else {
+							self.file_data.clear();
+							self.import_error = Some(PortableError::NotPortable.get_message());
+						}
+					}
+				}
+			}
+			Err(e) => self.import_error = Some(format!("{} {}", get_texts().import.read_failed, e)),
+		}
+	}
+
+	/// Only asks for a password if the Bitwarden export was protected with one.
+	fn check_bitwarden_protection(&mut self) {
+		match is_password_protected(&self.file_data) {
+			Ok(true) => self.internal_state = ImportState::EnterPassword,
+			Ok(false) => self.import_bitwarden_export(),
+			Err(e) => {
+				self.file_data.clear();
+				self.import_error = Some(e.get_message());
+				self.internal_state = ImportState::EnterPath;
+			}
+		}
+	}
+
+	fn import_bitwarden_export(&mut self) {
+		let password = std::mem::take(&mut self.password_buffer);
+		self.password_widget.update_password(String::new());
+
+		match import_bitwarden(&self.file_data, Some(password.as_str()).filter(|password| !password.is_empty())) {
+			Ok(result) => self.insert_result(result),
+			Err(e) => {
+				self.internal_state = match e {
+					BitwardenError::WrongPassword => ImportState::EnterPassword,
+					_ => ImportState::EnterPath,
+				};
+				self.import_error = Some(e.get_message());
+			}
+		}
+	}
+
+	fn import_onepassword_export(&mut self) {
+		match import_onepassword(&self.file_data) {
+			Ok(result) => self.insert_result(result),
+			Err(e) => {
+				self.file_data.clear();
+				self.import_error = Some(e.get_message());
+			}
+		}
+	}
+
+	/// Reads the key file, if one was given, and imports the KeePass database with the entered credentials.
+	fn import_keepass_database(&mut self) {
+		let key_file = match self.input_buffer.trim() {
+			"" => None,
+			path => match read_file_from_disk(&expand_user_path(path)) {
+				Ok(data) => Some(data),
+				Err(e) => {
+					self.import_error = Some(format!("{} {}", get_texts().import.read_failed, e));
+					return;
+				}
+			},
+		};
+		let password = Some(self.password_buffer.clone()).filter(|password| !password.is_empty());
+		self.password_buffer.clear();
+		self.password_widget.update_password(String::new());
+		self.input_buffer.clear();
+
+		match import_keepass(&self.file_data, &KdbxKey::new(password, key_file)) {
+			Ok(result) => self.insert_result(result),
+			Err(e) => {
+				self.internal_state = match e {
+					KdbxError::WrongKey | KdbxError::InvalidKeyFile => ImportState::EnterPassword,
+					_ => ImportState::EnterPath,
+				};
+				self.import_error = Some(e.get_message());
+			}
+		}
+	}
+
+	fn import_portable_export(&mut self) {
+		let passphrase = std::mem::take(&mut self.password_buffer);
+		self.password_widget.update_password(String::new());
+
+		match import_portable(&self.file_data, &passphrase) {
+			Ok(result) => self.insert_result(result),
+			Err(e) => {
+				self.internal_state = match e {
+					PortableError::WrongPassphrase => ImportState::EnterPassword,
+					_ => ImportState::EnterPath,
+				};
+				self.import_error = Some(e.get_message());
+			}
+		}
+	}
+
+	/// Starts with the mapping remembered for the header line of the file, or a guess.
+	fn read_csv_file(&mut self) {
+		match CsvFile::read(&self.file_data) {
+			Ok(file) => {
+				self.column_targets = get_column_targets(&file.headers);
+				self.selected_column = 0;
+				self.csv_file = Some(file);
+				self.internal_state = ImportState::MapColumns;
+			}
+			Err(e) => self.import_error = Some(e.get_message()),
+		}
+		self.file_data.clear();
+	}
+
+	fn import_csv_file(&mut self) {
+		let file = match self.csv_file.take() {
+			Some(file) => file,
+			None => return,
+		};
+		match import_csv(&file, &self.column_targets) {
+			Ok(result) => {
+				remember_column_targets(&file.headers, &self.column_targets);
+				self.insert_result(result);
+			}
+			Err(e) => {
+				self.csv_file = Some(file);
+				self.import_error = Some(e.get_message());
+			}
+		}
+	}
+
+	/// Entries which are already in the vault or imported twice are reviewed first, nothing is written before.
+	fn insert_result(&mut self, result: ImportResult) {
+		self.conflicts = {
+			let database_manager = self.db_manager.lock().unwrap();
+			match database_manager.get_database_context() {
+				Some(context) => find_conflicts(context, &result.entries),
+				None => panic!("Database not initialized"),
+			}
+		};
+		self.file_data.clear();
+		self.pending = result;
+		self.current_conflict = 0;
+		self.import_error = None;
+		if self.conflicts.is_empty() {
+			self.write_entries();
+		} else {
+			self.internal_state = ImportState::ReviewConflicts;
+		}
+	}
+
+	fn resolve_conflict(&mut self, resolution: ConflictResolution) {
+		let end = if self.apply_to_all { self.conflicts.len() } else { self.current_conflict + 1 };
+		for conflict in &mut self.conflicts[self.current_conflict..end] {
+			conflict.resolution = resolution;
+		}
+		self.current_conflict = end;
+		if self.current_conflict >= self.conflicts.len() {
+			self.write_entries();
+		}
+	}
+
+	fn write_entries(&mut self) {
+		{
+			let database_manager = self.db_manager.lock().unwrap();
+			let db_context = match database_manager.get_database_context() {
+				Some(context) => context,
+				None => panic!("Database not initialized"),
+			};
+			insert_entries(db_context, &self.pending.entries, &self.conflicts);
+			database_manager.safe_database();
+		}
+		let result = std::mem::take(&mut self.pending);
+		self.imported_count = result.entries.len() - self.count_resolutions(ConflictResolution::Skip);
+		self.unmapped = result.unmapped;
+		self.internal_state = ImportState::Summary;
+	}
+
+	fn count_resolutions(&self, resolution: ConflictResolution) -> usize {
+		self.conflicts.iter().filter(|conflict| conflict.resolution == resolution).count()
+	}
+
+	fn get_prompt(&self, prompt: &String) -> String {
+		match &self.import_error {
+			Some(e) => format!("{} {}", prompt, e),
+			None => prompt.clone(),
+		}
+	}
+
+	fn show_formats(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().import;
+		context.print_at_position(0, 2, &texts.format_prompt);
+		for (idx, format) in ImportFormat::ALL.iter().enumerate() {
+			if idx == self.selected_format {
+				context.print_styled_at_position(0, 4 + idx as u16, format.get_name(), StyleAttribute::InverseColor);
+			} else {
+				context.print_at_position(0, 4 + idx as u16, format.get_name());
+			}
+		}
+	}
+
+	/// Lists what could not be imported, as far as it fits on the screen.
+	fn show_summary(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().import;
+		context.print_at_position(0, 2, &texts.summary.replacen("{}", &self.imported_count.to_string(), 1));
+		if !self.conflicts.is_empty() {
+			let mut summary = texts.conflict_summary.clone();
+			for resolution in [ConflictResolution::Skip, ConflictResolution::Overwrite, ConflictResolution::Merge, ConflictResolution::KeepBoth] {
+				summary = summary.replacen("{}", &self.count_resolutions(resolution).to_string(), 1);
+			}
+			context.print_at_position(0, 3, &summary);
+		}
+		if self.unmapped.is_empty() {
+			return;
+		}
+
+		context.print_at_position(0, 4, &texts.unmapped_heading);
+		let footer_height = 3;
+		let available_rows = context.get_height().saturating_sub(5 + footer_height + 1).max(1) as usize;
+		let max_len = (context.get_width() - 1) as usize;
+		let shown_rows = if self.unmapped.len() > available_rows { available_rows - 1 } else { self.unmapped.len() };
+		for (idx, line) in self.unmapped.iter().take(shown_rows).enumerate() {
+			let line: String = line.chars().take(max_len).collect();
+			context.print_at_position(0, 5 + idx as u16, &line);
+		}
+		if shown_rows < self.unmapped.len() {
+			let more = texts.more_unmapped.replacen("{}", &(self.unmapped.len() - shown_rows).to_string(), 1);
+			context.print_at_position(0, 5 + shown_rows as u16, &more);
+		}
+	}
+
+	/// Lists the columns with the field they are imported as and the values of the first rows.
+	fn show_column_mapping(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().import;
+		let file = match &self.csv_file {
+			Some(file) => file,
+			None => return,
+		};
+		context.print_at_position(0, 2, self.get_format().get_name());
+		match &self.import_error {
+			Some(e) => context.print_at_position(0, 3, e),
+			None => context.print_at_position(0, 3, &texts.csv_rows.replacen("{}", &file.rows.len().to_string(), 1)),
+		}
+
+		let header_width = file.headers.iter().map(|header| header.chars().count()).max().unwrap_or(0)
+			.max(texts.csv_column_heading.chars().count()).min(24);
+		let target_width = ColumnTarget::ALL.iter().map(|target| target.get_name().chars().count()).max().unwrap_or(0)
+			.max(texts.csv_target_heading.chars().count());
+		let max_len = (context.get_width() - 1) as usize;
+		let heading = format!("{:<header_width$} {:<target_width$} {}", texts.csv_column_heading, texts.csv_target_heading, texts.csv_preview_heading);
+		context.print_styled_at_position(0, 5, &truncate(&heading, max_len), StyleAttribute::Bold);
+
+		let footer_height = 3;
+		let visible_rows = context.get_height().saturating_sub(6 + footer_height + 1).max(1) as usize;
+		let first_column = self.selected_column.saturating_sub(visible_rows - 1);
+		for (idx, header) in file.headers.iter().enumerate().skip(first_column).take(visible_rows) {
+			let preview: Vec<String> = (0..file.rows.len().min(PREVIEW_ROWS)).map(|row| file.get_value(row, idx).replace(['\r', '\n'], " ")).collect();
+			let line = format!("{:<header_width$} {:<target_width$} {}", truncate(header, header_width), self.column_targets[idx].get_name(), preview.join(" | "));
+			let y = 6 + (idx - first_column) as u16;
+			if idx == self.selected_column {
+				context.print_styled_at_position(0, y, &truncate(&line, max_len), StyleAttribute::InverseColor);
+			} else {
+				context.print_at_position(0, y, &truncate(&line, max_len));
+			}
+		}
+	}
+
+	fn map_columns_input(&mut self, key_code: KeyCode) {
+		let column_count = self.column_targets.len();
+		let target_idx = ColumnTarget::ALL.iter().position(|target| *target == self.column_targets[self.selected_column]).unwrap_or(0);
+		match key_code {
+			KeyCode::Up => self.selected_column = self.selected_column.checked_sub(1).unwrap_or(column_count - 1),
+			KeyCode::Down => self.selected_column = (self.selected_column + 1) % column_count,
+			KeyCode::Left => self.column_targets[self.selected_column] = ColumnTarget::ALL[target_idx.checked_sub(1).unwrap_or(ColumnTarget::ALL.len() - 1)],
+			KeyCode::Right => self.column_targets[self.selected_column] = ColumnTarget::ALL[(target_idx + 1) % ColumnTarget::ALL.len()],
+			KeyCode::Enter => {
+				self.import_error = None;
+				self.import_csv_file();
+				return;
+			}
+			KeyCode::Esc => {
+				self.csv_file = None;
+				self.internal_state = ImportState::EnterPath;
+			}
+			_ => return,
+		}
+		self.import_error = None;
+	}
+
+	/// Shows the entry of the vault, or the one imported before, and the imported one side by side, differing fields are marked.
+	fn show_conflict(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().import;
+		let conflict = &self.conflicts[self.current_conflict];
+		let imported = &self.pending.entries[conflict.entry_idx].account;
+		let username = imported.get_username();
+		let name = if username.is_empty() { imported.account_name.clone() } else { format!("{} ({})", imported.account_name, username) };
+		let heading = if conflict.earlier_idx.is_some() { &texts.conflict_duplicate_heading } else { &texts.conflict_heading };
+		let heading = heading.replacen("{}", &(self.current_conflict + 1).to_string(), 1)
+			.replacen("{}", &self.conflicts.len().to_string(), 1).replacen("{}", &name, 1);
+		context.print_styled_at_position(0, 2, &heading, StyleAttribute::Bold);
+
+		let diff = get_field_diff(&conflict.existing, imported);
+		let label_width = diff.iter().map(|field| field.label.chars().count()).max().unwrap_or(0);
+		let value_width = (context.get_width() as usize).saturating_sub(label_width + 5) / 2;
+		let existing_heading = if conflict.earlier_idx.is_some() { &texts.conflict_earlier_heading } else { &texts.conflict_existing_heading };
+		let heading = format!("{:<label_width$} {:<value_width$}   {}", "", existing_heading, texts.conflict_imported_heading);
+		context.print_styled_at_position(0, 4, &heading, StyleAttribute::Bold);
+
+		let footer_height = 3;
+		let visible_rows = context.get_height().saturating_sub(5 + footer_height + 1) as usize;
+		let display_value = |value: &str, hidden: bool| {
+			if hidden && !value.is_empty() { "********".to_string() } else { truncate(&value.replace(['\r', '\n'], " "), value_width) }
+		};
+		for (idx, field) in diff.iter().take(visible_rows).enumerate() {
+			let marker = if field.is_different() { "≠" } else { "=" };
+			let line = format!("{:<label_width$} {:<value_width$} {} {}", field.label, display_value(&field.existing, field.hidden), marker,
+				display_value(&field.imported, field.hidden));
+			context.print_at_position(0, 5 + idx as u16, &line);
+		}
+	}
+
+	fn review_conflict_input(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Char('s') => self.resolve_conflict(ConflictResolution::Skip),
+			KeyCode::Char('o') => self.resolve_conflict(ConflictResolution::Overwrite),
+			KeyCode::Char('k') => self.resolve_conflict(ConflictResolution::KeepBoth),
+			KeyCode::Char('m') => self.resolve_conflict(ConflictResolution::Merge),
+			KeyCode::Char('a') => self.apply_to_all = !self.apply_to_all,
+			// Nothing has been written yet, cancelling leaves the vault as it was
+			KeyCode::Esc => self.next_state = Some(Transition::ToMainMenu),
+			_ => {}
+		}
+	}
+
+	fn select_format_input(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Up => self.selected_format = self.selected_format.checked_sub(1).unwrap_or(ImportFormat::ALL.len() - 1),
+			KeyCode::Down => self.selected_format = (self.selected_format + 1) % ImportFormat::ALL.len(),
+			KeyCode::Enter => self.internal_state = ImportState::EnterPath,
+			KeyCode::Esc => self.next_state = Some(Transition::ToMainMenu),
+			_ => {}
+		}
+	}
+}
+
+impl StateItem for ImportStateItem {
+	fn display(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().import;
+		context.print_styled_at_position(0, 0, &texts.heading, StyleAttribute::Underline);
+
+		match self.internal_state {
+			ImportState::SelectFormat => {
+				self.show_formats(context);
+				let input = &get_texts().input;
+				context.draw_control_footer(vec![&input.down_arrow, &input.up_arrow, &input.enter, &input.escape]);
+			}
+			ImportState::EnterPath => {
+				context.print_at_position(0, 2, self.get_format().get_name());
+				context.draw_input_footer(&self.get_prompt(&texts.path_prompt), self.input_buffer.clone());
+			}
+			ImportState::EnterPassword => {
+				context.print_at_position(0, 2, self.get_format().get_name());
+				let prompt = match self.get_format() {
+					ImportFormat::KeePass => &texts.password_prompt,
+					ImportFormat::Portable => &texts.portable_password_prompt,
+					_ => &texts.bitwarden_password_prompt,
+				};
+				let password = self.password_widget.get_password_to_display().cloned().unwrap_or_default();
+				context.draw_input_footer(&self.get_prompt(prompt), password);
+			}
+			ImportState::EnterKeyFile => {
+				context.print_at_position(0, 2, self.get_format().get_name());
+				context.draw_input_footer(&self.get_prompt(&texts.key_file_prompt), self.input_buffer.clone());
+			}
+			ImportState::MapColumns => {
+				self.show_column_mapping(context);
+				let input = &get_texts().input;
+				context.draw_control_footer(vec![&input.down_arrow, &input.up_arrow, &texts.csv_change_input, &input.enter, &input.escape]);
+			}
+			ImportState::ReviewConflicts => {
+				self.show_conflict(context);
+				let apply_to_all = if self.apply_to_all { &texts.apply_to_all_on_input } else { &texts.apply_to_all_off_input };
+				context.draw_control_footer(vec![&texts.conflict_skip_input, &texts.conflict_overwrite_input, &texts.conflict_keep_both_input,
+					&texts.conflict_merge_input, apply_to_all, &get_texts().input.escape]);
+			}
+			ImportState::Summary => {
+				self.show_summary(context);
+				context.draw_control_footer(vec![&get_texts().input.enter]);
+			}
+		}
+	}
+
+	fn update_display(&self) -> bool {
+		false
+	}
+
+	fn register_input(&mut self, key_code: KeyCode) {
+		match self.internal_state {
+			ImportState::SelectFormat => self.select_format_input(key_code),
+			ImportState::EnterPath => {
+				if key_code == KeyCode::Esc {
+					self.import_error = None;
+					self.internal_state = ImportState::SelectFormat;
+				} else if get_text_input(key_code, &mut self.input_buffer) {
+					self.read_file();
+				}
+			}
+			ImportState::EnterPassword => {
+				if key_code == KeyCode::Esc {
+					self.import_error = None;
+					self.internal_state = ImportState::EnterPath;
+				} else if get_text_input(key_code, &mut self.password_buffer) {
+					self.import_error = None;
+					match self.get_format() {
+						ImportFormat::KeePass => self.internal_state = ImportState::EnterKeyFile,
+						ImportFormat::Portable => self.import_portable_export(),
+						_ => self.import_bitwarden_export(),
+					}
+				}
+				self.password_widget.update_password(self.password_buffer.clone());
+			}
+			ImportState::EnterKeyFile => {
+				if key_code == KeyCode::Esc {
+					self.input_buffer.clear();
+					self.internal_state = ImportState::EnterPassword;
+				} else if get_text_input(key_code, &mut self.input_buffer) {
+					self.import_keepass_database();
+				}
+			}
+			ImportState::MapColumns => self.map_columns_input(key_code),
+			ImportState::ReviewConflicts => self.review_conflict_input(key_code),
+			ImportState::Summary => {
+				if key_code == KeyCode::Enter || key_code == KeyCode::Esc {
+					self.next_state = Some(Transition::ToMainMenu);
+				}
+			}
+		}
+	}
+
+	fn next_state(&self) -> Option<Transition> {
+		self.next_state.clone()
+	}
+}
+
+fn truncate(text: &str, max_len: usize) -> String {
+	text.chars().take(max_len).collect()
+}
@@ -0,0 +1,914 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use aes::Aes256;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit, StreamCipher};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20::ChaCha20;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, hmac};
+use roxmltree::{Document, Node, NodeId};
+use twofish::Twofish;
+use crate::date_util::parse_date;
+use crate::texts::get_texts;
+use crate::xml_util::escape_xml;
+
+const SIGNATURE_1: u32 = 0x9AA2D903;
+const SIGNATURE_2: u32 = 0xB54BFB67;
+const SUPPORTED_MAJOR_VERSION: u32 = 4;
+const WRITTEN_VERSION: u32 = 0x00040000;
+
+const HEADER_END: u8 = 0;
+const HEADER_CIPHER_ID: u8 = 2;
+const HEADER_COMPRESSION: u8 = 3;
+const HEADER_MASTER_SEED: u8 = 4;
+const HEADER_ENCRYPTION_IV: u8 = 7;
+const HEADER_KDF_PARAMETERS: u8 = 11;
+
+const INNER_HEADER_END: u8 = 0;
+const INNER_HEADER_STREAM_ID: u8 = 1;
+const INNER_HEADER_STREAM_KEY: u8 = 2;
+const INNER_HEADER_BINARY: u8 = 3;
+
+const CIPHER_AES256: [u8; 16] = uuid(0x31c1f2e6bf714350be5805216afc5aff);
+const CIPHER_CHACHA20: [u8; 16] = uuid(0xd6038a2b8b6f4cb5a524339a31dbb59a);
+const CIPHER_TWOFISH: [u8; 16] = uuid(0xad68f29f576f4bb9a36ad47af965e35c);
+const KDF_AES: [u8; 16] = uuid(0xc9d9f39a628a4460bf740d08c18a4fea);
+const KDF_ARGON2D: [u8; 16] = uuid(0xef636ddf8c29444b91f7a9a403e30a0c);
+const KDF_ARGON2ID: [u8; 16] = uuid(0x9e298b1956db4773b23dfc3ec6f0a1e6);
+
+const STREAM_NONE: u32 = 0;
+const STREAM_CHACHA20: u32 = 3;
+
+/// Argon2d with the memory KeePassXC uses by default, opening the file takes about a second.
+const ARGON2_MEMORY: u64 = 64 * 1024 * 1024;
+const ARGON2_ITERATIONS: u64 = 10;
+const ARGON2_PARALLELISM: u32 = 2;
+const ARGON2_VERSION: u32 = 0x13;
+/// Limits for the key derivation settings read from a file, so a crafted database can not keep the
+/// import busy for hours or take all memory. They are far above what KeePass and KeePassXC offer.
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+const MAX_ARGON2_MEMORY: u64 = 2 * 1024 * 1024 * 1024;
+const MAX_ARGON2_ITERATIONS: u64 = 1000;
+const WRITTEN_BLOCK_SIZE: usize = 1024 * 1024;
+const GENERATOR: &str = "Rusty Password Manager";
+
+/// Times in KDBX 4 are seconds since 0001-01-01, this is the offset to the unix epoch.
+const SECONDS_BEFORE_UNIX_EPOCH: i64 = 62135596800;
+
+const fn uuid(value: u128) -> [u8; 16] {
+	value.to_be_bytes()
+}
+
+pub enum KdbxError {
+	NotKdbx,
+	UnsupportedVersion,
+	UnsupportedCipher,
+	UnsupportedKdf,
+	TooMuchWork,
+	InvalidKeyFile,
+	WrongKey,
+	Corrupted,
+}
+
+impl KdbxError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().import;
+		match self {
+			KdbxError::NotKdbx => texts.not_kdbx.clone(),
+			KdbxError::UnsupportedVersion => texts.unsupported_kdbx_version.clone(),
+			KdbxError::UnsupportedCipher => texts.unsupported_cipher.clone(),
+			KdbxError::UnsupportedKdf => texts.unsupported_kdf.clone(),
+			KdbxError::TooMuchWork => texts.too_much_work.clone(),
+			KdbxError::InvalidKeyFile => texts.invalid_key_file.clone(),
+			KdbxError::WrongKey => texts.wrong_key.clone(),
+			KdbxError::Corrupted => texts.corrupted_file.clone(),
+		}
+	}
+}
+
+/// The credentials of a KeePass database, a password, a key file or both.
+pub struct KdbxKey {
+	password: Option<String>,
+	key_file: Option<Vec<u8>>,
+}
+
+impl KdbxKey {
+	pub fn new(password: Option<String>, key_file: Option<Vec<u8>>) -> Self {
+		Self { password, key_file }
+	}
+
+	fn get_composite_key(&self) -> Result<Vec<u8>, KdbxError> {
+		let mut components = Vec::new();
+		if let Some(password) = &self.password {
+			components.extend_from_slice(&sha256(&[password.as_bytes()]));
+		}
+		if let Some(key_file) = &self.key_file {
+			components.extend_from_slice(&read_key_file(key_file)?);
+		}
+		Ok(sha256(&[&components]).to_vec())
+	}
+}
+
+/// KeePass accepts XML key files, 32 raw bytes, 64 hex digits or any other file, which is hashed.
+fn read_key_file(content: &[u8]) -> Result<Vec<u8>, KdbxError> {
+	if let Some(document) = std::str::from_utf8(content).ok().and_then(|text| Document::parse(text).ok()) {
+		if document.root_element().has_tag_name("KeyFile") {
+			return read_xml_key_file(&document).ok_or(KdbxError::InvalidKeyFile);
+		}
+	}
+	if content.len() == 32 {
+		return Ok(content.to_vec());
+	}
+	if content.len() == 64 {
+		if let Some(key) = std::str::from_utf8(content).ok().and_then(decode_hex) {
+			return Ok(key);
+		}
+	}
+	Ok(sha256(&[content]).to_vec())
+}
+
+/// Version 1.0 stores the key in base64, version 2.0 in hex with the start of its hash as checksum.
+fn read_xml_key_file(document: &Document) -> Option<Vec<u8>> {
+	let root = document.root_element();
+	let version = get_child_text(get_child(root, "Meta")?, "Version");
+	let data = get_child(get_child(root, "Key")?, "Data")?;
+	let text: String = data.text().unwrap_or_default().chars().filter(|c| !c.is_whitespace()).collect();
+	if version.starts_with("2.") {
+		let key = decode_hex(&text)?;
+		let checksum = data.attribute("Hash").and_then(decode_hex);
+		if checksum.is_some_and(|checksum| !sha256(&[&key]).starts_with(&checksum)) {
+			return None;
+		}
+		Some(key)
+	} else {
+		BASE64.decode(text).ok()
+	}
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+	if !text.len().is_multiple_of(2) {
+		return None;
+	}
+	(0..text.len()).step_by(2).map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok()).collect()
+}
+
+pub struct KdbxDatabase {
+	pub root: KdbxGroup,
+}
+
+pub struct KdbxGroup {
+	pub name: String,
+	pub groups: Vec<KdbxGroup>,
+	pub entries: Vec<KdbxEntry>,
+	pub is_recycle_bin: bool,
+}
+
+impl KdbxGroup {
+	pub fn new(name: &str) -> Self {
+		Self { name: name.to_string(), groups: Vec::new(), entries: Vec::new(), is_recycle_bin: false }
+	}
+}
+
+pub struct KdbxEntry {
+	pub fields: Vec<KdbxField>,
+	pub attachments: Vec<KdbxAttachment>,
+	pub tags: Vec<String>,
+	pub created_at: Option<SystemTime>,
+	pub modified_at: Option<SystemTime>,
+	pub expires_at: Option<SystemTime>,
+	/// Older versions of the entry, the oldest first.
+	pub history: Vec<KdbxEntry>,
+}
+
+impl KdbxEntry {
+	pub fn get_field(&self, key: &str) -> Option<&str> {
+		self.fields.iter().find(|field| field.key == key).map(|field| field.value.as_str())
+	}
+}
+
+pub struct KdbxField {
+	pub key: String,
+	pub value: String,
+	pub protected: bool,
+}
+
+pub struct KdbxAttachment {
+	pub name: String,
+	pub data: Vec<u8>,
+}
+
+/// A value of the KDF parameters. Booleans and strings are not used by any supported KDF.
+#[derive(Clone)]
+enum Variant {
+	UInt32(u32),
+	UInt64(u64),
+	Int32(i32),
+	Int64(i64),
+	Bytes(Vec<u8>),
+	Other,
+}
+
+struct ByteReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+	fn new(data: &'a [u8]) -> Self {
+		Self { data, pos: 0 }
+	}
+
+	fn take(&mut self, length: usize) -> Result<&'a [u8], KdbxError> {
+		let end = self.pos.checked_add(length).filter(|end| *end <= self.data.len()).ok_or(KdbxError::Corrupted)?;
+		let bytes = &self.data[self.pos..end];
+		self.pos = end;
+		Ok(bytes)
+	}
+
+	fn read_u8(&mut self) -> Result<u8, KdbxError> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn read_u16(&mut self) -> Result<u16, KdbxError> {
+		Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+	}
+
+	fn read_u32(&mut self) -> Result<u32, KdbxError> {
+		Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+	}
+
+	fn read_u64(&mut self) -> Result<u64, KdbxError> {
+		Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+	}
+}
+
+struct OuterHeader {
+	cipher_id: Vec<u8>,
+	compressed: bool,
+	master_seed: Vec<u8>,
+	encryption_iv: Vec<u8>,
+	kdf_parameters: HashMap<String, Variant>,
+}
+
+fn read_outer_header(reader: &mut ByteReader) -> Result<OuterHeader, KdbxError> {
+	let mut header = OuterHeader {
+		cipher_id: Vec::new(),
+		compressed: false,
+		master_seed: Vec::new(),
+		encryption_iv: Vec::new(),
+		kdf_parameters: HashMap::new(),
+	};
+	loop {
+		let field_id = reader.read_u8()?;
+		let length = reader.read_u32()? as usize;
+		let data = reader.take(length)?;
+		match field_id {
+			HEADER_END => break,
+			HEADER_CIPHER_ID => header.cipher_id = data.to_vec(),
+			HEADER_COMPRESSION => header.compressed = data.first().is_some_and(|flag| *flag != 0),
+			HEADER_MASTER_SEED => header.master_seed = data.to_vec(),
+			HEADER_ENCRYPTION_IV => header.encryption_iv = data.to_vec(),
+			HEADER_KDF_PARAMETERS => header.kdf_parameters = read_variant_dictionary(data)?,
+			_ => {}
+		}
+	}
+	if header.master_seed.len() != 32 {
+		return Err(KdbxError::Corrupted);
+	}
+	Ok(header)
+}
+
+fn read_variant_dictionary(data: &[u8]) -> Result<HashMap<String, Variant>, KdbxError> {
+	let mut reader = ByteReader::new(data);
+	let _version = reader.read_u16()?;
+	let mut dictionary = HashMap::new();
+	loop {
+		let value_type = reader.read_u8()?;
+		if value_type == 0 {
+			break;
+		}
+		let key_length = reader.read_u32()? as usize;
+		let key = String::from_utf8_lossy(reader.take(key_length)?).to_string();
+		let value_length = reader.read_u32()? as usize;
+		let mut value_reader = ByteReader::new(reader.take(value_length)?);
+		let value = match value_type {
+			0x04 => Variant::UInt32(value_reader.read_u32()?),
+			0x05 => Variant::UInt64(value_reader.read_u64()?),
+			0x0C => Variant::Int32(value_reader.read_u32()? as i32),
+			0x0D => Variant::Int64(value_reader.read_u64()? as i64),
+			0x42 => Variant::Bytes(value_reader.data.to_vec()),
+			0x08 | 0x18 => Variant::Other,
+			_ => return Err(KdbxError::Corrupted),
+		};
+		dictionary.insert(key, value);
+	}
+	Ok(dictionary)
+}
+
+fn get_variant_bytes<'a>(dictionary: &'a HashMap<String, Variant>, key: &str) -> Result<&'a [u8], KdbxError> {
+	match dictionary.get(key) {
+		Some(Variant::Bytes(bytes)) => Ok(bytes),
+		_ => Err(KdbxError::Corrupted),
+	}
+}
+
+fn get_variant_number(dictionary: &HashMap<String, Variant>, key: &str) -> Result<u64, KdbxError> {
+	match dictionary.get(key) {
+		Some(Variant::UInt32(value)) => Ok(*value as u64),
+		Some(Variant::UInt64(value)) => Ok(*value),
+		Some(Variant::Int32(value)) if *value >= 0 => Ok(*value as u64),
+		Some(Variant::Int64(value)) if *value >= 0 => Ok(*value as u64),
+		_ => Err(KdbxError::Corrupted),
+	}
+}
+
+/// Turns the composite key into the key the database is encrypted with, using AES-KDF or Argon2.
+fn transform_key(kdf_parameters: &HashMap<String, Variant>, composite_key: &[u8]) -> Result<Vec<u8>, KdbxError> {
+	let kdf_id = get_variant_bytes(kdf_parameters, "$UUID")?;
+	if kdf_id == KDF_AES {
+		let seed = get_variant_bytes(kdf_parameters, "S")?;
+		let rounds = get_variant_number(kdf_parameters, "R")?;
+		if rounds > MAX_AES_KDF_ROUNDS {
+			return Err(KdbxError::TooMuchWork);
+		}
+		let cipher = Aes256::new_from_slice(seed).map_err(|_| KdbxError::Corrupted)?;
+		let mut blocks = [aes::Block::clone_from_slice(&composite_key[..16]), aes::Block::clone_from_slice(&composite_key[16..])];
+		for _ in 0..rounds {
+			cipher.encrypt_blocks(&mut blocks);
+		}
+		return Ok(sha256(&[&blocks[0], &blocks[1]]).to_vec());
+	}
+
+	let algorithm = match kdf_id {
+		id if id == KDF_ARGON2D => Algorithm::Argon2d,
+		id if id == KDF_ARGON2ID => Algorithm::Argon2id,
+		_ => return Err(KdbxError::UnsupportedKdf),
+	};
+	let version = match get_variant_number(kdf_parameters, "V")? {
+		0x10 => Version::V0x10,
+		0x13 => Version::V0x13,
+		_ => return Err(KdbxError::UnsupportedKdf),
+	};
+	let salt = get_variant_bytes(kdf_parameters, "S")?;
+	let memory = get_variant_number(kdf_parameters, "M")?;
+	let iterations = get_variant_number(kdf_parameters, "I")?;
+	if memory > MAX_ARGON2_MEMORY || iterations > MAX_ARGON2_ITERATIONS {
+		return Err(KdbxError::TooMuchWork);
+	}
+	let memory_kib = (memory / 1024) as u32;
+	let iterations = iterations as u32;
+	let parallelism = u32::try_from(get_variant_number(kdf_parameters, "P")?).map_err(|_| KdbxError::UnsupportedKdf)?;
+	let params = Params::new(memory_kib, iterations, parallelism, Some(32)).map_err(|_| KdbxError::UnsupportedKdf)?;
+
+	let mut transformed_key = vec![0u8; 32];
+	Argon2::new(algorithm, version, params)
+		.hash_password_into(composite_key, salt, &mut transformed_key)
+		.map_err(|_| KdbxError::UnsupportedKdf)?;
+	Ok(transformed_key)
+}
+
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+	let mut context = digest::Context::new(&digest::SHA256);
+	for part in parts {
+		context.update(part);
+	}
+	context.finish().as_ref().try_into().unwrap()
+}
+
+fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+	let mut context = digest::Context::new(&digest::SHA512);
+	for part in parts {
+		context.update(part);
+	}
+	context.finish().as_ref().try_into().unwrap()
+}
+
+/// Every block has its own HMAC key, the header uses the index u64::MAX.
+fn get_block_hmac_key(hmac_key: &[u8], index: u64) -> hmac::Key {
+	hmac::Key::new(hmac::HMAC_SHA256, &sha512(&[&index.to_le_bytes(), hmac_key]))
+}
+
+/// Reads the HMAC protected blocks following the header until the empty end block.
+fn read_blocks(reader: &mut ByteReader, hmac_key: &[u8]) -> Result<Vec<u8>, KdbxError> {
+	let mut data = Vec::new();
+	for index in 0u64.. {
+		let block_hmac = reader.take(32)?;
+		let length = reader.read_u32()?;
+		let block = reader.take(length as usize)?;
+		let message = [&index.to_le_bytes()[..], &length.to_le_bytes(), block].concat();
+		hmac::verify(&get_block_hmac_key(hmac_key, index), &message, block_hmac).map_err(|_| KdbxError::Corrupted)?;
+		if length == 0 {
+			break;
+		}
+		data.extend_from_slice(block);
+	}
+	Ok(data)
+}
+
+fn decrypt_payload(cipher_id: &[u8], key: &[u8], iv: &[u8], mut data: Vec<u8>) -> Result<Vec<u8>, KdbxError> {
+	let length = match cipher_id {
+		id if id == CIPHER_AES256 => cbc::Decryptor::<Aes256>::new_from_slices(key, iv).map_err(|_| KdbxError::Corrupted)?
+			.decrypt_padded_mut::<Pkcs7>(&mut data).map_err(|_| KdbxError::Corrupted)?.len(),
+		id if id == CIPHER_TWOFISH => cbc::Decryptor::<Twofish>::new_from_slices(key, iv).map_err(|_| KdbxError::Corrupted)?
+			.decrypt_padded_mut::<Pkcs7>(&mut data).map_err(|_| KdbxError::Corrupted)?.len(),
+		id if id == CIPHER_CHACHA20 => {
+			ChaCha20::new_from_slices(key, iv).map_err(|_| KdbxError::Corrupted)?.apply_keystream(&mut data);
+			data.len()
+		}
+		_ => return Err(KdbxError::UnsupportedCipher),
+	};
+	data.truncate(length);
+	Ok(data)
+}
+
+/// The cipher which hides protected values, like passwords, inside the XML document.
+fn create_inner_stream(stream_id: u32, stream_key: &[u8]) -> Result<Option<ChaCha20>, KdbxError> {
+	match stream_id {
+		STREAM_NONE => Ok(None),
+		STREAM_CHACHA20 => {
+			let hash = sha512(&[stream_key]);
+			Ok(Some(ChaCha20::new_from_slices(&hash[..32], &hash[32..44]).map_err(|_| KdbxError::Corrupted)?))
+		}
+		_ => Err(KdbxError::UnsupportedCipher),
+	}
+}
+
+/// Decrypts a KDBX 4 file and reads its groups and entries.
+pub fn read_kdbx(data: &[u8], key: &KdbxKey) -> Result<KdbxDatabase, KdbxError> {
+	let mut reader = ByteReader::new(data);
+	let signature = (reader.read_u32(), reader.read_u32());
+	if !matches!(signature, (Ok(SIGNATURE_1), Ok(SIGNATURE_2))) {
+		return Err(KdbxError::NotKdbx);
+	}
+	if reader.read_u32()? >> 16 != SUPPORTED_MAJOR_VERSION {
+		return Err(KdbxError::UnsupportedVersion);
+	}
+
+	let header = read_outer_header(&mut reader)?;
+	let header_data = &data[..reader.pos];
+	if reader.take(32)? != sha256(&[header_data]) {
+		return Err(KdbxError::Corrupted);
+	}
+	let header_hmac = reader.take(32)?;
+
+	let transformed_key = transform_key(&header.kdf_parameters, &key.get_composite_key()?)?;
+	let hmac_key = sha512(&[&header.master_seed, &transformed_key, &[1]]);
+	// The header is authenticated with the key, so a mismatch means the credentials are wrong
+	hmac::verify(&get_block_hmac_key(&hmac_key, u64::MAX), header_data, header_hmac).map_err(|_| KdbxError::WrongKey)?;
+
+	let encrypted = read_blocks(&mut reader, &hmac_key)?;
+	let cipher_key = sha256(&[&header.master_seed, &transformed_key]);
+	let mut payload = decrypt_payload(&header.cipher_id, &cipher_key, &header.encryption_iv, encrypted)?;
+	if header.compressed {
+		let mut decompressed = Vec::new();
+		GzDecoder::new(payload.as_slice()).read_to_end(&mut decompressed).map_err(|_| KdbxError::Corrupted)?;
+		payload = decompressed;
+	}
+
+	let mut reader = ByteReader::new(&payload);
+	let mut stream_id = STREAM_NONE;
+	let mut stream_key = Vec::new();
+	let mut binaries = Vec::new();
+	loop {
+		let field_id = reader.read_u8()?;
+		let length = reader.read_u32()? as usize;
+		let field_data = reader.take(length)?;
+		match field_id {
+			INNER_HEADER_END => break,
+			INNER_HEADER_STREAM_ID => stream_id = ByteReader::new(field_data).read_u32()?,
+			INNER_HEADER_STREAM_KEY => stream_key = field_data.to_vec(),
+			// The first byte holds flags, the content follows
+			INNER_HEADER_BINARY => binaries.push(field_data.get(1..).unwrap_or_default().to_vec()),
+			_ => {}
+		}
+	}
+
+	let xml = std::str::from_utf8(&payload[reader.pos..]).map_err(|_| KdbxError::Corrupted)?;
+	let document = Document::parse(xml).map_err(|_| KdbxError::Corrupted)?;
+	let inner_stream = create_inner_stream(stream_id, &stream_key)?;
+	let protected_values = decrypt_protected_values(&document, inner_stream)?;
+
+	let root = document.root_element();
+	let meta = get_child(root, "Meta");
+	let recycle_bin = meta
+		.filter(|meta| get_child_text(*meta, "RecycleBinEnabled").eq_ignore_ascii_case("true"))
+		.map(|meta| get_child_text(meta, "RecycleBinUUID"));
+	let root_group = get_child(root, "Root").and_then(|root| get_child(root, "Group")).ok_or(KdbxError::Corrupted)?;
+
+	let reader = XmlReader { protected_values, binaries, recycle_bin };
+	Ok(KdbxDatabase { root: reader.read_group(root_group) })
+}
+
+/// Protected values are encrypted with one continuous key stream in the order they appear in the document.
+fn decrypt_protected_values(document: &Document, mut inner_stream: Option<ChaCha20>) -> Result<HashMap<NodeId, String>, KdbxError> {
+	let mut values = HashMap::new();
+	let protected_nodes = document.descendants()
+		.filter(|node| node.has_tag_name("Value") && node.attribute("Protected").is_some_and(|flag| flag.eq_ignore_ascii_case("true")));
+	for node in protected_nodes {
+		let mut value = BASE64.decode(node.text().unwrap_or_default().trim()).map_err(|_| KdbxError::Corrupted)?;
+		if let Some(stream) = inner_stream.as_mut() {
+			stream.apply_keystream(&mut value);
+		}
+		values.insert(node.id(), String::from_utf8_lossy(&value).to_string());
+	}
+	Ok(values)
+}
+
+fn get_child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+	node.children().find(|child| child.has_tag_name(name))
+}
+
+fn get_child_text(node: Node, name: &str) -> String {
+	get_child(node, name).and_then(|child| child.text()).unwrap_or_default().to_string()
+}
+
+/// KDBX 4 stores times as base64 encoded seconds since 0001-01-01, older files as ISO 8601 text.
+fn parse_time(value: &str) -> Option<SystemTime> {
+	let seconds = match BASE64.decode(value.trim()).ok().filter(|bytes| bytes.len() == 8) {
+		Some(bytes) => i64::from_le_bytes(bytes.try_into().unwrap()) - SECONDS_BEFORE_UNIX_EPOCH,
+		None => return parse_date(value.get(..10)?),
+	};
+	u64::try_from(seconds).ok().map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+struct XmlReader {
+	protected_values: HashMap<NodeId, String>,
+	binaries: Vec<Vec<u8>>,
+	recycle_bin: Option<String>,
+}
+
+impl XmlReader {
+	fn read_group(&self, node: Node) -> KdbxGroup {
+		let uuid = get_child_text(node, "UUID");
+		KdbxGroup {
+			name: get_child_text(node, "Name"),
+			groups: node.children().filter(|child| child.has_tag_name("Group")).map(|child| self.read_group(child)).collect(),
+			entries: node.children().filter(|child| child.has_tag_name("Entry")).map(|child| self.read_entry(child)).collect(),
+			is_recycle_bin: self.recycle_bin.as_ref().is_some_and(|recycle_bin| *recycle_bin == uuid),
+		}
+	}
+
+	fn read_entry(&self, node: Node) -> KdbxEntry {
+		let mut entry = KdbxEntry {
+			fields: Vec::new(),
+			attachments: Vec::new(),
+			tags: get_child_text(node, "Tags").split([';', ',']).map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect(),
+			created_at: None,
+			modified_at: None,
+			expires_at: None,
+			history: Vec::new(),
+		};
+
+		if let Some(times) = get_child(node, "Times") {
+			entry.created_at = parse_time(&get_child_text(times, "CreationTime"));
+			entry.modified_at = parse_time(&get_child_text(times, "LastModificationTime"));
+			if get_child_text(times, "Expires").eq_ignore_ascii_case("true") {
+				entry.expires_at = parse_time(&get_child_text(times, "ExpiryTime"));
+			}
+		}
+
+		for child in node.children().filter(Node::is_element) {
+			match child.tag_name().name() {
+				"String" => {
+					let value_node = get_child(child, "Value");
+					let protected = value_node.and_then(|value| self.protected_values.get(&value.id()));
+					entry.fields.push(KdbxField {
+						key: get_child_text(child, "Key"),
+						value: protected.cloned().unwrap_or_else(|| value_node.and_then(|value| value.text()).unwrap_or_default().to_string()),
+						protected: protected.is_some(),
+					});
+				}
+				"Binary" => {
+					let data = get_child(child, "Value")
+						.and_then(|value| value.attribute("Ref"))
+						.and_then(|reference| reference.parse::<usize>().ok())
+						.and_then(|reference| self.binaries.get(reference));
+					if let Some(data) = data {
+						entry.attachments.push(KdbxAttachment { name: get_child_text(child, "Key"), data: data.clone() });
+					}
+				}
+				"History" => {
+					entry.history = child.children().filter(|version| version.has_tag_name("Entry")).map(|version| self.read_entry(version)).collect();
+				}
+				_ => {}
+			}
+		}
+		entry
+	}
+}
+
+fn random_bytes(length: usize) -> Vec<u8> {
+	let mut bytes = vec![0u8; length];
+	SystemRandom::new().fill(&mut bytes).expect("Could not generate random bytes");
+	bytes
+}
+
+fn write_field(output: &mut Vec<u8>, field_id: u8, data: &[u8]) {
+	output.push(field_id);
+	output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	output.extend_from_slice(data);
+}
+
+fn write_variant_dictionary(items: &[(&str, Variant)]) -> Vec<u8> {
+	let mut output = 0x0100u16.to_le_bytes().to_vec();
+	for (key, value) in items {
+		let (value_type, data) = match value {
+			Variant::UInt32(value) => (0x04, value.to_le_bytes().to_vec()),
+			Variant::UInt64(value) => (0x05, value.to_le_bytes().to_vec()),
+			Variant::Int32(value) => (0x0C, value.to_le_bytes().to_vec()),
+			Variant::Int64(value) => (0x0D, value.to_le_bytes().to_vec()),
+			Variant::Bytes(bytes) => (0x42, bytes.clone()),
+			Variant::Other => continue,
+		};
+		output.push(value_type);
+		output.extend_from_slice(&(key.len() as u32).to_le_bytes());
+		output.extend_from_slice(key.as_bytes());
+		output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+		output.extend_from_slice(&data);
+	}
+	output.push(0);
+	output
+}
+
+/// Writes a KDBX 4 file, encrypted with AES-256 and a key derived with Argon2d, as KeePassXC does by default.
+pub fn write_kdbx(database: &KdbxDatabase, key: &KdbxKey) -> Result<Vec<u8>, KdbxError> {
+	let master_seed = random_bytes(32);
+	let encryption_iv = random_bytes(16);
+	let kdf_items = [
+		("$UUID", Variant::Bytes(KDF_ARGON2D.to_vec())),
+		("S", Variant::Bytes(random_bytes(32))),
+		("P", Variant::UInt32(ARGON2_PARALLELISM)),
+		("M", Variant::UInt64(ARGON2_MEMORY)),
+		("I", Variant::UInt64(ARGON2_ITERATIONS)),
+		("V", Variant::UInt32(ARGON2_VERSION)),
+	];
+
+	let mut header = Vec::new();
+	header.extend_from_slice(&SIGNATURE_1.to_le_bytes());
+	header.extend_from_slice(&SIGNATURE_2.to_le_bytes());
+	header.extend_from_slice(&WRITTEN_VERSION.to_le_bytes());
+	write_field(&mut header, HEADER_CIPHER_ID, &CIPHER_AES256);
+	write_field(&mut header, HEADER_COMPRESSION, &1u32.to_le_bytes());
+	write_field(&mut header, HEADER_MASTER_SEED, &master_seed);
+	write_field(&mut header, HEADER_ENCRYPTION_IV, &encryption_iv);
+	write_field(&mut header, HEADER_KDF_PARAMETERS, &write_variant_dictionary(&kdf_items));
+	write_field(&mut header, HEADER_END, b"\r\n\r\n");
+
+	let kdf_parameters = kdf_items.iter().map(|(key, value)| (key.to_string(), value.clone())).collect();
+	let transformed_key = transform_key(&kdf_parameters, &key.get_composite_key()?)?;
+	let hmac_key = sha512(&[&master_seed, &transformed_key, &[1]]);
+	let mut output = header.clone();
+	output.extend_from_slice(&sha256(&[&header]));
+	output.extend_from_slice(hmac::sign(&get_block_hmac_key(&hmac_key, u64::MAX), &header).as_ref());
+
+	let stream_key = random_bytes(64);
+	let mut writer = XmlWriter {
+		xml: String::new(),
+		inner_stream: create_inner_stream(STREAM_CHACHA20, &stream_key)?.expect("ChaCha20 is a protecting stream"),
+		binaries: Vec::new(),
+	};
+	writer.write_document(database);
+
+	let mut payload = Vec::new();
+	write_field(&mut payload, INNER_HEADER_STREAM_ID, &STREAM_CHACHA20.to_le_bytes());
+	write_field(&mut payload, INNER_HEADER_STREAM_KEY, &stream_key);
+	for binary in &writer.binaries {
+		write_field(&mut payload, INNER_HEADER_BINARY, &[&[0u8][..], binary].concat());
+	}
+	write_field(&mut payload, INNER_HEADER_END, &[]);
+	payload.extend_from_slice(writer.xml.as_bytes());
+
+	let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+	encoder.write_all(&payload).and_then(|_| encoder.flush()).expect("Could not compress the database");
+	let compressed = encoder.finish().expect("Could not compress the database");
+
+	let cipher_key = sha256(&[&master_seed, &transformed_key]);
+	let mut encrypted = compressed.clone();
+	encrypted.resize(compressed.len() + 16, 0);
+	let encrypted_length = cbc::Encryptor::<Aes256>::new_from_slices(&cipher_key, &encryption_iv).expect("Key and IV have valid lengths")
+		.encrypt_padded_mut::<Pkcs7>(&mut encrypted, compressed.len()).expect("The buffer has room for the padding").len();
+	encrypted.truncate(encrypted_length);
+
+	let mut blocks: Vec<&[u8]> = encrypted.chunks(WRITTEN_BLOCK_SIZE).collect();
+	blocks.push(&[]);
+	for (index, block) in blocks.into_iter().enumerate() {
+		let index = index as u64;
+		let length = (block.len() as u32).to_le_bytes();
+		let message = [&index.to_le_bytes()[..], &length, block].concat();
+		output.extend_from_slice(hmac::sign(&get_block_hmac_key(&hmac_key, index), &message).as_ref());
+		output.extend_from_slice(&length);
+		output.extend_from_slice(block);
+	}
+	Ok(output)
+}
+
+fn format_time(time: SystemTime) -> String {
+	let seconds = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0);
+	BASE64.encode((seconds + SECONDS_BEFORE_UNIX_EPOCH).to_le_bytes())
+}
+
+struct XmlWriter {
+	xml: String,
+	inner_stream: ChaCha20,
+	binaries: Vec<Vec<u8>>,
+}
+
+impl XmlWriter {
+	fn write_document(&mut self, database: &KdbxDatabase) {
+		self.xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile><Meta>");
+		self.xml.push_str(&format!("<Generator>{0}</Generator><DatabaseName>{0}</DatabaseName>", GENERATOR));
+		self.xml.push_str("<MemoryProtection><ProtectTitle>False</ProtectTitle><ProtectUserName>False</ProtectUserName>\
+			<ProtectPassword>True</ProtectPassword><ProtectURL>False</ProtectURL><ProtectNotes>False</ProtectNotes></MemoryProtection>");
+		self.xml.push_str("<RecycleBinEnabled>False</RecycleBinEnabled></Meta><Root>");
+		self.write_group(&database.root);
+		self.xml.push_str("<DeletedObjects/></Root></KeePassFile>");
+	}
+
+	fn write_group(&mut self, group: &KdbxGroup) {
+		self.xml.push_str(&format!("<Group><UUID>{}</UUID><Name>{}</Name>", BASE64.encode(random_bytes(16)), escape_xml(&group.name)));
+		for entry in &group.entries {
+			self.write_entry(entry);
+		}
+		for child in &group.groups {
+			self.write_group(child);
+		}
+		self.xml.push_str("</Group>");
+	}
+
+	fn write_entry(&mut self, entry: &KdbxEntry) {
+		let now = SystemTime::now();
+		let created_at = entry.created_at.unwrap_or(now);
+		self.xml.push_str(&format!("<Entry><UUID>{}</UUID><Tags>{}</Tags>", BASE64.encode(random_bytes(16)), escape_xml(&entry.tags.join(";"))));
+		self.xml.push_str(&format!(
+			"<Times><CreationTime>{}</CreationTime><LastModificationTime>{}</LastModificationTime><LastAccessTime>{}</LastAccessTime>\
+				<ExpiryTime>{}</ExpiryTime><Expires>{}</Expires><UsageCount>0</UsageCount><LocationChanged>{}</LocationChanged></Times>",
+			format_time(created_at),
+			format_time(entry.modified_at.unwrap_or(created_at)),
+			format_time(now),
+			format_time(entry.expires_at.unwrap_or(created_at)),
+			if entry.expires_at.is_some() { "True" } else { "False" },
+			format_time(now),
+		));
+
+		for field in &entry.fields {
+			let value = if field.protected {
+				let mut value = field.value.clone().into_bytes();
+				self.inner_stream.apply_keystream(&mut value);
+				format!("<Value Protected=\"True\">{}</Value>", BASE64.encode(value))
+			} else {
+				format!("<Value>{}</Value>", escape_xml(&field.value))
+			};
+			self.xml.push_str(&format!("<String><Key>{}</Key>{}</String>", escape_xml(&field.key), value));
+		}
+		for attachment in &entry.attachments {
+			self.xml.push_str(&format!("<Binary><Key>{}</Key><Value Ref=\"{}\"/></Binary>", escape_xml(&attachment.name), self.binaries.len()));
+			self.binaries.push(attachment.data.clone());
+		}
+		if !entry.history.is_empty() {
+			self.xml.push_str("<History>");
+			for version in &entry.history {
+				self.write_entry(version);
+			}
+			self.xml.push_str("</History>");
+		}
+		self.xml.push_str("</Entry>");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn argon2_parameters(memory: u64, iterations: u64) -> HashMap<String, Variant> {
+		HashMap::from([
+			("$UUID".to_string(), Variant::Bytes(KDF_ARGON2D.to_vec())),
+			("V".to_string(), Variant::UInt32(ARGON2_VERSION)),
+			("S".to_string(), Variant::Bytes(vec![0; 32])),
+			("M".to_string(), Variant::UInt64(memory)),
+			("I".to_string(), Variant::UInt64(iterations)),
+			("P".to_string(), Variant::UInt32(1)),
+		])
+	}
+
+	fn field(key: &str, value: &str, protected: bool) -> KdbxField {
+		KdbxField { key: key.to_string(), value: value.to_string(), protected }
+	}
+
+	fn entry(password: &str, history: Vec<KdbxEntry>) -> KdbxEntry {
+		KdbxEntry {
+			fields: vec![
+				field("Title", "Mail <work> & \"home\"", false),
+				field("UserName", "bob", false),
+				field("Password", password, true),
+				field("PIN", "1234", true),
+			],
+			attachments: Vec::new(),
+			tags: vec!["private".to_string(), "mail".to_string()],
+			created_at: Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+			modified_at: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+			expires_at: None,
+			history,
+		}
+	}
+
+	#[test]
+	fn reads_written_database() {
+		let mut current = entry("current", vec![entry("first", Vec::new()), entry("second", Vec::new())]);
+		current.attachments = vec![
+			KdbxAttachment { name: "key.bin".to_string(), data: vec![0, 1, 2, 255] },
+			KdbxAttachment { name: "empty.txt".to_string(), data: Vec::new() },
+		];
+		current.expires_at = Some(UNIX_EPOCH + Duration::from_secs(1_800_000_000));
+		let mut group = KdbxGroup::new("Work");
+		group.entries.push(current);
+		let mut root = KdbxGroup::new("Root");
+		root.groups.push(group);
+
+		let key = KdbxKey::new(Some("secret".to_string()), None);
+		let data = write_kdbx(&KdbxDatabase { root }, &key).ok().unwrap();
+		let database = read_kdbx(&data, &key).ok().unwrap();
+
+		assert_eq!(database.root.name, "Root");
+		let group = &database.root.groups[0];
+		assert_eq!(group.name, "Work");
+		let read = &group.entries[0];
+		assert_eq!(read.get_field("Title"), Some("Mail <work> & \"home\""));
+		assert_eq!(read.get_field("UserName"), Some("bob"));
+		assert_eq!(read.get_field("Password"), Some("current"));
+		assert_eq!(read.get_field("PIN"), Some("1234"));
+		assert!(read.fields.iter().all(|field| field.protected == (field.key == "Password" || field.key == "PIN")));
+		assert_eq!(read.tags, vec!["private", "mail"]);
+		assert_eq!(read.created_at, Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)));
+		assert_eq!(read.modified_at, Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)));
+		assert_eq!(read.expires_at, Some(UNIX_EPOCH + Duration::from_secs(1_800_000_000)));
+		let attachments: Vec<(&str, &[u8])> = read.attachments.iter().map(|attachment| (attachment.name.as_str(), attachment.data.as_slice())).collect();
+		assert_eq!(attachments, vec![("key.bin", &[0, 1, 2, 255][..]), ("empty.txt", &[][..])]);
+		let history: Vec<Option<&str>> = read.history.iter().map(|version| version.get_field("Password")).collect();
+		assert_eq!(history, vec![Some("first"), Some("second")]);
+		assert!(read.history.iter().all(|version| version.get_field("PIN") == Some("1234")));
+
+		let wrong_key = KdbxKey::new(Some("wrong".to_string()), None);
+		assert!(matches!(read_kdbx(&data, &wrong_key), Err(KdbxError::WrongKey)));
+	}
+
+	/// Written by a separate KDBX 4 writer in Python, with Argon2id and ChaCha20 unlike write_kdbx.
+	/// The password is "secret".
+	#[test]
+	fn reads_database_of_other_writer() {
+		let data = include_bytes!("../tests/fixtures/keepass.kdbx");
+		let database = read_kdbx(data, &KdbxKey::new(Some("secret".to_string()), None)).ok().unwrap();
+
+		let root = &database.root;
+		assert_eq!(root.name, "Passwords");
+		let bank = &root.entries[0];
+		assert_eq!(bank.get_field("Title"), Some("Bank"));
+		assert_eq!(bank.get_field("Password"), Some("new-bank-pw"));
+		assert_eq!(bank.get_field("PIN"), Some("1234"));
+		assert_eq!(bank.get_field("Notes"), Some("line one\nline two"));
+		assert!(bank.fields.iter().all(|field| field.protected == (field.key == "Password" || field.key == "PIN")));
+		assert_eq!(bank.tags, vec!["private", "banking"]);
+		assert_eq!(bank.created_at, Some(UNIX_EPOCH + Duration::from_secs(1_640_995_200)));
+		assert_eq!(bank.modified_at, Some(UNIX_EPOCH + Duration::from_secs(1_704_067_200)));
+		assert_eq!(bank.expires_at, Some(UNIX_EPOCH + Duration::from_secs(1_814_313_600)));
+		assert_eq!(bank.attachments[0].name, "recovery.txt");
+		assert_eq!(bank.attachments[0].data, b"recovery codes: 1111 2222");
+		let history: Vec<Option<&str>> = bank.history.iter().map(|version| version.get_field("Password")).collect();
+		assert_eq!(history, vec![Some("old-bank-pw"), Some("new-bank-pw")]);
+
+		let work = &root.groups[0];
+		assert_eq!(work.name, "Work");
+		assert_eq!(work.entries[0].get_field("Password"), Some("j&<>\"pw"));
+		assert_eq!(work.groups[0].entries[0].get_field("UserName"), Some("carol@example.com"));
+		assert!(root.groups[1].is_recycle_bin);
+
+		let wrong_key = KdbxKey::new(Some("wrong".to_string()), None);
+		assert!(matches!(read_kdbx(data, &wrong_key), Err(KdbxError::WrongKey)));
+	}
+
+	#[test]
+	fn rejects_excessive_aes_kdf_rounds() {
+		let parameters = HashMap::from([
+			("$UUID".to_string(), Variant::Bytes(KDF_AES.to_vec())),
+			("S".to_string(), Variant::Bytes(vec![0; 32])),
+			("R".to_string(), Variant::UInt64(u64::MAX)),
+		]);
+		assert!(matches!(transform_key(&parameters, &[0; 32]), Err(KdbxError::TooMuchWork)));
+	}
+
+	#[test]
+	fn rejects_excessive_argon2_settings() {
+		assert!(matches!(transform_key(&argon2_parameters(u64::MAX, 2), &[0; 32]), Err(KdbxError::TooMuchWork)));
+		assert!(matches!(transform_key(&argon2_parameters(64 * 1024, u64::MAX), &[0; 32]), Err(KdbxError::TooMuchWork)));
+	}
+
+	#[test]
+	fn accepts_argon2_settings_within_limits() {
+		assert!(transform_key(&argon2_parameters(64 * 1024, 2), &[0; 32]).is_ok());
+	}
+}
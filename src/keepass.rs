@@ -0,0 +1,265 @@
+use std::time::SystemTime;
+use crate::attachments_state_item::MAX_ATTACHMENT_SIZE;
+use crate::database_context::{Account, DatabaseContext, EMAIL_FIELD_KEY, GROUP_FIELD_KEY, NAME_FIELD_KEY, NOTES_FIELD_KEY, PASSWORD_FIELD_KEY,
+	PASSWORD_HISTORY_FIELD_KEY, TAGS_FIELD_KEY, URL_FIELD_KEY, USERNAME_FIELD_KEY};
+use crate::entry_view::EntryView;
+use crate::kdbx::{read_kdbx, write_kdbx, KdbxAttachment, KdbxDatabase, KdbxEntry, KdbxError, KdbxField, KdbxGroup, KdbxKey};
+use crate::otpauth::{format_otpauth_uri, parse_otp_uri};
+use crate::texts::get_texts;
+use crate::vault_import::{ImportResult, ImportedEntry};
+
+const IMPORTED_ENTRY_TYPE: &str = "login";
+
+const TITLE_KEY: &str = "Title";
+const USERNAME_KEY: &str = "UserName";
+const PASSWORD_KEY: &str = "Password";
+const URL_KEY: &str = "URL";
+const NOTES_KEY: &str = "Notes";
+/// KeePassXC keeps the one-time password of an entry as otpauth:// URI in this field.
+const OTP_KEY: &str = "otp";
+const STANDARD_KEYS: [&str; 5] = [TITLE_KEY, USERNAME_KEY, PASSWORD_KEY, URL_KEY, NOTES_KEY];
+/// Fields which are written as the standard fields of KeePass or as groups and tags.
+const MAPPED_FIELD_KEYS: [&str; 7] = [NAME_FIELD_KEY, PASSWORD_FIELD_KEY, USERNAME_FIELD_KEY, URL_FIELD_KEY, NOTES_FIELD_KEY, GROUP_FIELD_KEY, TAGS_FIELD_KEY];
+const EXPORT_ROOT_GROUP: &str = "Rusty Password Manager";
+
+/// Reads a KeePass database and maps its entries onto login entries.
+/// Groups become the group field, entries in the recycle bin are left out.
+/// Of the older versions of an entry only the passwords are kept, as the password history.
+pub fn import_keepass(data: &[u8], key: &KdbxKey) -> Result<ImportResult, KdbxError> {
+	let database = read_kdbx(data, key)?;
+	let mut importer = KeePassImporter::default();
+	importer.import_group(&database.root, &[]);
+	Ok(importer.finish())
+}
+
+#[derive(Default)]
+struct KeePassImporter {
+	result: ImportResult,
+	recycled_count: usize,
+}
+
+impl KeePassImporter {
+	fn import_group(&mut self, group: &KdbxGroup, path: &[&str]) {
+		if group.is_recycle_bin {
+			self.recycled_count += count_entries(group);
+			return;
+		}
+		for entry in &group.entries {
+			let imported = self.import_entry(entry, &path.join("/"));
+			self.result.entries.push(imported);
+		}
+		for child in &group.groups {
+			let mut child_path = path.to_vec();
+			child_path.push(&child.name);
+			self.import_group(child, &child_path);
+		}
+	}
+
+	fn import_entry(&mut self, entry: &KdbxEntry, group_path: &str) -> ImportedEntry {
+		let texts = &get_texts().import;
+		let mut account = Account::new(IMPORTED_ENTRY_TYPE);
+		let title = entry.get_field(TITLE_KEY).unwrap_or_default().trim();
+		account.account_name = if title.is_empty() { texts.untitled.clone() } else { title.to_string() };
+		account.password = entry.get_field(PASSWORD_KEY).unwrap_or_default().to_string();
+		for (key, field_key) in [(USERNAME_KEY, USERNAME_FIELD_KEY), (URL_KEY, URL_FIELD_KEY), (NOTES_KEY, NOTES_FIELD_KEY)] {
+			if let Some(value) = entry.get_field(key).filter(|value| !value.is_empty()) {
+				account.set_field(field_key, value.to_string());
+			}
+		}
+		if !group_path.is_empty() {
+			account.set_field(GROUP_FIELD_KEY, group_path.to_string());
+		}
+		if !entry.tags.is_empty() {
+			account.set_field(TAGS_FIELD_KEY, entry.tags.join(", "));
+		}
+
+		let display_name = if group_path.is_empty() { account.account_name.clone() } else { format!("{}/{}", group_path, account.account_name) };
+		for field in entry.fields.iter().filter(|field| !STANDARD_KEYS.contains(&field.key.as_str()) && !field.value.is_empty()) {
+			if field.key == OTP_KEY {
+				match parse_otp_uri(&field.value) {
+					Ok(mut imports) if imports.len() == 1 => {
+						account.totp = Some(imports.remove(0).totp);
+						continue;
+					}
+					_ => self.result.unmapped.push(texts.invalid_otp.replacen("{}", &display_name, 1)),
+				}
+			}
+			if account.get_field(&field.key).is_some_and(|value| !value.is_empty()) {
+				self.result.unmapped.push(texts.field_conflict.replacen("{}", &display_name, 1).replacen("{}", &field.key, 1));
+				continue;
+			}
+			// The otp uri holds the secret, so it is hidden even where KeePass did not protect it
+			if field.protected || field.key == OTP_KEY {
+				account.set_hidden_field(&field.key, field.value.clone());
+			} else {
+				account.set_field(&field.key, field.value.clone());
+			}
+		}
+
+		let now = SystemTime::now();
+		account.created_at = entry.created_at.unwrap_or(now);
+		account.updated_at = entry.modified_at.unwrap_or(account.created_at);
+		account.password_changed_at = get_password_changed_at(entry).unwrap_or(account.updated_at);
+		account.expires_at = entry.expires_at;
+		let password_history = get_password_history(entry);
+		if !password_history.is_empty() {
+			account.set_field(PASSWORD_HISTORY_FIELD_KEY, password_history.join("\n"));
+		}
+
+		let mut attachments = Vec::new();
+		for attachment in &entry.attachments {
+			if attachment.data.len() > MAX_ATTACHMENT_SIZE {
+				self.result.unmapped.push(texts.attachment_too_large.replacen("{}", &display_name, 1).replacen("{}", &attachment.name, 1));
+			} else {
+				attachments.push((attachment.name.clone(), attachment.data.clone()));
+			}
+		}
+		ImportedEntry { account, attachments }
+	}
+
+	fn finish(mut self) -> ImportResult {
+		let texts = &get_texts().import;
+		if self.recycled_count > 0 {
+			self.result.unmapped.insert(0, texts.recycle_bin_skipped.replacen("{}", &self.recycled_count.to_string(), 1));
+		}
+		self.result
+	}
+}
+
+fn count_entries(group: &KdbxGroup) -> usize {
+	group.entries.len() + group.groups.iter().map(count_entries).sum::<usize>()
+}
+
+/// There is no entry history, but it tells when the current password was set:
+/// by the oldest of the latest versions which all have the current password.
+fn get_password_changed_at(entry: &KdbxEntry) -> Option<SystemTime> {
+	let password = entry.get_field(PASSWORD_KEY);
+	let mut changed_at = entry.modified_at;
+	for version in entry.history.iter().rev() {
+		if version.get_field(PASSWORD_KEY) != password {
+			return changed_at;
+		}
+		changed_at = version.modified_at;
+	}
+	entry.created_at.or(changed_at)
+}
+
+/// The passwords of the older versions, the newest first, without the current one and repetitions.
+fn get_password_history(entry: &KdbxEntry) -> Vec<String> {
+	let password = entry.get_field(PASSWORD_KEY).unwrap_or_default();
+	let mut history: Vec<String> = Vec::new();
+	for version in entry.history.iter().rev() {
+		let previous = version.get_field(PASSWORD_KEY).unwrap_or_default();
+		if !previous.is_empty() && previous != password && !history.iter().any(|known| known == previous) {
+			history.push(previous.to_string());
+		}
+	}
+	history
+}
+
+/// Writes the entries to a KeePass database. The group field becomes the group structure,
+/// fields without a KeePass counterpart become custom fields, protected if they are hidden here.
+pub fn export_keepass(db_context: &DatabaseContext, accounts: &[Account], key: &KdbxKey) -> Result<Vec<u8>, KdbxError> {
+	let mut root = KdbxGroup::new(EXPORT_ROOT_GROUP);
+	for account in accounts {
+		let entry = export_entry(db_context, account);
+		let mut group = &mut root;
+		for name in account.get_field(GROUP_FIELD_KEY).unwrap_or_default().split('/').map(str::trim).filter(|name| !name.is_empty()) {
+			let idx = match group.groups.iter().position(|child| child.name == name) {
+				Some(idx) => idx,
+				None => {
+					group.groups.push(KdbxGroup::new(name));
+					group.groups.len() - 1
+				}
+			};
+			group = &mut group.groups[idx];
+		}
+		group.entries.push(entry);
+	}
+	write_kdbx(&KdbxDatabase { root }, key)
+}
+
+fn export_entry(db_context: &DatabaseContext, account: &Account) -> KdbxEntry {
+	let username = account.get_username();
+	let field = |key: &str, value: &str, protected: bool| KdbxField { key: key.to_string(), value: value.to_string(), protected };
+	let mut fields = vec![
+		field(TITLE_KEY, &account.account_name, false),
+		field(USERNAME_KEY, username, false),
+		field(PASSWORD_KEY, &account.password, true),
+		field(URL_KEY, account.get_field(URL_FIELD_KEY).unwrap_or_default(), false),
+		field(NOTES_KEY, account.get_field(NOTES_FIELD_KEY).unwrap_or_default(), false),
+	];
+	for row in EntryView::new(account).get_rows() {
+		let value = account.get_field(&row.key).unwrap_or_default();
+		// The email is already the username of entries without one
+		let is_username = row.key == EMAIL_FIELD_KEY && value == username;
+		if !value.is_empty() && !is_username && !MAPPED_FIELD_KEYS.contains(&row.key.as_str()) {
+			fields.push(field(&row.key, value, row.is_hidden()));
+		}
+	}
+	if let Some(totp) = &account.totp {
+		fields.push(field(OTP_KEY, &format_otpauth_uri(&account.account_name, username, totp), true));
+	}
+
+	let attachments = db_context.list_attachments(account.id).unwrap().into_iter()
+		.map(|attachment| KdbxAttachment { data: db_context.get_attachment_data(attachment.id).unwrap(), name: attachment.file_name })
+		.collect();
+	KdbxEntry {
+		fields,
+		attachments,
+		tags: account.get_field(TAGS_FIELD_KEY).unwrap_or_default().split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect(),
+		created_at: Some(account.created_at),
+		modified_at: Some(account.updated_at),
+		expires_at: account.expires_at,
+		history: Vec::new(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry_with_password(password: &str, history: Vec<KdbxEntry>) -> KdbxEntry {
+		KdbxEntry {
+			fields: vec![KdbxField { key: PASSWORD_KEY.to_string(), value: password.to_string(), protected: true }],
+			attachments: Vec::new(),
+			tags: Vec::new(),
+			created_at: None,
+			modified_at: None,
+			expires_at: None,
+			history,
+		}
+	}
+
+	#[test]
+	fn keeps_previous_passwords_newest_first() {
+		let history = ["first", "second", "second", "", "first", "current"].iter()
+			.map(|password| entry_with_password(password, Vec::new()))
+			.collect();
+		let entry = entry_with_password("current", history);
+		assert_eq!(get_password_history(&entry), vec!["first", "second"]);
+	}
+
+	#[test]
+	fn has_no_history_without_older_versions() {
+		assert!(get_password_history(&entry_with_password("current", Vec::new())).is_empty());
+	}
+
+	#[test]
+	fn keeps_custom_fields_protected() {
+		let mut entry = entry_with_password("current", Vec::new());
+		let field = |key: &str, value: &str, protected: bool| KdbxField { key: key.to_string(), value: value.to_string(), protected };
+		entry.fields.push(field("API key", "sk-123", true));
+		entry.fields.push(field("Server", "db.example.com", false));
+		entry.fields.push(field(OTP_KEY, "otpauth://totp/Example?secret=not+base32", false));
+
+		let mut importer = KeePassImporter::default();
+		let account = importer.import_entry(&entry, "").account;
+		let is_hidden = |key: &str| account.fields.iter().find(|field| field.key == key).map(|field| field.hidden);
+		assert_eq!(is_hidden("API key"), Some(true));
+		assert_eq!(is_hidden("Server"), Some(false));
+		// The uri of a one-time password which could not be read still holds the secret
+		assert_eq!(is_hidden(OTP_KEY), Some(true));
+		assert_eq!(importer.result.unmapped.len(), 1);
+	}
+}
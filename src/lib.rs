@@ -0,0 +1,58 @@
+mod state_item;
+mod startup_state_item;
+pub mod state_manager;
+mod authentication_state_item;
+mod transition;
+mod main_menu_state_item;
+mod add_account_state_item;
+pub mod terminal_context;
+mod set_authentication_state_item;
+mod input_handler;
+pub mod file_accesssor;
+mod encryption_controller;
+pub mod database_context;
+mod list_accounts_state;
+mod show_account_state_item;
+mod wipe_database_state_item;
+mod clipboard_controller;
+mod page_list_view;
+pub mod texts;
+mod password_widget;
+mod password_generator;
+mod widget;
+pub mod entry_templates;
+mod entry_view;
+mod attachments_state_item;
+mod date_util;
+mod rotation_reminder_state_item;
+mod totp;
+mod otpauth;
+mod import_otp_state_item;
+mod qr_code;
+mod ssh_key;
+mod ssh_agent;
+#[cfg(unix)]
+mod socket_util;
+mod ssh_agent_state_item;
+pub mod vault_unlock;
+pub mod vault_agent;
+pub mod git_credential;
+pub mod docker_credential;
+pub mod cli;
+mod secret_env;
+mod secret_template;
+mod cloud_credential;
+mod xml_util;
+mod kdbx;
+mod keepass;
+mod bitwarden;
+mod csv_import;
+mod zip_archive;
+mod onepassword;
+mod vault_import;
+mod import_state_item;
+mod vault_export;
+mod portable_export;
+mod plain_export;
+mod emergency_kit;
+mod export_state_item;
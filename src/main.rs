@@ -1,32 +1,24 @@
-use crate::terminal_context::TerminalContext;
+use clap::Parser;
 use crossterm::terminal::size;
-
-mod state_item;
-mod startup_state_item;
-mod state_manager;
-mod authentication_state_item;
-mod transition;
-mod main_menu_state_item;
-mod add_account_state_item;
-mod terminal_context;
-mod set_authentication_state_item;
-mod input_handler;
-mod file_accesssor;
-mod encryption_controller;
-mod database_context;
-mod list_accounts_state;
-mod show_account_state_item;
-mod wipe_database_state_item;
-mod clipboard_controller;
-mod page_list_view;
-mod texts;
-mod password_widget;
-mod widget;
+use rusty_password_manager::cli::{run_command, Cli};
+use rusty_password_manager::terminal_context::TerminalContext;
+use rusty_password_manager::{entry_templates, state_manager, texts};
 
 fn main() {
-	println!("cargo:rustc-link-lib=sqlcipher");
-
 	texts::init_texts();
+	entry_templates::init_templates();
+
+	let cli = Cli::parse();
+	if let Some(command) = cli.command {
+		match run_command(command) {
+			Ok(0) => return,
+			Ok(exit_code) => std::process::exit(exit_code),
+			Err(e) => {
+				eprintln!("rpm: {}", e.get_message());
+				std::process::exit(1);
+			}
+		}
+	}
 
 	if let Some(mut context) = create_terminal_context() {
 		let mut state_manager = state_manager::StateManager::new();
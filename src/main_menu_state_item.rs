@@ -20,6 +20,10 @@ impl MainMenuStateItem {
 			Transition::ToListAccounts,
 			Transition::ToChangeAuthentication,
 			Transition::ToWipeDatabase,
+			Transition::ToImportOtp,
+			Transition::ToSshAgent,
+			Transition::ToImport,
+			Transition::ToExport,
 			Transition::ToExit
 		];
 
@@ -0,0 +1,397 @@
+use std::time::SystemTime;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::attachments_state_item::MAX_ATTACHMENT_SIZE;
+use crate::database_context::{Account, GROUP_FIELD_KEY, NOTES_FIELD_KEY, PASSWORD_FIELD_KEY, TAGS_FIELD_KEY, URL_FIELD_KEY, USERNAME_FIELD_KEY};
+use crate::date_util::{format_date, from_timestamp};
+use crate::otpauth::parse_totp;
+use crate::ssh_key::{SshKey, PRIVATE_KEY_FIELD_KEY, PUBLIC_KEY_FIELD_KEY};
+use crate::texts::get_texts;
+use crate::vault_import::{ImportResult, ImportedEntry};
+use crate::zip_archive::ZipArchive;
+
+const DATA_FILE_NAME: &str = "export.data";
+/// Attachments are stored as files/<document id>__<file name>.
+const FILES_DIRECTORY: &str = "files/";
+
+const CATEGORY_LOGIN: &str = "001";
+const CATEGORY_CREDIT_CARD: &str = "002";
+const CATEGORY_PASSWORD: &str = "005";
+const CATEGORY_SSH_KEY: &str = "114";
+
+const DESIGNATION_USERNAME: &str = "username";
+const DESIGNATION_PASSWORD: &str = "password";
+/// Login fields of this type are the submit buttons of the web form.
+const LOGIN_FIELD_BUTTON: &str = "B";
+
+const LOGIN_ENTRY_TYPE: &str = "login";
+const CARD_ENTRY_TYPE: &str = "credit_card";
+const NOTE_ENTRY_TYPE: &str = "secure_note";
+const SSH_KEY_ENTRY_TYPE: &str = "ssh_key";
+const OTP_FIELD_KEY: &str = "otp";
+
+/// Fields of credit cards, identified by their id, and the fields of our template they are written to.
+const CARD_FIELD_KEYS: [(&str, &str); 6] = [
+	("ccnum", PASSWORD_FIELD_KEY), ("cardholder", "cardholder"), ("expiry", "expiry"), ("cvv", "cvv"), ("type", "brand"), ("pin", "pin"),
+];
+
+pub enum OnePasswordError {
+	NotOnePux,
+	Corrupted,
+}
+
+impl OnePasswordError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().import;
+		match self {
+			OnePasswordError::NotOnePux => texts.not_onepux.clone(),
+			OnePasswordError::Corrupted => texts.corrupted_file.clone(),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct OnePuxExport {
+	accounts: Vec<OnePuxAccount>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxAccount {
+	#[serde(default)]
+	vaults: Vec<Vault>,
+}
+
+#[derive(Deserialize)]
+struct Vault {
+	attrs: VaultAttributes,
+	#[serde(default)]
+	items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct VaultAttributes {
+	name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+	#[serde(default)]
+	fav_index: u32,
+	created_at: Option<u64>,
+	updated_at: Option<u64>,
+	category_uuid: String,
+	details: Details,
+	overview: Overview,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Details {
+	#[serde(default)]
+	login_fields: Vec<LoginField>,
+	notes_plain: Option<String>,
+	#[serde(default)]
+	sections: Vec<Section>,
+	#[serde(default)]
+	password_history: Vec<PasswordHistory>,
+	document_attributes: Option<Document>,
+	password: Option<String>,
+	passkey: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginField {
+	value: Option<String>,
+	name: Option<String>,
+	field_type: Option<String>,
+	designation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PasswordHistory {
+	time: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+	file_name: String,
+	document_id: String,
+}
+
+#[derive(Deserialize)]
+struct Section {
+	#[serde(default)]
+	fields: Vec<SectionField>,
+}
+
+#[derive(Deserialize)]
+struct SectionField {
+	title: Option<String>,
+	id: Option<String>,
+	/// An object with a single member, its name is the kind of the field.
+	value: serde_json::Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct Overview {
+	title: Option<String>,
+	url: Option<String>,
+	#[serde(default)]
+	urls: Vec<OverviewUrl>,
+	#[serde(default)]
+	tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct OverviewUrl {
+	url: Option<String>,
+}
+
+/// The value of a section field, converted from one of the kinds 1Password knows.
+enum FieldValue {
+	Text(String),
+	Totp(String),
+	File(Document),
+	SshKey { private_key: String, public_key: Option<String> },
+	Unsupported,
+}
+
+impl FieldValue {
+	fn from_json(value: &serde_json::Map<String, Value>) -> Self {
+		let (kind, content) = match value.iter().next() {
+			Some(member) => member,
+			None => return FieldValue::Text(String::new()),
+		};
+		match kind.as_str() {
+			"string" | "concealed" | "url" | "phone" | "menu" | "gender" | "creditCardNumber" | "creditCardType" => {
+				FieldValue::Text(content.as_str().unwrap_or_default().to_string())
+			}
+			"email" => FieldValue::Text(content.get("email_address").unwrap_or(content).as_str().unwrap_or_default().to_string()),
+			"totp" => FieldValue::Totp(content.as_str().unwrap_or_default().to_string()),
+			"date" => FieldValue::Text(content.as_u64().and_then(from_timestamp).map(format_date).unwrap_or_default()),
+			// Stored as YYYYMM
+			"monthYear" => FieldValue::Text(content.as_u64().map(|date| format!("{:02}/{:02}", date % 100, date / 100 % 100)).unwrap_or_default()),
+			"address" => {
+				let parts = ["street", "city", "state", "zip", "country"].iter()
+					.filter_map(|part| content.get(part).and_then(Value::as_str))
+					.filter(|part| !part.is_empty())
+					.collect::<Vec<_>>();
+				FieldValue::Text(parts.join(", "))
+			}
+			"file" => serde_json::from_value(content.clone()).map(FieldValue::File).unwrap_or(FieldValue::Unsupported),
+			"sshKey" => FieldValue::SshKey {
+				private_key: content.get("privateKey").and_then(Value::as_str).unwrap_or_default().to_string(),
+				public_key: content.pointer("/metadata/publicKey").and_then(Value::as_str).map(str::to_string),
+			},
+			_ => FieldValue::Unsupported,
+		}
+	}
+}
+
+/// Reads a 1Password export, a ZIP archive with the items of all vaults as JSON in export.data and the attachments
+/// next to it. The vault becomes the group of an entry.
+pub fn import_onepassword(data: &[u8]) -> Result<ImportResult, OnePasswordError> {
+	let archive = ZipArchive::read(data).ok_or(OnePasswordError::NotOnePux)?;
+	let data_file = archive.find_entry(DATA_FILE_NAME).ok_or(OnePasswordError::NotOnePux)?;
+	let export_data = archive.read_entry(data_file).ok_or(OnePasswordError::Corrupted)?;
+	let export: OnePuxExport = serde_json::from_slice(&export_data).map_err(|_| OnePasswordError::Corrupted)?;
+
+	let mut importer = OnePasswordImporter { archive, result: ImportResult::default(), history_count: 0 };
+	for vault in export.accounts.iter().flat_map(|account| account.vaults.iter()) {
+		let group = vault.attrs.name.as_deref().unwrap_or_default().trim();
+		for item in &vault.items {
+			let imported = importer.import_item(item, group);
+			importer.result.entries.push(imported);
+		}
+	}
+	Ok(importer.finish())
+}
+
+struct OnePasswordImporter<'a> {
+	archive: ZipArchive<'a>,
+	result: ImportResult,
+	history_count: usize,
+}
+
+impl OnePasswordImporter<'_> {
+	fn import_item(&mut self, item: &Item, group: &str) -> ImportedEntry {
+		let texts = &get_texts().import;
+		let entry_type = match item.category_uuid.as_str() {
+			CATEGORY_LOGIN | CATEGORY_PASSWORD => LOGIN_ENTRY_TYPE,
+			CATEGORY_CREDIT_CARD => CARD_ENTRY_TYPE,
+			CATEGORY_SSH_KEY => SSH_KEY_ENTRY_TYPE,
+			_ => NOTE_ENTRY_TYPE,
+		};
+		let mut account = Account::new(entry_type);
+		let name = item.overview.title.as_deref().unwrap_or_default().trim();
+		account.account_name = if name.is_empty() { texts.untitled.clone() } else { name.to_string() };
+		let display_name = if group.is_empty() { account.account_name.clone() } else { format!("{}/{}", group, account.account_name) };
+		if !group.is_empty() {
+			account.set_field(GROUP_FIELD_KEY, group.to_string());
+		}
+		let tags: Vec<&str> = item.overview.tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()).collect();
+		if !tags.is_empty() {
+			account.set_field(TAGS_FIELD_KEY, tags.join(", "));
+		}
+		if let Some(notes) = item.details.notes_plain.as_deref().filter(|notes| !notes.is_empty()) {
+			account.set_field(NOTES_FIELD_KEY, notes.to_string());
+		}
+		account.pinned = item.fav_index > 0;
+
+		let mut urls: Vec<&str> = item.overview.urls.iter().filter_map(|url| url.url.as_deref()).filter(|url| !url.is_empty()).collect();
+		if urls.is_empty() {
+			urls.extend(item.overview.url.as_deref().filter(|url| !url.is_empty()));
+		}
+		for (idx, url) in urls.iter().enumerate() {
+			let key = if idx == 0 { URL_FIELD_KEY.to_string() } else { format!("{}_{}", URL_FIELD_KEY, idx + 1) };
+			account.set_field(&key, url.to_string());
+		}
+
+		let mut attachments = Vec::new();
+		self.import_login_fields(&mut account, item, &display_name);
+		for field in item.details.sections.iter().flat_map(|section| section.fields.iter()) {
+			self.import_section_field(&mut account, &mut attachments, field, &item.category_uuid, &display_name);
+		}
+		if let Some(document) = &item.details.document_attributes {
+			self.import_attachment(&mut attachments, document, &display_name);
+		}
+		if item.details.passkey.is_some() {
+			self.result.unmapped.push(texts.passkey_skipped.replacen("{}", &display_name, 1));
+		}
+
+		let now = SystemTime::now();
+		account.created_at = item.created_at.and_then(from_timestamp).unwrap_or(now);
+		account.updated_at = item.updated_at.and_then(from_timestamp).unwrap_or(account.created_at);
+		// The newest entry of the history is the time the current password replaced it
+		account.password_changed_at = item.details.password_history.iter().filter_map(|entry| entry.time).max().and_then(from_timestamp)
+			.unwrap_or(account.created_at);
+		self.history_count += item.details.password_history.len();
+		ImportedEntry { account, attachments }
+	}
+
+	/// The username and password of logins, other fields of the web form are kept as fields.
+	fn import_login_fields(&mut self, account: &mut Account, item: &Item, display_name: &str) {
+		if let Some(password) = &item.details.password {
+			account.password = password.clone();
+		}
+		for field in &item.details.login_fields {
+			let value = field.value.as_deref().unwrap_or_default();
+			if value.is_empty() || field.field_type.as_deref() == Some(LOGIN_FIELD_BUTTON) {
+				continue;
+			}
+			match field.designation.as_deref() {
+				Some(DESIGNATION_USERNAME) => account.set_field(USERNAME_FIELD_KEY, value.to_string()),
+				Some(DESIGNATION_PASSWORD) => account.password = value.to_string(),
+				_ => {
+					if let Some(name) = field.name.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+						self.set_field(account, name, value.to_string(), display_name);
+					}
+				}
+			}
+		}
+	}
+
+	fn import_section_field(&mut self, account: &mut Account, attachments: &mut Vec<(String, Vec<u8>)>, field: &SectionField,
+							category: &str, display_name: &str) {
+		let texts = &get_texts().import;
+		let title = field.title.as_deref().map(str::trim).filter(|title| !title.is_empty()).unwrap_or(texts.untitled.as_str());
+		match FieldValue::from_json(&field.value) {
+			FieldValue::Text(value) if value.is_empty() => {}
+			FieldValue::Text(value) => {
+				let card_key = CARD_FIELD_KEYS.iter().find(|(id, _)| category == CATEGORY_CREDIT_CARD && field.id.as_deref() == Some(id));
+				let key = card_key.map_or(title, |(_, key)| key);
+				self.set_field(account, key, value, display_name);
+			}
+			FieldValue::Totp(totp) if totp.trim().is_empty() => {}
+			FieldValue::Totp(totp) => match parse_totp(totp.trim()) {
+				Some(config) if account.totp.is_none() => account.totp = Some(config),
+				// Only one one-time password per entry, further ones are kept as text
+				Some(_) => self.set_field(account, title, totp, display_name),
+				None => {
+					self.result.unmapped.push(texts.invalid_otp.replacen("{}", display_name, 1));
+					self.set_field(account, OTP_FIELD_KEY, totp, display_name);
+				}
+			},
+			FieldValue::File(document) => self.import_attachment(attachments, &document, display_name),
+			FieldValue::SshKey { private_key, public_key } => match SshKey::from_openssh_pem(&private_key) {
+				Ok(key) => {
+					account.set_field(PRIVATE_KEY_FIELD_KEY, key.to_stored());
+					account.set_field(PUBLIC_KEY_FIELD_KEY, key.get_public_key_line());
+				}
+				Err(_) => {
+					if let Some(public_key) = public_key.filter(|public_key| !public_key.is_empty()) {
+						account.set_field(PUBLIC_KEY_FIELD_KEY, public_key);
+					}
+					if !private_key.is_empty() {
+						self.result.unmapped.push(texts.unsupported_ssh_key.replacen("{}", display_name, 1));
+					}
+				}
+			},
+			FieldValue::Unsupported => self.result.unmapped.push(texts.unsupported_field.replacen("{}", display_name, 1).replacen("{}", title, 1)),
+		}
+	}
+
+	fn set_field(&mut self, account: &mut Account, key: &str, value: String, display_name: &str) {
+		if account.get_field(key).is_some_and(|value| !value.is_empty()) {
+			self.result.unmapped.push(get_texts().import.field_conflict.replacen("{}", display_name, 1).replacen("{}", key, 1));
+			return;
+		}
+		account.set_field(key, value);
+	}
+
+	fn import_attachment(&mut self, attachments: &mut Vec<(String, Vec<u8>)>, document: &Document, display_name: &str) {
+		let texts = &get_texts().import;
+		let prefix = format!("{}{}", FILES_DIRECTORY, document.document_id);
+		let entry = match self.archive.get_entries().iter().find(|entry| entry.name.starts_with(&prefix)) {
+			Some(entry) => entry,
+			None => {
+				self.result.unmapped.push(texts.attachment_missing.replacen("{}", display_name, 1).replacen("{}", &document.file_name, 1));
+				return;
+			}
+		};
+		if entry.get_size() > MAX_ATTACHMENT_SIZE {
+			self.result.unmapped.push(texts.attachment_too_large.replacen("{}", display_name, 1).replacen("{}", &document.file_name, 1));
+			return;
+		}
+		match self.archive.read_entry(entry) {
+			Some(data) => attachments.push((document.file_name.clone(), data)),
+			None => self.result.unmapped.push(texts.attachment_missing.replacen("{}", display_name, 1).replacen("{}", &document.file_name, 1)),
+		}
+	}
+
+	fn finish(mut self) -> ImportResult {
+		if self.history_count > 0 {
+			self.result.unmapped.insert(0, get_texts().import.password_history_skipped.replacen("{}", &self.history_count.to_string(), 1));
+		}
+		self.result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::{Duration, UNIX_EPOCH};
+	use base64::engine::general_purpose::STANDARD as BASE64;
+	use base64::Engine;
+
+	/// A 1PUX file with two logins, "Huge" with timestamps beyond what a time can hold and "Normal".
+	const EXPORT: &str = "UEsDBBQAAAAIAJ0iU13ARTZ74gAAAFMCAAALAAAAZXhwb3J0LmRhdGHVUD1rwzAQ/StBcwaZ+iPt1qVkKh3aqXg4LNUcyJKRTjbB+L/3JCdNCIXOlZbTe093790ioOtctBTE0+5zERNEc6mByKdyERYGzYV48zgBabHudwJJD2dh5zWj6pn4WRzKsm7KUjYPjXysqqIuKlbHUf0l6VjQO3/6iKjSMCkLwbDSBGg2H8b1aF9QG/Vj18TsbJw3bcDeAqGzGYQQZudVor7Sr/fTuOUQa7u/8kcMxIO3loQ5628m1zYFd5P2E+o5OyIkk1seY897Yf5uHbU8n/slNDfE/47+6vwAhsO3+X4DUEsBAhQDFAAAAAgAnSJTXcBFNnviAAAAUwIAAAsAAAAAAAAAAAAAAIABAAAAAGV4cG9ydC5kYXRhUEsFBgAAAAABAAEAOQAAAAsBAAAAAA==";
+
+	#[test]
+	fn ignores_timestamps_out_of_range() {
+		let before = SystemTime::now();
+		let result = import_onepassword(&BASE64.decode(EXPORT).unwrap()).ok().unwrap();
+		let huge = &result.entries[0].account;
+		assert_eq!(huge.account_name, "Huge");
+		assert!(huge.created_at >= before);
+		assert_eq!(huge.updated_at, huge.created_at);
+		assert_eq!(huge.password_changed_at, huge.created_at);
+
+		let normal = &result.entries[1].account;
+		assert_eq!(normal.created_at, UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+		assert_eq!(normal.updated_at, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+	}
+}
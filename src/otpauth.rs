@@ -0,0 +1,386 @@
+use std::time::SystemTime;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+use base64::Engine;
+use crate::texts::get_texts;
+use crate::totp::{decode_base32, encode_base32, is_valid_digits, normalize_secret, TotpAlgorithm, TotpConfig};
+
+const OTPAUTH_SCHEME: &str = "otpauth://";
+const MIGRATION_SCHEME: &str = "otpauth-migration://";
+
+/// A one-time password secret read from an otpauth URI, waiting to be reviewed by the user.
+#[derive(Clone, Debug)]
+pub struct OtpImport {
+	pub issuer: String,
+	pub label: String,
+	pub totp: TotpConfig,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OtpImportError {
+	InvalidUri,
+	UnsupportedType,
+	MissingSecret,
+	InvalidPayload,
+}
+
+impl OtpImportError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().otp_import;
+		match self {
+			OtpImportError::InvalidUri => texts.invalid_uri.clone(),
+			OtpImportError::UnsupportedType => texts.unsupported_type.clone(),
+			OtpImportError::MissingSecret => texts.missing_secret.clone(),
+			OtpImportError::InvalidPayload => texts.invalid_payload.clone(),
+		}
+	}
+}
+
+/// Parses a single otpauth:// URI or a Google Authenticator otpauth-migration:// export.
+pub fn parse_otp_uri(uri: &str) -> Result<Vec<OtpImport>, OtpImportError> {
+	let uri = uri.trim();
+	if uri.starts_with(MIGRATION_SCHEME) {
+		parse_migration_uri(uri)
+	} else if uri.starts_with(OTPAUTH_SCHEME) {
+		parse_otpauth_uri(uri).map(|otp| vec![otp])
+	} else {
+		Err(OtpImportError::InvalidUri)
+	}
+}
+
+/// Format: otpauth://totp/Issuer:account?secret=...&issuer=...&algorithm=...&digits=...&period=...
+fn parse_otpauth_uri(uri: &str) -> Result<OtpImport, OtpImportError> {
+	let rest = &uri[OTPAUTH_SCHEME.len()..];
+	let (otp_type, rest) = rest.split_once('/').ok_or(OtpImportError::InvalidUri)?;
+	if !otp_type.eq_ignore_ascii_case("totp") {
+		return Err(OtpImportError::UnsupportedType);
+	}
+
+	let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+	let path = percent_decode(path);
+	let (label_issuer, label) = match path.split_once(':') {
+		Some((issuer, label)) => (issuer.trim().to_string(), label.trim().to_string()),
+		None => (String::new(), path.trim().to_string()),
+	};
+
+	let mut totp = TotpConfig::new("");
+	let mut issuer = label_issuer;
+	for (key, value) in parse_query(query) {
+		match key.to_lowercase().as_str() {
+			"secret" => totp.secret = normalize_secret(&value),
+			"issuer" if !value.trim().is_empty() => issuer = value.trim().to_string(),
+			"algorithm" => totp.algorithm = TotpAlgorithm::from_name(&value).ok_or(OtpImportError::InvalidUri)?,
+			"digits" => {
+				totp.digits = value.parse().ok().filter(|digits| is_valid_digits(*digits)).ok_or(OtpImportError::InvalidUri)?;
+			}
+			"period" => {
+				totp.period = value.parse().ok().filter(|period| *period > 0).ok_or(OtpImportError::InvalidUri)?;
+			}
+			_ => {}
+		}
+	}
+
+	if totp.secret.is_empty() {
+		return Err(OtpImportError::MissingSecret);
+	}
+	if decode_base32(&totp.secret).is_none() {
+		return Err(OtpImportError::InvalidUri);
+	}
+
+	Ok(OtpImport { issuer, label, totp })
+}
+
+/// Format: otpauth-migration://offline?data=<base64 encoded protobuf MigrationPayload>
+fn parse_migration_uri(uri: &str) -> Result<Vec<OtpImport>, OtpImportError> {
+	let query = uri.split_once('?').map(|(_, query)| query).ok_or(OtpImportError::InvalidUri)?;
+	let data = parse_query(query).into_iter()
+		.find(|(key, _)| key == "data")
+		.map(|(_, value)| value)
+		.ok_or(OtpImportError::InvalidUri)?;
+	let data = data.replace(' ', "+");
+	let payload = STANDARD.decode(&data)
+		.or_else(|_| URL_SAFE.decode(&data))
+		.map_err(|_| OtpImportError::InvalidPayload)?;
+
+	let mut imports = Vec::new();
+	let mut skipped_unsupported = false;
+	for (field_number, value) in read_protobuf_fields(&payload)? {
+		if let (1, ProtobufValue::Bytes(parameters)) = (field_number, value) {
+			match parse_migration_parameters(parameters)? {
+				Some(otp) => imports.push(otp),
+				None => skipped_unsupported = true,
+			}
+		}
+	}
+
+	if imports.is_empty() {
+		return Err(if skipped_unsupported { OtpImportError::UnsupportedType } else { OtpImportError::InvalidPayload });
+	}
+	Ok(imports)
+}
+
+/// Reads one OtpParameters message, None if it is not a TOTP secret.
+fn parse_migration_parameters(data: &[u8]) -> Result<Option<OtpImport>, OtpImportError> {
+	let mut secret = Vec::new();
+	let mut name = String::new();
+	let mut issuer = String::new();
+	let mut totp = TotpConfig::new("");
+	let mut is_totp = true;
+
+	for (field_number, value) in read_protobuf_fields(data)? {
+		match (field_number, value) {
+			(1, ProtobufValue::Bytes(bytes)) => secret = bytes.to_vec(),
+			(2, ProtobufValue::Bytes(bytes)) => name = String::from_utf8_lossy(bytes).to_string(),
+			(3, ProtobufValue::Bytes(bytes)) => issuer = String::from_utf8_lossy(bytes).to_string(),
+			(4, ProtobufValue::Varint(algorithm)) => {
+				totp.algorithm = match algorithm {
+					0 | 1 => TotpAlgorithm::Sha1,
+					2 => TotpAlgorithm::Sha256,
+					3 => TotpAlgorithm::Sha512,
+					_ => return Ok(None),
+				};
+			}
+			(5, ProtobufValue::Varint(digits)) => totp.digits = if digits == 2 { 8 } else { 6 },
+			(6, ProtobufValue::Varint(otp_type)) => is_totp = otp_type != 1,
+			_ => {}
+		}
+	}
+
+	if !is_totp {
+		return Ok(None);
+	}
+	if secret.is_empty() {
+		return Err(OtpImportError::MissingSecret);
+	}
+	totp.secret = encode_base32(&secret);
+
+	// The name may repeat the issuer like the label of an otpauth URI does
+	let label = match name.split_once(':') {
+		Some((name_issuer, label)) if issuer.is_empty() || name_issuer.trim() == issuer.trim() => {
+			if issuer.is_empty() {
+				issuer = name_issuer.trim().to_string();
+			}
+			label.trim().to_string()
+		}
+		_ => name.trim().to_string(),
+	};
+
+	Ok(Some(OtpImport { issuer: issuer.trim().to_string(), label, totp }))
+}
+
+enum ProtobufValue<'a> {
+	Varint(u64),
+	Bytes(&'a [u8]),
+	Fixed,
+}
+
+/// Minimal protobuf wire format reader, sufficient for the migration payload.
+fn read_protobuf_fields(data: &[u8]) -> Result<Vec<(u64, ProtobufValue<'_>)>, OtpImportError> {
+	let mut fields = Vec::new();
+	let mut pos = 0;
+	while pos < data.len() {
+		let key = read_varint(data, &mut pos)?;
+		let value = match key & 0x07 {
+			0 => ProtobufValue::Varint(read_varint(data, &mut pos)?),
+			1 => {
+				pos += 8;
+				ProtobufValue::Fixed
+			}
+			2 => {
+				let length = read_varint(data, &mut pos)? as usize;
+				let end = pos.checked_add(length).filter(|end| *end <= data.len()).ok_or(OtpImportError::InvalidPayload)?;
+				let bytes = &data[pos..end];
+				pos = end;
+				ProtobufValue::Bytes(bytes)
+			}
+			5 => {
+				pos += 4;
+				ProtobufValue::Fixed
+			}
+			_ => return Err(OtpImportError::InvalidPayload),
+		};
+		if pos > data.len() {
+			return Err(OtpImportError::InvalidPayload);
+		}
+		fields.push((key >> 3, value));
+	}
+	Ok(fields)
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, OtpImportError> {
+	let mut value = 0u64;
+	for shift in (0..64).step_by(7) {
+		let byte = *data.get(*pos).ok_or(OtpImportError::InvalidPayload)?;
+		*pos += 1;
+		value |= ((byte & 0x7F) as u64) << shift;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err(OtpImportError::InvalidPayload)
+}
+
+/// Password managers store either an otpauth:// URI or just the base32 secret.
+pub fn parse_totp(totp: &str) -> Option<TotpConfig> {
+	if totp.contains("://") {
+		return match parse_otp_uri(totp) {
+			Ok(mut imports) if imports.len() == 1 => Some(imports.remove(0).totp),
+			_ => None,
+		};
+	}
+	let config = TotpConfig::new(totp);
+	config.generate_code(SystemTime::now()).map(|_| config)
+}
+
+/// Builds an otpauth:// URI that authenticator apps can import, the inverse of the parser above.
+pub fn format_otpauth_uri(issuer: &str, label: &str, totp: &TotpConfig) -> String {
+	let path = if issuer.is_empty() { percent_encode(label) } else { format!("{}:{}", percent_encode(issuer), percent_encode(label)) };
+	let mut uri = format!("{}totp/{}?secret={}", OTPAUTH_SCHEME, path, totp.secret);
+	if !issuer.is_empty() {
+		uri.push_str(&format!("&issuer={}", percent_encode(issuer)));
+	}
+	uri.push_str(&format!("&algorithm={}&digits={}&period={}", totp.algorithm.get_name(), totp.digits, totp.period));
+	uri
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+	query.split('&')
+		.filter(|pair| !pair.is_empty())
+		.map(|pair| {
+			let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+			(percent_decode(key), percent_decode(value))
+		})
+		.collect()
+}
+
+pub fn percent_decode(encoded: &str) -> String {
+	let bytes = encoded.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut idx = 0;
+	while idx < bytes.len() {
+		if bytes[idx] == b'%' && idx + 2 < bytes.len() {
+			let hex = std::str::from_utf8(&bytes[idx + 1..idx + 3]).ok();
+			if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+				decoded.push(byte);
+				idx += 3;
+				continue;
+			}
+		}
+		decoded.push(bytes[idx]);
+		idx += 1;
+	}
+	String::from_utf8_lossy(&decoded).to_string()
+}
+
+pub fn percent_encode(value: &str) -> String {
+	let mut encoded = String::with_capacity(value.len());
+	for byte in value.bytes() {
+		if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'@') {
+			encoded.push(byte as char);
+		} else {
+			encoded.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	encoded
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn push_varint(data: &mut Vec<u8>, mut value: u64) {
+		while value >= 0x80 {
+			data.push((value as u8) | 0x80);
+			value >>= 7;
+		}
+		data.push(value as u8);
+	}
+
+	fn push_bytes_field(data: &mut Vec<u8>, field_number: u64, bytes: &[u8]) {
+		push_varint(data, field_number << 3 | 2);
+		push_varint(data, bytes.len() as u64);
+		data.extend_from_slice(bytes);
+	}
+
+	fn push_varint_field(data: &mut Vec<u8>, field_number: u64, value: u64) {
+		push_varint(data, field_number << 3);
+		push_varint(data, value);
+	}
+
+	/// An OtpParameters message of the migration payload.
+	fn create_parameters(secret: &[u8], name: &str, issuer: &str, algorithm: u64, digits: u64, otp_type: u64) -> Vec<u8> {
+		let mut parameters = Vec::new();
+		push_bytes_field(&mut parameters, 1, secret);
+		push_bytes_field(&mut parameters, 2, name.as_bytes());
+		push_bytes_field(&mut parameters, 3, issuer.as_bytes());
+		push_varint_field(&mut parameters, 4, algorithm);
+		push_varint_field(&mut parameters, 5, digits);
+		push_varint_field(&mut parameters, 6, otp_type);
+		parameters
+	}
+
+	fn create_migration_uri(payload: &[u8]) -> String {
+		format!("otpauth-migration://offline?data={}", percent_encode(&STANDARD.encode(payload)))
+	}
+
+	#[test]
+	fn parses_otpauth_uri() {
+		let imports = parse_otp_uri("otpauth://totp/ACME%20Co:john@example.com?secret=JBSW Y3DP EHPK 3PXP&algorithm=SHA256&digits=8&period=60").unwrap();
+		assert_eq!(imports.len(), 1);
+		assert_eq!(imports[0].issuer, "ACME Co");
+		assert_eq!(imports[0].label, "john@example.com");
+		assert_eq!(imports[0].totp, TotpConfig { digits: 8, period: 60, algorithm: TotpAlgorithm::Sha256, ..TotpConfig::new("JBSWY3DPEHPK3PXP") });
+
+		let uri = format_otpauth_uri(&imports[0].issuer, &imports[0].label, &imports[0].totp);
+		let reparsed = parse_otp_uri(&uri).unwrap();
+		assert_eq!((reparsed[0].issuer.as_str(), reparsed[0].label.as_str()), ("ACME Co", "john@example.com"));
+		assert_eq!(reparsed[0].totp, imports[0].totp);
+	}
+
+	#[test]
+	fn rejects_invalid_otpauth_uris() {
+		assert_eq!(parse_otp_uri("https://example.com").unwrap_err(), OtpImportError::InvalidUri);
+		assert_eq!(parse_otp_uri("otpauth://hotp/label?secret=JBSWY3DP&counter=1").unwrap_err(), OtpImportError::UnsupportedType);
+		assert_eq!(parse_otp_uri("otpauth://totp/label?issuer=ACME").unwrap_err(), OtpImportError::MissingSecret);
+		assert_eq!(parse_otp_uri("otpauth://totp/label?secret=JBSWY3DP&digits=20").unwrap_err(), OtpImportError::InvalidUri);
+		assert_eq!(parse_otp_uri("otpauth://totp/label?secret=JBSWY3DP&algorithm=MD5").unwrap_err(), OtpImportError::InvalidUri);
+	}
+
+	#[test]
+	fn parses_migration_payload() {
+		let mut payload = Vec::new();
+		push_bytes_field(&mut payload, 1, &create_parameters(b"12345678901234567890", "ACME:alice", "ACME", 2, 2, 2));
+		push_bytes_field(&mut payload, 1, &create_parameters(b"abcdefghij", "counter", "", 1, 1, 1));
+		push_bytes_field(&mut payload, 1, &create_parameters(b"klmnopqrst", "Example:bob", "", 1, 1, 2));
+		// Version, batch size and index follow the entries and are ignored
+		push_varint_field(&mut payload, 2, 1);
+		push_varint_field(&mut payload, 3, 1);
+
+		let imports = parse_otp_uri(&create_migration_uri(&payload)).unwrap();
+		assert_eq!(imports.len(), 2);
+		assert_eq!((imports[0].issuer.as_str(), imports[0].label.as_str()), ("ACME", "alice"));
+		assert_eq!(imports[0].totp, TotpConfig { digits: 8, algorithm: TotpAlgorithm::Sha256, ..TotpConfig::new(&encode_base32(b"12345678901234567890")) });
+		assert_eq!((imports[1].issuer.as_str(), imports[1].label.as_str()), ("Example", "bob"));
+		assert_eq!(imports[1].totp, TotpConfig::new(&encode_base32(b"klmnopqrst")));
+	}
+
+	#[test]
+	fn rejects_invalid_migration_payload() {
+		let mut payload = Vec::new();
+		push_bytes_field(&mut payload, 1, &create_parameters(b"abcdefghij", "counter", "", 1, 1, 1));
+		assert_eq!(parse_otp_uri(&create_migration_uri(&payload)).unwrap_err(), OtpImportError::UnsupportedType);
+
+		let mut payload = Vec::new();
+		push_bytes_field(&mut payload, 1, &create_parameters(b"12345678901234567890", "alice", "ACME", 1, 1, 2));
+		payload.truncate(payload.len() - 3);
+		assert_eq!(parse_otp_uri(&create_migration_uri(&payload)).unwrap_err(), OtpImportError::InvalidPayload);
+		assert_eq!(parse_otp_uri("otpauth-migration://offline?data=%%%").unwrap_err(), OtpImportError::InvalidPayload);
+		assert_eq!(parse_otp_uri("otpauth-migration://offline").unwrap_err(), OtpImportError::InvalidUri);
+	}
+
+	#[test]
+	fn parses_stored_totp() {
+		assert_eq!(parse_totp("jbsw y3dp ehpk 3pxp"), Some(TotpConfig::new("JBSWY3DPEHPK3PXP")));
+		assert_eq!(parse_totp("otpauth://totp/label?secret=JBSWY3DPEHPK3PXP&period=60").map(|totp| totp.period), Some(60));
+		assert_eq!(parse_totp("not base32!"), None);
+	}
+}
@@ -1,5 +1,6 @@
 use crate::database_context::Account;
 use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
 
 struct Entry {
 	account_id: i32,
@@ -18,11 +19,11 @@ pub struct PageView {
 }
 
 impl Entry {
-	pub fn new(account: &Account) -> Self
+	pub fn new(account_id: i32, account_name: String) -> Self
 	{
 		Entry {
-			account_id: account.id,
-			account_name: account.account_name.clone(),
+			account_id,
+			account_name,
 		}
 	}
 
@@ -36,8 +37,7 @@ impl Entry {
 }
 
 impl Page {
-	pub fn new(accounts: &[Account]) -> Self {
-		let entries: Vec<Entry> = accounts.iter().map(|account| Entry::new(account)).collect();
+	pub fn new(entries: Vec<Entry>) -> Self {
 		Page {
 			entries,
 			selected_idx: 0,
@@ -76,17 +76,24 @@ impl Page {
 
 impl PageView {
 	pub fn new(accounts: &[Account]) -> Self {
-		let mut pages_needed = accounts.len() / MAX_ENTRIES_PER_PAGE;
-		if accounts.len() % 10 > 0 {
-			pages_needed += 1;
-		}
+		let items = accounts.iter().map(|account| {
+			if account.pinned {
+				(account.id, format!("{} {}", get_texts().list_accounts.pinned_marker, account.account_name))
+			} else {
+				(account.id, account.account_name.clone())
+			}
+		}).collect();
+		Self::new_from_items(items)
+	}
 
+	/// Creates a paged list of arbitrary items, each identified by an id and displayed by its label.
+	pub fn new_from_items(items: Vec<(i32, String)>) -> Self {
 		let mut pages: Vec<Page> = vec![];
-		for i in 0..pages_needed {
-			let start = i * MAX_ENTRIES_PER_PAGE;
-			let end = usize::min(start + 10, accounts.len());
-			let accounts_at_page = &accounts[start..end];
-			pages.push(Page::new(accounts_at_page));
+		let mut entries: Vec<Entry> = items.into_iter().map(|(id, label)| Entry::new(id, label)).collect();
+		while !entries.is_empty() {
+			let remaining = entries.split_off(usize::min(MAX_ENTRIES_PER_PAGE, entries.len()));
+			pages.push(Page::new(entries));
+			entries = remaining;
 		}
 
 		Self {
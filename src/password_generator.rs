@@ -0,0 +1,27 @@
+use rand::distributions::Alphanumeric;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+pub const DEFAULT_PASSWORD_LENGTH: usize = 30;
+
+const SPECIAL_CHARS: &[u8] = b"!@#$%^&*()_+-=[]{}|;:,.<>?";
+
+/// Creates a random alphanumeric password, roughly a quarter of the characters are
+/// replaced by special characters unless the target does not accept them.
+pub fn generate_password(length: usize, with_special_chars: bool) -> String {
+	let mut rng = rand::thread_rng();
+
+	let mut password: Vec<char> = (0..length)
+		.map(|_| rng.sample(Alphanumeric) as char)
+		.collect();
+
+	if with_special_chars && length > 0 {
+		for _ in 0..(length / 4) {
+			let idx = rng.gen_range(0..length);
+			let special_char = *SPECIAL_CHARS.choose(&mut rng).unwrap() as char;
+			password[idx] = special_char;
+		}
+	}
+
+	password.into_iter().collect()
+}
@@ -13,7 +13,8 @@ pub struct PasswordWidget {
 impl Widget for PasswordWidget {
 	fn display(&self, context: &mut TerminalContext, pos_x: u16, pos_y: u16) {
 		if let Some(str) = self.get_password_to_display().clone() {
-			context.print_at_position(pos_x, pos_y, str)
+			// A password history holds one password per line, they are shown on one
+			context.print_at_position(pos_x, pos_y, &str.replace('\n', " "))
 		}
 	}
 
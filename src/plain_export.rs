@@ -0,0 +1,85 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+use crate::database_context::{Account, DatabaseContext};
+use crate::date_util::format_date;
+use crate::otpauth::format_otpauth_uri;
+
+/// Columns every row of a CSV export starts with, the fields of the entries follow.
+const CSV_COLUMNS: [&str; 5] = ["name", "type", "password", "email", "totp"];
+
+#[derive(Serialize)]
+struct PlainEntry<'a> {
+	name: &'a str,
+	#[serde(rename = "type")]
+	entry_type: &'a str,
+	password: &'a str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	email: Option<&'a str>,
+	fields: serde_json::Map<String, serde_json::Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	totp: Option<String>,
+	created: String,
+	updated: String,
+	password_changed: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	expires: Option<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	attachments: Vec<PlainAttachment>,
+}
+
+#[derive(Serialize)]
+struct PlainAttachment {
+	name: String,
+	/// Base64 encoded content.
+	data: String,
+}
+
+fn get_totp_uri(account: &Account) -> Option<String> {
+	account.totp.as_ref().map(|totp| format_otpauth_uri("", &account.account_name, totp))
+}
+
+/// Writes the entries unencrypted as CSV with one column per field, attachments are left out.
+pub fn export_plain_csv(accounts: &[Account]) -> Vec<u8> {
+	let mut field_keys: Vec<&str> = Vec::new();
+	for field in accounts.iter().flat_map(|account| account.fields.iter()) {
+		if !field_keys.contains(&field.key.as_str()) {
+			field_keys.push(&field.key);
+		}
+	}
+
+	let mut writer = csv::Writer::from_writer(Vec::new());
+	writer.write_record(CSV_COLUMNS.iter().chain(field_keys.iter())).expect("Could not write CSV");
+	for account in accounts {
+		let mut record = vec![
+			account.account_name.clone(),
+			account.entry_type.clone(),
+			account.password.clone(),
+			account.email.clone().unwrap_or_default(),
+			get_totp_uri(account).unwrap_or_default(),
+		];
+		record.extend(field_keys.iter().map(|key| account.get_field(key).unwrap_or_default().to_string()));
+		writer.write_record(&record).expect("Could not write CSV");
+	}
+	writer.into_inner().expect("Could not write CSV")
+}
+
+/// Writes the entries unencrypted as a JSON array, including their attachments.
+pub fn export_plain_json(db_context: &DatabaseContext, accounts: &[Account]) -> Vec<u8> {
+	let entries: Vec<PlainEntry> = accounts.iter().map(|account| PlainEntry {
+		name: &account.account_name,
+		entry_type: &account.entry_type,
+		password: &account.password,
+		email: account.email.as_deref(),
+		fields: account.fields.iter().map(|field| (field.key.clone(), serde_json::Value::String(field.value.clone()))).collect(),
+		totp: get_totp_uri(account),
+		created: format_date(account.created_at),
+		updated: format_date(account.updated_at),
+		password_changed: format_date(account.password_changed_at),
+		expires: account.expires_at.map(format_date),
+		attachments: db_context.list_attachments(account.id).unwrap().into_iter()
+			.map(|attachment| PlainAttachment { data: BASE64.encode(db_context.get_attachment_data(attachment.id).unwrap()), name: attachment.file_name })
+			.collect(),
+	}).collect();
+	serde_json::to_vec_pretty(&entries).expect("Could not serialize export")
+}
@@ -0,0 +1,219 @@
+use std::io::{Read, Write};
+use std::iter;
+use std::time::SystemTime;
+use age::secrecy::SecretString;
+use age::DecryptError;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use crate::database_context::{Account, DatabaseContext, EntryField};
+use crate::date_util::{from_timestamp, to_timestamp};
+use crate::texts::get_texts;
+use crate::totp::{decode_base32, is_valid_digits, TotpAlgorithm, TotpConfig};
+use crate::vault_import::{ImportResult, ImportedEntry};
+
+const FORMAT_NAME: &str = "rusty-password-manager";
+/// Raised whenever the document changes incompatibly, older versions stay importable.
+const FORMAT_VERSION: u32 = 1;
+
+pub enum PortableError {
+	NotPortable,
+	WrongPassphrase,
+	TooMuchWork,
+	UnsupportedVersion,
+	Corrupted,
+}
+
+impl PortableError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().import;
+		match self {
+			PortableError::NotPortable => texts.not_portable.clone(),
+			PortableError::WrongPassphrase => texts.wrong_password.clone(),
+			PortableError::TooMuchWork => texts.too_much_work.clone(),
+			PortableError::UnsupportedVersion => texts.unsupported_portable_version.clone(),
+			PortableError::Corrupted => texts.corrupted_file.clone(),
+		}
+	}
+}
+
+/// The document inside the encrypted file. Times are seconds since the unix epoch.
+#[derive(Serialize, Deserialize)]
+struct PortableVault {
+	format: String,
+	version: u32,
+	exported_at: u64,
+	entries: Vec<PortableEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PortableEntry {
+	entry_type: String,
+	name: String,
+	password: String,
+	email: Option<String>,
+	fields: Vec<PortableField>,
+	created_at: u64,
+	updated_at: u64,
+	password_changed_at: u64,
+	expires_at: Option<u64>,
+	rotation_days: Option<u32>,
+	pinned: bool,
+	totp: Option<PortableTotp>,
+	ssh_confirm: bool,
+	attachments: Vec<PortableAttachment>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PortableField {
+	key: String,
+	value: String,
+	#[serde(default)]
+	hidden: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PortableTotp {
+	secret: String,
+	digits: u32,
+	period: u64,
+	algorithm: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PortableAttachment {
+	name: String,
+	/// Base64 encoded content.
+	data: String,
+}
+
+/// Writes the entries with everything they hold to a JSON document, encrypted as age file
+/// with the passphrase. The `age` command line tool can decrypt it as well.
+pub fn export_portable(db_context: &DatabaseContext, accounts: &[Account], passphrase: &str) -> Vec<u8> {
+	let entries = accounts.iter().map(|account| {
+		let attachments = db_context.list_attachments(account.id).unwrap().into_iter()
+			.map(|attachment| PortableAttachment { data: BASE64.encode(db_context.get_attachment_data(attachment.id).unwrap()), name: attachment.file_name })
+			.collect();
+		PortableEntry {
+			entry_type: account.entry_type.clone(),
+			name: account.account_name.clone(),
+			password: account.password.clone(),
+			email: account.email.clone(),
+			fields: account.fields.iter().map(|field| PortableField { key: field.key.clone(), value: field.value.clone(), hidden: field.hidden }).collect(),
+			created_at: to_timestamp(account.created_at),
+			updated_at: to_timestamp(account.updated_at),
+			password_changed_at: to_timestamp(account.password_changed_at),
+			expires_at: account.expires_at.map(to_timestamp),
+			rotation_days: account.rotation_days,
+			pinned: account.pinned,
+			totp: account.totp.as_ref().map(|totp| PortableTotp {
+				secret: totp.secret.clone(),
+				digits: totp.digits,
+				period: totp.period,
+				algorithm: totp.algorithm.get_name().to_string(),
+			}),
+			ssh_confirm: account.ssh_confirm,
+			attachments,
+		}
+	}).collect();
+	let vault = PortableVault { format: FORMAT_NAME.to_string(), version: FORMAT_VERSION, exported_at: to_timestamp(SystemTime::now()), entries };
+	let document = serde_json::to_vec(&vault).expect("Could not serialize export");
+
+	let encryptor = age::Encryptor::with_user_passphrase(SecretString::from(passphrase.to_string()));
+	let mut encrypted = Vec::new();
+	let mut writer = encryptor.wrap_output(&mut encrypted).expect("Could not encrypt export");
+	writer.write_all(&document).expect("Could not encrypt export");
+	writer.finish().expect("Could not encrypt export");
+	encrypted
+}
+
+/// Tells whether the file is encrypted with a passphrase the way exports are written.
+pub fn is_portable_export(data: &[u8]) -> bool {
+	age::Decryptor::new_buffered(data).is_ok_and(|decryptor| decryptor.is_scrypt())
+}
+
+pub fn import_portable(data: &[u8], passphrase: &str) -> Result<ImportResult, PortableError> {
+	let decryptor = age::Decryptor::new_buffered(data).map_err(|_| PortableError::NotPortable)?;
+	let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+	let mut reader = decryptor.decrypt(iter::once(&identity as &dyn age::Identity)).map_err(|e| match e {
+		DecryptError::DecryptionFailed | DecryptError::NoMatchingKeys => PortableError::WrongPassphrase,
+		DecryptError::ExcessiveWork { .. } => PortableError::TooMuchWork,
+		_ => PortableError::Corrupted,
+	})?;
+	let mut document = Vec::new();
+	reader.read_to_end(&mut document).map_err(|_| PortableError::Corrupted)?;
+
+	let header: serde_json::Value = serde_json::from_slice(&document).map_err(|_| PortableError::Corrupted)?;
+	if header.get("format").and_then(|format| format.as_str()) != Some(FORMAT_NAME) {
+		return Err(PortableError::NotPortable);
+	}
+	if header.get("version").and_then(|version| version.as_u64()).is_none_or(|version| version > FORMAT_VERSION as u64) {
+		return Err(PortableError::UnsupportedVersion);
+	}
+	let vault: PortableVault = serde_json::from_value(header).map_err(|_| PortableError::Corrupted)?;
+
+	let mut result = ImportResult::default();
+	for entry in vault.entries {
+		let mut account = Account::new(&entry.entry_type);
+		account.account_name = entry.name;
+		account.password = entry.password;
+		account.email = entry.email;
+		account.fields = entry.fields.into_iter().map(|field| EntryField { key: field.key, value: field.value, hidden: field.hidden }).collect();
+		account.created_at = read_timestamp(entry.created_at)?;
+		account.updated_at = read_timestamp(entry.updated_at)?;
+		account.password_changed_at = read_timestamp(entry.password_changed_at)?;
+		account.expires_at = entry.expires_at.map(read_timestamp).transpose()?;
+		account.rotation_days = entry.rotation_days;
+		account.pinned = entry.pinned;
+		account.totp = entry.totp.map(read_totp).transpose()?;
+		account.ssh_confirm = entry.ssh_confirm;
+		let attachments = entry.attachments.into_iter()
+			.map(|attachment| BASE64.decode(attachment.data).map(|data| (attachment.name, data)))
+			.collect::<Result<_, _>>().map_err(|_| PortableError::Corrupted)?;
+		result.entries.push(ImportedEntry { account, attachments });
+	}
+	Ok(result)
+}
+
+fn read_timestamp(timestamp: u64) -> Result<SystemTime, PortableError> {
+	from_timestamp(timestamp).ok_or(PortableError::Corrupted)
+}
+
+/// Exports only contain one-time passwords the vault accepts, anything else means the file was altered.
+fn read_totp(totp: PortableTotp) -> Result<TotpConfig, PortableError> {
+	let algorithm = TotpAlgorithm::from_name(&totp.algorithm).ok_or(PortableError::Corrupted)?;
+	if !is_valid_digits(totp.digits) || totp.period == 0 || decode_base32(&totp.secret).is_none() {
+		return Err(PortableError::Corrupted);
+	}
+	Ok(TotpConfig { secret: totp.secret, digits: totp.digits, period: totp.period, algorithm })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn totp(digits: u32, period: u64, algorithm: &str) -> PortableTotp {
+		PortableTotp { secret: "JBSWY3DPEHPK3PXP".to_string(), digits, period, algorithm: algorithm.to_string() }
+	}
+
+	#[test]
+	fn reads_valid_totp() {
+		let config = read_totp(totp(8, 60, "SHA256")).ok().unwrap();
+		assert_eq!(config, TotpConfig { digits: 8, period: 60, algorithm: TotpAlgorithm::Sha256, ..TotpConfig::new("JBSWY3DPEHPK3PXP") });
+	}
+
+	#[test]
+	fn rejects_altered_totp() {
+		for altered in [totp(20, 30, "SHA1"), totp(5, 30, "SHA1"), totp(6, 0, "SHA1"), totp(6, 30, "MD5")] {
+			assert!(matches!(read_totp(altered), Err(PortableError::Corrupted)));
+		}
+		let invalid_secret = PortableTotp { secret: "not base32!".to_string(), ..totp(6, 30, "SHA1") };
+		assert!(matches!(read_totp(invalid_secret), Err(PortableError::Corrupted)));
+	}
+
+	#[test]
+	fn rejects_timestamps_out_of_range() {
+		assert!(matches!(read_timestamp(u64::MAX), Err(PortableError::Corrupted)));
+		assert_eq!(read_timestamp(1_700_000_000).ok().map(to_timestamp), Some(1_700_000_000));
+	}
+}
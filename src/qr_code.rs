@@ -0,0 +1,73 @@
+use qrcode::render::svg;
+use qrcode::{Color, EcLevel, QrCode};
+
+/// Modules of empty border around the code, scanners need it to find the code on a dark terminal.
+const QUIET_ZONE: usize = 2;
+
+/// Renders the data as QR code lines, two modules per character using unicode half blocks.
+/// Light modules are drawn as blocks, so the code reads correctly on a dark terminal background.
+pub fn render_qr_code(data: &str) -> Option<Vec<String>> {
+	render_half_blocks(data, Color::Light)
+}
+
+/// Like [render_qr_code], but dark modules are drawn as blocks for printing on white paper.
+pub fn render_printable_qr_code(data: &str) -> Option<Vec<String>> {
+	render_half_blocks(data, Color::Dark)
+}
+
+/// Renders the code as SVG image with dark modules, to embed it in HTML documents.
+pub fn render_qr_code_svg(data: &str) -> Option<String> {
+	let code = QrCode::with_error_correction_level(data.as_bytes(), EcLevel::M).ok()?;
+	Some(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+fn render_half_blocks(data: &str, block_color: Color) -> Option<Vec<String>> {
+	let code = QrCode::with_error_correction_level(data.as_bytes(), EcLevel::L).ok()?;
+	let width = code.width();
+	let modules = code.to_colors();
+	let size = width + 2 * QUIET_ZONE;
+	let is_block = |x: usize, y: usize| {
+		if x < QUIET_ZONE || y < QUIET_ZONE || x >= width + QUIET_ZONE || y >= width + QUIET_ZONE {
+			return block_color == Color::Light;
+		}
+		modules[(y - QUIET_ZONE) * width + (x - QUIET_ZONE)] == block_color
+	};
+
+	let mut lines = Vec::with_capacity(size.div_ceil(2));
+	for y in (0..size).step_by(2) {
+		let line = (0..size).map(|x| {
+			let top = is_block(x, y);
+			let bottom = is_block(x, y + 1);
+			match (top, bottom) {
+				(true, true) => '█',
+				(true, false) => '▀',
+				(false, true) => '▄',
+				(false, false) => ' ',
+			}
+		}).collect();
+		lines.push(line);
+	}
+	Some(lines)
+}
+
+/// Builds the string phones understand to join a Wi-Fi network.
+pub fn format_wifi_string(ssid: &str, security: &str, password: &str) -> String {
+	let security = match security.trim().to_uppercase().as_str() {
+		"" if password.is_empty() => "nopass".to_string(),
+		"" => "WPA".to_string(),
+		"NOPASS" | "NONE" => "nopass".to_string(),
+		other => other.to_string(),
+	};
+	format!("WIFI:T:{};S:{};P:{};;", security, escape_wifi_value(ssid), escape_wifi_value(password))
+}
+
+fn escape_wifi_value(value: &str) -> String {
+	let mut escaped = String::with_capacity(value.len());
+	for c in value.chars() {
+		if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+			escaped.push('\\');
+		}
+		escaped.push(c);
+	}
+	escaped
+}
@@ -0,0 +1,93 @@
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use crossterm::event::KeyCode;
+use crate::database_context::{Account, DatabaseManager};
+use crate::date_util::format_date;
+use crate::page_list_view::PageView;
+use crate::state_item::StateItem;
+use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
+use crate::transition::Transition;
+
+/// Entries are listed as soon as their rotation is due within this number of days.
+pub const ROTATION_WARNING_DAYS: u32 = 14;
+
+pub struct RotationReminderStateItem {
+	accounts: Vec<Account>,
+	page_view: PageView,
+	next_state: Option<Transition>,
+}
+
+impl RotationReminderStateItem {
+	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>) -> Self {
+		let accounts = match db_manager.lock().unwrap().get_database_context() {
+			Some(context) => context.list_accounts_due_for_rotation(ROTATION_WARNING_DAYS).unwrap(),
+			None => panic!("Database not initialized"),
+		};
+
+		let items = accounts.iter().map(|account| {
+			let status = get_rotation_status(account).unwrap_or_default();
+			(account.id, format!("{} - {}", account.account_name, status))
+		}).collect();
+
+		Self {
+			page_view: PageView::new_from_items(items),
+			accounts,
+			next_state: None,
+		}
+	}
+
+	fn select_account(&mut self) {
+		let id = match self.page_view.get_selected_account_id() {
+			Some(id) => id,
+			None => return,
+		};
+
+		if let Some(account) = self.accounts.iter().find(|account| account.id == id) {
+			self.next_state = Some(Transition::ToShowAccount(account.clone()));
+		}
+	}
+}
+
+/// Describes when the password of the account has to be changed, if a rotation is configured.
+pub fn get_rotation_status(account: &Account) -> Option<String> {
+	let due_date = account.get_rotation_due_date()?;
+	let texts = &get_texts().rotation;
+	if due_date <= SystemTime::now() {
+		Some(format!("{} {}", texts.expired_on, format_date(due_date)))
+	} else {
+		Some(format!("{} {}", texts.due_on, format_date(due_date)))
+	}
+}
+
+impl StateItem for RotationReminderStateItem {
+	fn display(&self, context: &mut TerminalContext) {
+		context.print_styled_at_position(0, 0, &get_texts().rotation.heading, StyleAttribute::Underline);
+		self.page_view.display_page(context, 0, 2);
+
+		let input = &get_texts().input;
+		context.draw_control_footer(vec![&get_texts().rotation.continue_input, &input.down_arrow, &input.up_arrow, &input.left_arrow, &input.right_arrow, &input.enter]);
+	}
+
+	fn update_display(&self) -> bool {
+		false
+	}
+
+	fn register_input(&mut self, key_code: KeyCode) {
+		match key_code {
+			KeyCode::Enter => self.select_account(),
+			KeyCode::Down => self.page_view.next_account(),
+			KeyCode::Up => self.page_view.prev_account(),
+			KeyCode::Left => self.page_view.prev_page(),
+			KeyCode::Right => self.page_view.next_page(),
+			KeyCode::Char('q') | KeyCode::Esc => {
+				self.next_state = Some(Transition::ToMainMenu);
+			}
+			_ => {}
+		}
+	}
+
+	fn next_state(&self) -> Option<Transition> {
+		self.next_state.clone()
+	}
+}
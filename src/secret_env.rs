@@ -0,0 +1,139 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+const READ_BUFFER_SIZE: usize = 8192;
+
+/// Replaces secrets in a stream of output. A secret may be split across two reads,
+/// so the end of a chunk which could be the start of a secret is held back until more data arrives.
+pub struct OutputMasker {
+	secrets: Vec<Vec<u8>>,
+	mask: Vec<u8>,
+	pending: Vec<u8>,
+}
+
+impl OutputMasker {
+	pub fn new(secrets: &[String], mask: &str) -> Self {
+		let mut secrets: Vec<Vec<u8>> = secrets.iter().filter(|secret| !secret.is_empty()).map(|secret| secret.as_bytes().to_vec()).collect();
+		// Longer secrets first, so a secret containing another one is masked as a whole
+		secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+		secrets.dedup();
+		Self { secrets, mask: mask.as_bytes().to_vec(), pending: Vec::new() }
+	}
+
+	/// Returns the output which is safe to show, at the end of the stream finish has to be true.
+	pub fn mask(&mut self, data: &[u8], finish: bool) -> Vec<u8> {
+		self.pending.extend_from_slice(data);
+		let mut output = Vec::with_capacity(self.pending.len());
+		let mut idx = 0;
+		while idx < self.pending.len() {
+			let rest = &self.pending[idx..];
+			if let Some(secret) = self.secrets.iter().find(|secret| rest.starts_with(secret)) {
+				output.extend_from_slice(&self.mask);
+				idx += secret.len();
+			} else if !finish && self.secrets.iter().any(|secret| secret.len() > rest.len() && secret.starts_with(rest)) {
+				break;
+			} else {
+				output.push(rest[0]);
+				idx += 1;
+			}
+		}
+		self.pending.drain(..idx);
+		output
+	}
+}
+
+fn forward_masked<R: Read, W: Write>(mut reader: R, mut writer: W, mut masker: OutputMasker) {
+	let mut buffer = [0u8; READ_BUFFER_SIZE];
+	loop {
+		let (data, finish) = match reader.read(&mut buffer) {
+			Ok(0) | Err(_) => (&buffer[..0], true),
+			Ok(length) => (&buffer[..length], false),
+		};
+		let output = masker.mask(data, finish);
+		if writer.write_all(&output).and_then(|_| writer.flush()).is_err() || finish {
+			return;
+		}
+	}
+}
+
+/// Starts the command with the variables added to its environment and waits for it.
+/// Its output is passed through with the values of the variables masked.
+/// Returns the exit code of the command.
+pub fn run_with_secrets(program: &str, args: &[String], variables: &[(String, String)], mask: &str) -> io::Result<i32> {
+	let secrets: Vec<String> = variables.iter().map(|(_, value)| value.clone()).collect();
+	let mut child = Command::new(program)
+		.args(args)
+		.envs(variables.iter().map(|(name, value)| (name, value)))
+		.stdin(Stdio::inherit())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()?;
+
+	let stdout = child.stdout.take().expect("Child stdout is piped");
+	let stderr = child.stderr.take().expect("Child stderr is piped");
+	let stdout_masker = OutputMasker::new(&secrets, mask);
+	let stderr_masker = OutputMasker::new(&secrets, mask);
+	let stdout_thread = thread::spawn(move || forward_masked(stdout, io::stdout(), stdout_masker));
+	let stderr_thread = thread::spawn(move || forward_masked(stderr, io::stderr(), stderr_masker));
+
+	let status = child.wait()?;
+	let _ = stdout_thread.join();
+	let _ = stderr_thread.join();
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::process::ExitStatusExt;
+		// Like a shell, a command killed by a signal reports 128 plus the signal number
+		if let Some(signal) = status.signal() {
+			return Ok(128 + signal);
+		}
+	}
+	Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mask_chunks(secrets: &[&str], chunks: &[&str]) -> String {
+		let secrets: Vec<String> = secrets.iter().map(|secret| secret.to_string()).collect();
+		let mut masker = OutputMasker::new(&secrets, "***");
+		let mut output = Vec::new();
+		for chunk in chunks {
+			output.extend(masker.mask(chunk.as_bytes(), false));
+		}
+		output.extend(masker.mask(&[], true));
+		String::from_utf8(output).unwrap()
+	}
+
+	#[test]
+	fn masks_secret_split_across_reads() {
+		assert_eq!(mask_chunks(&["hunter2"], &["token=hun", "ter2\n"]), "token=***\n");
+		assert_eq!(mask_chunks(&["hunter2"], &["h", "u", "n", "t", "e", "r", "2"]), "***");
+	}
+
+	#[test]
+	fn holds_back_only_possible_secret_start() {
+		let secrets = vec!["hunter2".to_string()];
+		let mut masker = OutputMasker::new(&secrets, "***");
+		assert_eq!(masker.mask(b"say hun", false), b"say ");
+		assert_eq!(masker.mask(b"gry", false), b"hungry");
+	}
+
+	#[test]
+	fn masks_overlapping_secrets() {
+		// The longer secret is masked as a whole, the first match wins where secrets overlap
+		assert_eq!(mask_chunks(&["pass", "password"], &["password pass"]), "*** ***");
+		assert_eq!(mask_chunks(&["abcd", "cdef"], &["abcdef cdef"]), "***ef ***");
+	}
+
+	#[test]
+	fn flushes_incomplete_secret_at_end() {
+		let secrets = vec!["hunter2".to_string()];
+		let mut masker = OutputMasker::new(&secrets, "***");
+		assert_eq!(masker.mask(b"end hunt", false), b"end ");
+		assert_eq!(masker.mask(b"", true), b"hunt");
+		assert_eq!(mask_chunks(&["hunter2", ""], &["hunter"]), "hunter");
+	}
+}
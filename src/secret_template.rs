@@ -0,0 +1,54 @@
+const PLACEHOLDER_START: &str = "{{";
+const PLACEHOLDER_END: &str = "}}";
+const REFERENCE_SCHEME: &str = "rpm://";
+
+/// A reference which could not be replaced, with the line it is on for the error message.
+pub struct TemplateError {
+	pub line: usize,
+	pub reference: String,
+	pub message: String,
+}
+
+/// Replaces every `{{ rpm://ENTRY/FIELD }}` placeholder with the value the resolver returns.
+/// Other `{{ ... }}` placeholders are left alone, so files which are templated by another tool as well still work.
+/// Nothing is rendered if a single reference fails, all failures are returned instead.
+pub fn render_template<F>(content: &str, unterminated_message: &str, mut resolve: F) -> Result<String, Vec<TemplateError>>
+where
+	F: FnMut(&str) -> Result<String, String>,
+{
+	let mut output = String::with_capacity(content.len());
+	let mut errors = Vec::new();
+	let mut rest = content;
+
+	while let Some(start) = rest.find(PLACEHOLDER_START) {
+		let after_start = &rest[start + PLACEHOLDER_START.len()..];
+		let reference_offset = after_start.len() - after_start.trim_start().len();
+		if !after_start[reference_offset..].starts_with(REFERENCE_SCHEME) {
+			output.push_str(&rest[..start + PLACEHOLDER_START.len()]);
+			rest = after_start;
+			continue;
+		}
+
+		let line = content[..content.len() - rest.len() + start].matches('\n').count() + 1;
+		output.push_str(&rest[..start]);
+		let end = match after_start.find(PLACEHOLDER_END) {
+			Some(end) if !after_start[..end].contains('\n') => end,
+			_ => {
+				let reference = after_start[reference_offset..].lines().next().unwrap_or_default().to_string();
+				errors.push(TemplateError { line, reference, message: unterminated_message.to_string() });
+				rest = "";
+				break;
+			}
+		};
+
+		let reference = after_start[..end].trim();
+		match resolve(&reference[REFERENCE_SCHEME.len()..]) {
+			Ok(value) => output.push_str(&value),
+			Err(message) => errors.push(TemplateError { line, reference: reference.to_string(), message }),
+		}
+		rest = &after_start[end + PLACEHOLDER_END.len()..];
+	}
+	output.push_str(rest);
+
+	if errors.is_empty() { Ok(output) } else { Err(errors) }
+}
@@ -1,25 +1,41 @@
 use crate::clipboard_controller::ClipboardController;
-use crate::database_context::{Account, DatabaseManager};
-use crate::input_handler::evaluate_yes_no_answer;
+use crate::database_context::{Account, DatabaseManager, PASSWORD_FIELD_KEY};
+use crate::date_util::{format_date, parse_date};
+use crate::input_handler::{evaluate_yes_no_answer, get_text_input};
 use crate::state_item::StateItem;
 use crate::terminal_context::{StyleAttribute, TerminalContext};
 use crate::transition::Transition;
 use crossterm::event::KeyCode;
+use std::cell::Cell;
 use std::cmp::PartialEq;
 use std::sync::{Arc, Mutex};
-use crate::password_widget::PasswordWidget;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::entry_templates::{find_template, ValidationError};
+use crate::entry_view::EntryView;
+use crate::rotation_reminder_state_item::get_rotation_status;
 use crate::texts::get_texts;
-use crate::widget::Widget;
+use crate::otpauth::format_otpauth_uri;
+use crate::qr_code::{format_wifi_string, render_qr_code};
+use crate::totp::{decode_base32, is_valid_digits, normalize_secret, TotpAlgorithm, TotpConfig};
 
-#[derive(PartialEq)]
+const FIELDS_POS_Y: u16 = 2;
+
+#[derive(PartialEq, Clone, Copy)]
 enum ShowAccountState {
 	ShowAccount,
-	EditAccountName,
-	EditPassword,
-	EditEmail,
+	EditField(usize),
 	DeleteAccount,
 	CopyPassword,
 	SaveChanges,
+	EditExpiry,
+	EditRotationInterval,
+	CopyTotpCode,
+	EditTotpSecret,
+	EditTotpDigits,
+	EditTotpPeriod,
+	EditTotpAlgorithm,
+	SelectQrValue(usize),
+	ShowQrCode(usize),
 }
 
 pub struct ShowAccountStateItem {
@@ -28,27 +44,59 @@ pub struct ShowAccountStateItem {
 	next_state: Option<Transition>,
 	db_manager: Arc<Mutex<DatabaseManager>>,
 	clipboard_controller: ClipboardController,
-	password_widget: PasswordWidget,
+	entry_view: EntryView,
+	validation_error: Option<ValidationError>,
+	input_buffer: String,
+	pending_totp: Option<TotpConfig>,
+	totp_drawn_at: Cell<u64>,
 }
 
 impl ShowAccountStateItem {
 	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>, account: Account) -> Self {
-		Self {
-			password_widget: PasswordWidget::new(account.password.clone()),
+		let state_item = Self {
+			entry_view: EntryView::new(&account),
 			account,
 			internal_state: Arc::new(Mutex::new(ShowAccountState::ShowAccount)),
 			db_manager,
 			next_state: None,
 			clipboard_controller: ClipboardController::new(),
+			validation_error: None,
+			input_buffer: String::new(),
+			pending_totp: None,
+			totp_drawn_at: Cell::new(0),
+		};
+		state_item.mark_account_used();
+		state_item
+	}
+
+	fn mark_account_used(&self) {
+		self.db_manager.lock().unwrap().mark_account_used(self.account.id);
+	}
+
+	fn toggle_pinned(&mut self) {
+		let database_manager = self.db_manager.lock().unwrap();
+		if let Some(db_context) = database_manager.get_database_context() {
+			db_context.set_account_pinned(self.account.id, !self.account.pinned).unwrap();
+			database_manager.safe_database();
+			self.account.pinned = !self.account.pinned;
 		}
 	}
 
+	fn show_fields(&self, context: &mut TerminalContext, highlighted: Option<usize>) {
+		self.entry_view.display(context, &self.account, FIELDS_POS_Y, highlighted);
+	}
+
 	fn show_account(&self, context: &mut TerminalContext) {
-		self.show_account_name(context, false);
-		self.show_email(context, false);
-		self.show_password(context, false);
+		self.show_fields(context, None);
 
-		context.draw_control_footer(vec![&get_texts().show_account.edit_input, &get_texts().show_account.delete_input, &get_texts().show_account.copy_input, &get_texts().show_account.quit_input])
+		let texts = &get_texts().show_account;
+		let pin_input = if self.account.pinned { &texts.unpin_input } else { &texts.pin_input };
+		let mut content = vec![&texts.edit_input, &texts.delete_input, &texts.copy_input, pin_input, &texts.attachments_input, &texts.rotation_input, &get_texts().totp.setup_input, &get_texts().qr_code.show_input];
+		if self.account.totp.is_some() {
+			content.push(&get_texts().totp.copy_input);
+		}
+		content.push(&texts.quit_input);
+		context.draw_control_footer(content)
 	}
 
 	fn show_account_input(&mut self, key_code: KeyCode) {
@@ -60,9 +108,24 @@ impl ShowAccountStateItem {
 					let mut state = state_ref.lock().unwrap();
 					*state = ShowAccountState::ShowAccount;
 				});
+				self.mark_account_used();
+			}
+			KeyCode::Char('p') => {
+				self.toggle_pinned();
+			}
+			KeyCode::Char('t') => {
+				self.copy_totp_code();
+			}
+			KeyCode::Char('k') if !self.get_qr_values().is_empty() => {
+				self.internal_state = Arc::new(Mutex::new(ShowAccountState::SelectQrValue(0)));
+			}
+			KeyCode::Char('o') => {
+				self.pending_totp = Some(self.account.totp.clone().unwrap_or_else(|| TotpConfig::new("")));
+				self.input_buffer = self.account.totp.as_ref().map(|totp| totp.secret.clone()).unwrap_or_default();
+				self.internal_state = Arc::new(Mutex::new(ShowAccountState::EditTotpSecret));
 			}
 			KeyCode::Char('e') => {
-				self.internal_state = Arc::new(Mutex::new(ShowAccountState::EditAccountName));
+				self.change_internal_state(ShowAccountState::EditField(0));
 			}
 			KeyCode::Char('q') => {
 				self.next_state = Some(Transition::ToMainMenu);
@@ -70,19 +133,24 @@ impl ShowAccountStateItem {
 			KeyCode::Char('d') => {
 				self.internal_state = Arc::new(Mutex::new(ShowAccountState::DeleteAccount));
 			}
+			KeyCode::Char('a') => {
+				self.next_state = Some(Transition::ToAttachments(self.account.clone()));
+			}
+			KeyCode::Char('r') => {
+				self.input_buffer = self.account.expires_at.map(format_date).unwrap_or_default();
+				self.internal_state = Arc::new(Mutex::new(ShowAccountState::EditExpiry));
+			}
 			_ => {}
 		}
 	}
 
-	fn show_copy_password(&self, context: &mut TerminalContext) {
-		self.show_account_name(context, false);
-		self.show_email(context, false);
-		self.show_password(context, false);
+	fn show_copy_value(&self, context: &mut TerminalContext, copy_msg: &String) {
+		self.show_fields(context, None);
 
 
 		let time_left = self.clipboard_controller.get_countdown_value();
 		if self.clipboard_controller.get_countdown_duration() == time_left {
-			context.draw_control_footer(vec![&get_texts().show_account.copy_msg])
+			context.draw_control_footer(vec![copy_msg])
 		} else {
 			let countdown = format!("{} {}s", &get_texts().show_account.copy_countdown, time_left);
 			context.draw_control_footer(vec![&countdown])
@@ -90,9 +158,7 @@ impl ShowAccountStateItem {
 	}
 
 	fn show_save_changes(&self, context: &mut TerminalContext) {
-		self.show_account_name(context, false);
-		self.show_email(context, false);
-		self.show_password(context, false);
+		self.show_fields(context, None);
 
 		context.draw_request_footer(&get_texts().show_account.save_question);
 	}
@@ -117,165 +183,422 @@ impl ShowAccountStateItem {
 					Some(account) => account,
 					None => panic!("Account id was unknown!"),
 				};
+				self.entry_view = EntryView::new(&account);
 				self.account = account;
 			}
 			self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
 		}
 	}
 
-	fn show_edit_accountname(&self, context: &mut TerminalContext) {
-		self.show_account_name(context, true);
-		self.show_email(context, false);
-		self.show_password(context, false);
+	fn show_edit_field(&self, context: &mut TerminalContext, row_idx: usize) {
+		self.show_fields(context, Some(row_idx));
 
-		let content = vec![&get_texts().input.down_arrow, &get_texts().input.up_arrow, &get_texts().input.enter];
+		let error_message = self.validation_error.as_ref().map(|e| e.get_message());
+		let mut content = vec![&get_texts().input.down_arrow, &get_texts().input.up_arrow, &get_texts().input.enter];
+		if let Some(message) = &error_message {
+			content.insert(0, message);
+		}
 		context.draw_control_footer(content);
-		context.move_cursor_to_position(self.account.account_name.len() as u16, 3);
-	}
 
-	fn show_edit_accountname_input(&mut self, key_code: KeyCode) {
-		let mut account_name = self.account.account_name.clone();
-		self.edit_account_input(key_code, &mut account_name, ShowAccountState::EditEmail, ShowAccountState::EditPassword);
-		self.account.account_name = account_name;
+		let (cursor_x, cursor_y) = self.entry_view.get_value_end_position(context, &self.account, FIELDS_POS_Y, row_idx);
+		context.move_cursor_to_position(cursor_x, cursor_y);
 	}
 
-	fn show_edit_password_name(&self, context: &mut TerminalContext) {
-		self.show_account_name(context, false);
-		self.show_email(context, false);
-		self.show_password(context, true);
-
-		let content = vec![&get_texts().input.down_arrow, &get_texts().input.up_arrow, &get_texts().input.enter];
-		context.draw_control_footer(content);
-		context.move_cursor_to_position(0, 9);
+	fn show_edit_field_input(&mut self, key_code: KeyCode, row_idx: usize) {
+		let row_count = self.entry_view.get_rows().len();
+		match key_code {
+			KeyCode::Enter => self.validate_changes(),
+			KeyCode::Backspace => {
+				let mut value = self.get_row_value(row_idx);
+				value.pop();
+				self.set_row_value(row_idx, value);
+			}
+			KeyCode::Char(c) => {
+				let mut value = self.get_row_value(row_idx);
+				value.push(c);
+				self.set_row_value(row_idx, value);
+			}
+			KeyCode::Up => {
+				let prev_idx = if row_idx == 0 { row_count - 1 } else { row_idx - 1 };
+				self.change_internal_state(ShowAccountState::EditField(prev_idx));
+			}
+			KeyCode::Down => {
+				self.change_internal_state(ShowAccountState::EditField((row_idx + 1) % row_count));
+			}
+			_ => (),
+		};
 	}
 
-	fn show_edit_password_input(&mut self, key_code: KeyCode)
-	{
-		let mut password = self.account.password.clone();
-		self.edit_account_input(key_code, &mut password, ShowAccountState::EditAccountName, ShowAccountState::EditEmail);
-		self.account.password = password;
+	fn get_row_value(&self, row_idx: usize) -> String {
+		let key = &self.entry_view.get_rows()[row_idx].key;
+		self.account.get_field(key).unwrap_or_default().to_string()
 	}
 
-	fn show_edit_email(&self, context: &mut TerminalContext) {
-		self.show_account_name(context, false);
-		self.show_email(context, true);
-		self.show_password(context, false);
+	fn set_row_value(&mut self, row_idx: usize, value: String) {
+		let key = self.entry_view.get_rows()[row_idx].key.clone();
+		self.entry_view.update_value(row_idx, &value);
+		self.account.set_field(&key, value);
+	}
 
-		let content = vec![&get_texts().input.down_arrow, &get_texts().input.up_arrow, &get_texts().input.enter];
-		context.draw_control_footer(content);
-		if let Some(email) = &self.account.email {
-			context.move_cursor_to_position(email.len() as u16, 6);
+	fn validate_changes(&mut self) {
+		for (row_idx, row) in self.entry_view.get_rows().iter().enumerate() {
+			if let Err(e) = row.validate(self.account.get_field(&row.key).unwrap_or_default()) {
+				self.validation_error = Some(e);
+				self.change_internal_state(ShowAccountState::EditField(row_idx));
+				return;
+			}
 		}
+		self.validation_error = None;
+		self.change_internal_state(ShowAccountState::SaveChanges);
 	}
 
-	fn show_edit_email_input(&mut self, key_code: KeyCode) {
-		let mut email = match &self.account.email {
-			Some(email) => email.clone(),
-			None => "".to_string(),
+	fn copy_totp_code(&mut self) {
+		let totp = match &self.account.totp {
+			Some(totp) => totp,
+			None => return,
+		};
+		let now = SystemTime::now();
+		let code = match totp.generate_code(now) {
+			Some(code) => code,
+			None => return,
 		};
 
-		self.edit_account_input(key_code, &mut email, ShowAccountState::EditPassword, ShowAccountState::EditAccountName);
-		self.account.email = Some(email);
+		// The code is useless once its period is over, so the clipboard is cleared at that point
+		let time_available = totp.get_seconds_remaining(now).min(u8::MAX as u64) as u8;
+		self.internal_state = Arc::new(Mutex::new(ShowAccountState::CopyTotpCode));
+		let state_ref = Arc::clone(&self.internal_state);
+		self.clipboard_controller.copy_value_to_clipboard(&code, time_available, move || {
+			let mut state = state_ref.lock().unwrap();
+			*state = ShowAccountState::ShowAccount;
+		});
+		self.mark_account_used();
 	}
 
-	fn show_delete_account(&self, context: &mut TerminalContext) {
-		self.show_account_name(context, false);
-		self.show_email(context, false);
-		self.show_password(context, false);
+	fn show_totp_code(&self, context: &mut TerminalContext) {
+		let totp = match &self.account.totp {
+			Some(totp) => totp,
+			None => return,
+		};
+		let now = SystemTime::now();
+		let texts = &get_texts().totp;
+		let line = match totp.generate_code(now) {
+			Some(code) => format!("{} {} ({}s)", texts.code_label, code, totp.get_seconds_remaining(now)),
+			None => format!("{} {}", texts.code_label, texts.invalid_secret),
+		};
+		context.print_at_position(0, 1, &line);
+	}
 
-		context.draw_request_footer(&get_texts().show_account.delete_question);
+	fn show_edit_totp_input(&mut self, key_code: KeyCode, current_state: ShowAccountState) {
+		if key_code == KeyCode::Esc {
+			self.validation_error = None;
+			self.pending_totp = None;
+			self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
+			return;
+		}
+		if !get_text_input(key_code, &mut self.input_buffer) {
+			return;
+		}
+
+		let mut totp = match self.pending_totp.take() {
+			Some(totp) => totp,
+			None => return,
+		};
+		let input = self.input_buffer.trim().to_string();
+
+		let result = match current_state {
+			ShowAccountState::EditTotpSecret => {
+				if input.is_empty() {
+					self.save_totp(None);
+					return;
+				}
+				match decode_base32(&input) {
+					Some(_) => {
+						totp.secret = normalize_secret(&input);
+						self.input_buffer = totp.digits.to_string();
+						Ok(ShowAccountState::EditTotpDigits)
+					}
+					None => Err(ValidationError::InvalidSecret),
+				}
+			}
+			ShowAccountState::EditTotpDigits => match input.parse::<u32>() {
+				Ok(digits) if is_valid_digits(digits) => {
+					totp.digits = digits;
+					self.input_buffer = totp.period.to_string();
+					Ok(ShowAccountState::EditTotpPeriod)
+				}
+				Ok(_) => Err(ValidationError::InvalidDigits),
+				Err(_) => Err(ValidationError::NotANumber),
+			},
+			ShowAccountState::EditTotpPeriod => match input.parse::<u64>() {
+				Ok(period) if period > 0 => {
+					totp.period = period;
+					self.input_buffer = totp.algorithm.get_name().to_string();
+					Ok(ShowAccountState::EditTotpAlgorithm)
+				}
+				_ => Err(ValidationError::NotANumber),
+			},
+			_ => match TotpAlgorithm::from_name(&input) {
+				Some(algorithm) => {
+					totp.algorithm = algorithm;
+					self.save_totp(Some(totp));
+					return;
+				}
+				None => Err(ValidationError::InvalidAlgorithm),
+			},
+		};
+
+		self.pending_totp = Some(totp);
+		match result {
+			Ok(next_state) => {
+				self.validation_error = None;
+				self.internal_state = Arc::new(Mutex::new(next_state));
+			}
+			Err(e) => self.validation_error = Some(e),
+		}
 	}
 
-	fn show_delete_account_input(&mut self, key_code: KeyCode) {
-		if let Some(accept) = evaluate_yes_no_answer(key_code) {
+	fn save_totp(&mut self, totp: Option<TotpConfig>) {
+		self.account.totp = totp;
+		self.pending_totp = None;
+		self.validation_error = None;
+		self.input_buffer.clear();
+		{
 			let database_manager = self.db_manager.lock().unwrap();
-			let db_context = match database_manager.get_database_context() {
-				Some(context) => context,
-				None => return,
-			};
-			if accept {
-				db_context.remove_account(self.account.id).unwrap();
+			if let Some(db_context) = database_manager.get_database_context() {
+				db_context.update_account(&self.account);
 				database_manager.safe_database();
-				self.next_state = Some(Transition::ToMainMenu);
-				return;
 			}
-			self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
 		}
+		self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
 	}
 
-	fn show_account_name(&self, context: &mut TerminalContext, highlighted: bool) {
-		if highlighted {
-			context.print_styled_at_position(0, 2, &get_texts().account.account_name, StyleAttribute::Bold);
-			context.print_styled_at_position(0, 3, &self.account.account_name, StyleAttribute::InverseColor);
-		} else {
-			context.print_at_position(0, 2, &get_texts().account.account_name);
-			context.print_at_position(0, 3, &self.account.account_name);
+	/// The values of the entry that can be shown as QR code, each with the name to list it under.
+	fn get_qr_values(&self) -> Vec<(String, String)> {
+		let texts = &get_texts().qr_code;
+		let mut values: Vec<(String, String)> = self.entry_view.get_rows().iter()
+			.filter_map(|row| {
+				let value = self.account.get_field(&row.key).unwrap_or_default();
+				if value.is_empty() { None } else { Some((row.get_name().to_string(), value.to_string())) }
+			})
+			.collect();
+
+		if let Some(ssid) = self.account.get_field("ssid").filter(|ssid| !ssid.is_empty()) {
+			let security = self.account.get_field("security").unwrap_or_default();
+			let password = self.account.get_field(PASSWORD_FIELD_KEY).unwrap_or_default();
+			values.push((texts.wifi_value.clone(), format_wifi_string(ssid, security, password)));
+		}
+		if let Some(totp) = &self.account.totp {
+			let username = self.account.get_username();
+			let (issuer, label) = if username.is_empty() { ("", self.account.account_name.as_str()) } else { (self.account.account_name.as_str(), username) };
+			values.push((texts.otpauth_value.clone(), format_otpauth_uri(issuer, label, totp)));
+		}
+		values
+	}
+
+	fn show_select_qr_value(&self, context: &mut TerminalContext, selected_idx: usize) {
+		context.print_at_position(0, FIELDS_POS_Y, &get_texts().qr_code.select_value);
+		for (idx, (name, _)) in self.get_qr_values().iter().enumerate() {
+			let pos_y = FIELDS_POS_Y + 2 + idx as u16;
+			if idx == selected_idx {
+				context.print_styled_at_position(0, pos_y, name, StyleAttribute::InverseColor);
+			} else {
+				context.print_at_position(0, pos_y, name);
+			}
+		}
+
+		let input = &get_texts().input;
+		context.draw_control_footer(vec![&input.down_arrow, &input.up_arrow, &input.enter, &input.escape]);
+	}
+
+	fn show_select_qr_value_input(&mut self, key_code: KeyCode, selected_idx: usize) {
+		let value_count = self.get_qr_values().len();
+		let next_state = match key_code {
+			KeyCode::Up => ShowAccountState::SelectQrValue(if selected_idx == 0 { value_count - 1 } else { selected_idx - 1 }),
+			KeyCode::Down => ShowAccountState::SelectQrValue((selected_idx + 1) % value_count),
+			KeyCode::Enter => ShowAccountState::ShowQrCode(selected_idx),
+			KeyCode::Esc => ShowAccountState::ShowAccount,
+			_ => return,
+		};
+		self.internal_state = Arc::new(Mutex::new(next_state));
+	}
+
+	fn show_qr_code(&self, context: &mut TerminalContext, value_idx: usize) {
+		let texts = &get_texts().qr_code;
+		let values = self.get_qr_values();
+		let (name, value) = match values.get(value_idx) {
+			Some(value) => value,
+			None => return,
+		};
+		context.print_styled_at_position(0, FIELDS_POS_Y, name, StyleAttribute::Bold);
+
+		// Leave room for the heading and the footer
+		let available_height = context.get_height().saturating_sub(FIELDS_POS_Y + 4) as usize;
+		match render_qr_code(value) {
+			Some(lines) if lines.len() <= available_height && lines[0].chars().count() < context.get_width() as usize => {
+				let pos_x = (context.get_width() - lines[0].chars().count() as u16) / 2;
+				for (idx, line) in lines.iter().enumerate() {
+					context.print_at_position(pos_x, FIELDS_POS_Y + 2 + idx as u16, line);
+				}
+			}
+			Some(_) => context.print_at_position(0, FIELDS_POS_Y + 2, &texts.too_small),
+			None => context.print_at_position(0, FIELDS_POS_Y + 2, &texts.too_long),
 		}
+
+		context.draw_control_footer(vec![&texts.close_input]);
 	}
 
-	fn show_email(&self, context: &mut TerminalContext, highlighted: bool) {
-		let email = match &self.account.email {
-			Some(email) => email,
-			None => "",
+	fn show_prompt_input(&self, context: &mut TerminalContext, prompt: &String) {
+		self.show_fields(context, None);
+
+		let heading = match &self.validation_error {
+			Some(e) => format!("{} {}", prompt, e.get_message()),
+			None => prompt.clone(),
 		};
-		if highlighted {
-			context.print_styled_at_position(0, 5, &get_texts().account.email, StyleAttribute::Bold);
-			context.print_styled_at_position(0, 6, &email, StyleAttribute::InverseColor);
+		context.draw_input_footer(&heading, self.input_buffer.clone());
+	}
+
+	fn show_edit_expiry_input(&mut self, key_code: KeyCode) {
+		if key_code == KeyCode::Esc {
+			self.validation_error = None;
+			self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
+			return;
+		}
+		if !get_text_input(key_code, &mut self.input_buffer) {
+			return;
+		}
+
+		if self.input_buffer.trim().is_empty() {
+			self.account.expires_at = None;
 		} else {
-			context.print_at_position(0, 5, &get_texts().account.email);
-			context.print_at_position(0, 6, &email);
+			match parse_date(&self.input_buffer) {
+				Some(date) => self.account.expires_at = Some(date),
+				None => {
+					self.validation_error = Some(ValidationError::InvalidDate);
+					return;
+				}
+			}
 		}
+
+		self.validation_error = None;
+		self.input_buffer = self.account.rotation_days.map(|days| days.to_string()).unwrap_or_default();
+		self.internal_state = Arc::new(Mutex::new(ShowAccountState::EditRotationInterval));
 	}
 
-	fn show_password(&self, context: &mut TerminalContext, highlighted: bool) {
-		if highlighted {
-			context.print_styled_at_position(0, 8, &get_texts().account.password, StyleAttribute::Bold);
-			self.password_widget.display(context, 0, 9);
+	fn show_edit_rotation_interval_input(&mut self, key_code: KeyCode) {
+		if key_code == KeyCode::Esc {
+			self.validation_error = None;
+			self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
+			return;
+		}
+		if !get_text_input(key_code, &mut self.input_buffer) {
+			return;
+		}
+
+		if self.input_buffer.trim().is_empty() {
+			self.account.rotation_days = None;
 		} else {
-			context.print_at_position(0, 8, &get_texts().account.password);
-			self.password_widget.display(context, 0, 9)
+			match self.input_buffer.trim().parse::<u32>() {
+				Ok(days) => self.account.rotation_days = if days == 0 { None } else { Some(days) },
+				Err(_) => {
+					self.validation_error = Some(ValidationError::NotANumber);
+					return;
+				}
+			}
+		}
+
+		self.validation_error = None;
+		self.input_buffer.clear();
+		{
+			let database_manager = self.db_manager.lock().unwrap();
+			if let Some(db_context) = database_manager.get_database_context() {
+				db_context.update_account(&self.account);
+				database_manager.safe_database();
+			}
 		}
+		self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
 	}
 
-	fn edit_account_input(&mut self, key_code: KeyCode, text_buffer: &mut String, next_state: ShowAccountState, prev_state: ShowAccountState) {
-		match key_code {
-			KeyCode::Enter => { self.change_internal_state(ShowAccountState::SaveChanges) }
-			KeyCode::Backspace => { text_buffer.pop(); }
-			KeyCode::Char(c) => text_buffer.push(c),
-			KeyCode::Up => { self.change_internal_state(prev_state) }
-			KeyCode::Down => { self.change_internal_state(next_state) }
+	fn show_delete_account(&self, context: &mut TerminalContext) {
+		self.show_fields(context, None);
 
-			_ => (),
-		};
+		context.draw_request_footer(&get_texts().show_account.delete_question);
+	}
+
+	fn show_delete_account_input(&mut self, key_code: KeyCode) {
+		if let Some(accept) = evaluate_yes_no_answer(key_code) {
+			let database_manager = self.db_manager.lock().unwrap();
+			let db_context = match database_manager.get_database_context() {
+				Some(context) => context,
+				None => return,
+			};
+			if accept {
+				db_context.remove_account(self.account.id).unwrap();
+				database_manager.safe_database();
+				self.next_state = Some(Transition::ToMainMenu);
+				return;
+			}
+			self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
+		}
 	}
 
 	fn change_internal_state(&mut self, new_state: ShowAccountState) {
-		self.password_widget.change_visibility(new_state == ShowAccountState::EditPassword);
+		let revealed_row = match new_state {
+			ShowAccountState::EditField(row_idx) => Some(row_idx),
+			_ => None,
+		};
+		self.entry_view.reveal_row(revealed_row);
 		self.internal_state = Arc::new(Mutex::new(new_state));
 	}
 }
 
 impl StateItem for ShowAccountStateItem {
 	fn display(&self, context: &mut TerminalContext) {
-		context.print_styled_at_position(0, 0, &get_texts().show_account.heading, StyleAttribute::Underline);
+		let mut heading = match find_template(&self.account.entry_type) {
+			Some(template) => format!("{} - {}", &get_texts().show_account.heading, template.name),
+			None => get_texts().show_account.heading.clone(),
+		};
+		if self.account.pinned {
+			heading = format!("{} {}", heading, get_texts().list_accounts.pinned_marker);
+		}
+		context.print_styled_at_position(0, 0, &heading, StyleAttribute::Underline);
+		if let Some(status) = get_rotation_status(&self.account) {
+			let pos_x = context.get_width().saturating_sub(1 + status.chars().count() as u16);
+			context.print_at_position(pos_x, 0, &status);
+		}
+		self.show_totp_code(context);
 
 		let internal_state = self.internal_state.lock().unwrap();
 		match &*internal_state {
 			ShowAccountState::ShowAccount => self.show_account(context),
-			ShowAccountState::EditAccountName => self.show_edit_accountname(context),
-			ShowAccountState::EditPassword => self.show_edit_password_name(context),
-			ShowAccountState::EditEmail => self.show_edit_email(context),
-			ShowAccountState::CopyPassword => self.show_copy_password(context),
+			ShowAccountState::EditField(row_idx) => self.show_edit_field(context, *row_idx),
+			ShowAccountState::CopyPassword => self.show_copy_value(context, &get_texts().show_account.copy_msg),
+			ShowAccountState::CopyTotpCode => self.show_copy_value(context, &get_texts().totp.copy_msg),
 			ShowAccountState::SaveChanges => self.show_save_changes(context),
 			ShowAccountState::DeleteAccount => self.show_delete_account(context),
+			ShowAccountState::EditExpiry => self.show_prompt_input(context, &get_texts().rotation.expiry_prompt),
+			ShowAccountState::EditRotationInterval => self.show_prompt_input(context, &get_texts().rotation.interval_prompt),
+			ShowAccountState::EditTotpSecret => self.show_prompt_input(context, &get_texts().totp.secret_prompt),
+			ShowAccountState::EditTotpDigits => self.show_prompt_input(context, &get_texts().totp.digits_prompt),
+			ShowAccountState::EditTotpPeriod => self.show_prompt_input(context, &get_texts().totp.period_prompt),
+			ShowAccountState::EditTotpAlgorithm => self.show_prompt_input(context, &get_texts().totp.algorithm_prompt),
+			ShowAccountState::SelectQrValue(selected_idx) => self.show_select_qr_value(context, *selected_idx),
+			ShowAccountState::ShowQrCode(value_idx) => self.show_qr_code(context, *value_idx),
 		};
 	}
 
 	fn update_display(&self) -> bool {
 		let internal_state = self.internal_state.lock().unwrap();
-		if &*internal_state == &ShowAccountState::CopyPassword {
+		if matches!(&*internal_state, ShowAccountState::CopyPassword | ShowAccountState::CopyTotpCode) {
 			return true;
 		}
+
+		// The one-time code and its countdown change every second, a QR code is left alone to avoid flicker
+		if self.account.totp.is_some() && !matches!(&*internal_state, ShowAccountState::ShowQrCode(_)) {
+			let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+			if self.totp_drawn_at.replace(now) != now {
+				return true;
+			}
+		}
 		false
 	}
 
@@ -284,16 +607,24 @@ impl StateItem for ShowAccountStateItem {
 		let internal_state = state_clone.lock().unwrap();
 		match &*internal_state {
 			ShowAccountState::ShowAccount => self.show_account_input(key_code),
-			ShowAccountState::EditAccountName => self.show_edit_accountname_input(key_code),
-			ShowAccountState::EditPassword => self.show_edit_password_input(key_code),
-			ShowAccountState::EditEmail => self.show_edit_email_input(key_code),
-			ShowAccountState::CopyPassword => {}
+			ShowAccountState::EditField(row_idx) => self.show_edit_field_input(key_code, *row_idx),
+			ShowAccountState::CopyPassword | ShowAccountState::CopyTotpCode => {}
 			ShowAccountState::SaveChanges => self.show_save_changes_input(key_code),
 			ShowAccountState::DeleteAccount => self.show_delete_account_input(key_code),
+			ShowAccountState::EditExpiry => self.show_edit_expiry_input(key_code),
+			ShowAccountState::EditRotationInterval => self.show_edit_rotation_interval_input(key_code),
+			ShowAccountState::EditTotpSecret | ShowAccountState::EditTotpDigits |
+			ShowAccountState::EditTotpPeriod | ShowAccountState::EditTotpAlgorithm => self.show_edit_totp_input(key_code, *internal_state),
+			ShowAccountState::SelectQrValue(selected_idx) => self.show_select_qr_value_input(key_code, *selected_idx),
+			ShowAccountState::ShowQrCode(_) => {
+				if key_code == KeyCode::Esc || key_code == KeyCode::Enter {
+					self.internal_state = Arc::new(Mutex::new(ShowAccountState::ShowAccount));
+				}
+			}
 		}
 	}
 
 	fn next_state(&self) -> Option<Transition> {
 		self.next_state.clone()
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,29 @@
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+/// The user the process at the other end of the socket runs as.
+#[cfg(target_os = "linux")]
+fn get_peer_uid(stream: &UnixStream) -> Option<u32> {
+	let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+	let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+	// SAFETY: the buffer is a ucred and its size is passed along
+	let result = unsafe {
+		libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, &mut credentials as *mut libc::ucred as *mut libc::c_void, &mut length)
+	};
+	(result == 0).then_some(credentials.uid)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_peer_uid(stream: &UnixStream) -> Option<u32> {
+	let mut uid = 0;
+	let mut gid = 0;
+	// SAFETY: both pointers refer to local variables
+	let result = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+	(result == 0).then_some(uid)
+}
+
+/// The agents only answer processes of their own user, whatever the permissions of the socket are.
+pub fn is_peer_current_user(stream: &UnixStream) -> bool {
+	// SAFETY: geteuid has no preconditions and cannot fail
+	get_peer_uid(stream) == Some(unsafe { libc::geteuid() })
+}
@@ -0,0 +1,282 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::ssh_key::{write_string, write_u32, SshKey, SshReader};
+#[cfg(unix)]
+use crate::file_accesssor::create_socket_directory;
+#[cfg(unix)]
+use crate::socket_util::is_peer_current_user;
+
+// Message numbers of the ssh-agent protocol (draft-miller-ssh-agent)
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+const MAX_MESSAGE_LENGTH: usize = 256 * 1024;
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct AgentKey {
+	pub account_id: i32,
+	pub name: String,
+	pub key: SshKey,
+	pub confirm: bool,
+}
+
+/// Sent to the user interface when a key that requires confirmation is about to be used.
+pub struct ConfirmRequest {
+	pub key_name: String,
+	reply: Sender<bool>,
+}
+
+impl ConfirmRequest {
+	pub fn answer(self, allow: bool) {
+		let _ = self.reply.send(allow);
+	}
+}
+
+/// Where confirmations are sent to, None while no screen is there to ask the user.
+type ConfirmSender = Arc<Mutex<Option<Sender<ConfirmRequest>>>>;
+
+/// Serves the keys over a Unix socket until it is dropped.
+pub struct SshAgent {
+	socket_path: PathBuf,
+	keys: Arc<Mutex<Vec<AgentKey>>>,
+	running: Arc<AtomicBool>,
+	confirm_sender: ConfirmSender,
+}
+
+impl SshAgent {
+	pub fn start(socket_path: &Path, keys: Vec<AgentKey>) -> io::Result<Self> {
+		let agent = Self {
+			socket_path: socket_path.to_path_buf(),
+			keys: Arc::new(Mutex::new(keys)),
+			running: Arc::new(AtomicBool::new(true)),
+			confirm_sender: Arc::new(Mutex::new(None)),
+		};
+		listen(&agent.socket_path, Arc::clone(&agent.keys), Arc::clone(&agent.running), Arc::clone(&agent.confirm_sender))?;
+		Ok(agent)
+	}
+
+	pub fn get_socket_path(&self) -> &Path {
+		&self.socket_path
+	}
+
+	/// Replaces the served keys, for entries which were added or removed since the agent started.
+	pub fn set_keys(&self, keys: Vec<AgentKey>) {
+		*self.keys.lock().unwrap() = keys;
+	}
+
+	/// Keys which require confirmation are refused while there is no sender.
+	pub fn set_confirm_sender(&self, confirm_sender: Option<Sender<ConfirmRequest>>) {
+		*self.confirm_sender.lock().unwrap() = confirm_sender;
+	}
+
+	pub fn set_confirm(&self, account_id: i32, confirm: bool) {
+		if let Some(key) = self.keys.lock().unwrap().iter_mut().find(|key| key.account_id == account_id) {
+			key.confirm = confirm;
+		}
+	}
+}
+
+impl Drop for SshAgent {
+	fn drop(&mut self) {
+		self.running.store(false, Ordering::SeqCst);
+		let _ = std::fs::remove_file(&self.socket_path);
+	}
+}
+
+#[cfg(unix)]
+fn listen(socket_path: &Path, keys: Arc<Mutex<Vec<AgentKey>>>, running: Arc<AtomicBool>, confirm_sender: ConfirmSender) -> io::Result<()> {
+	use std::os::unix::net::UnixListener;
+
+	// Only the user may enter the directory, so nobody else can connect to the socket in it
+	create_socket_directory()?;
+	// A socket left behind by a crashed agent would make bind fail
+	if socket_path.exists() {
+		std::fs::remove_file(socket_path)?;
+	}
+	let listener = UnixListener::bind(socket_path)?;
+	listener.set_nonblocking(true)?;
+
+	std::thread::spawn(move || {
+		while running.load(Ordering::SeqCst) {
+			match listener.accept() {
+				Ok((stream, _)) if !is_peer_current_user(&stream) => {}
+				Ok((stream, _)) => {
+					let keys = Arc::clone(&keys);
+					let confirm_sender = Arc::clone(&confirm_sender);
+					std::thread::spawn(move || {
+						if stream.set_nonblocking(false).is_ok() {
+							serve_client(stream, keys, confirm_sender);
+						}
+					});
+				}
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(100)),
+				Err(_) => break,
+			}
+		}
+	});
+	Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_socket_path: &Path, _keys: Arc<Mutex<Vec<AgentKey>>>, _running: Arc<AtomicBool>, _confirm_sender: ConfirmSender) -> io::Result<()> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "The ssh agent requires Unix sockets"))
+}
+
+fn serve_client<S: io::Read + io::Write>(mut stream: S, keys: Arc<Mutex<Vec<AgentKey>>>, confirm_sender: ConfirmSender) {
+	loop {
+		let mut length = [0u8; 4];
+		if stream.read_exact(&mut length).is_err() {
+			return;
+		}
+		let length = u32::from_be_bytes(length) as usize;
+		if length == 0 || length > MAX_MESSAGE_LENGTH {
+			return;
+		}
+		let mut message = vec![0u8; length];
+		if stream.read_exact(&mut message).is_err() {
+			return;
+		}
+
+		let response = handle_message(&message, &keys, &confirm_sender).unwrap_or_else(|| vec![SSH_AGENT_FAILURE]);
+		let mut framed = Vec::with_capacity(response.len() + 4);
+		write_string(&mut framed, &response);
+		if stream.write_all(&framed).is_err() {
+			return;
+		}
+	}
+}
+
+/// Answers a single request, None results in a failure message.
+fn handle_message(message: &[u8], keys: &Arc<Mutex<Vec<AgentKey>>>, confirm_sender: &ConfirmSender) -> Option<Vec<u8>> {
+	let mut reader = SshReader::new(message);
+	match reader.read_u8().ok()? {
+		SSH_AGENTC_REQUEST_IDENTITIES => {
+			let keys = keys.lock().unwrap();
+			let mut response = vec![SSH_AGENT_IDENTITIES_ANSWER];
+			write_u32(&mut response, keys.len() as u32);
+			for key in keys.iter() {
+				write_string(&mut response, &key.key.get_public_key_blob());
+				write_string(&mut response, key.name.as_bytes());
+			}
+			Some(response)
+		}
+		SSH_AGENTC_SIGN_REQUEST => {
+			let key_blob = reader.read_string().ok()?;
+			let data = reader.read_string().ok()?;
+
+			let (key, name, confirm) = {
+				let keys = keys.lock().unwrap();
+				let agent_key = keys.iter().find(|key| key.key.get_public_key_blob() == key_blob)?;
+				(agent_key.key.clone(), agent_key.name.clone(), agent_key.confirm)
+			};
+
+			// The lock is released while waiting, so other clients can still list the keys
+			if confirm && !request_confirmation(confirm_sender, name) {
+				return None;
+			}
+
+			let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+			write_string(&mut response, &key.sign(data));
+			Some(response)
+		}
+		_ => None,
+	}
+}
+
+fn request_confirmation(confirm_sender: &ConfirmSender, key_name: String) -> bool {
+	let (reply, answer) = channel();
+	let sent = match &*confirm_sender.lock().unwrap() {
+		Some(sender) => sender.send(ConfirmRequest { key_name, reply }).is_ok(),
+		None => false,
+	};
+	if !sent {
+		return false;
+	}
+	answer.recv_timeout(CONFIRM_TIMEOUT).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::{Cursor, Read, Write};
+
+	const PRIVATE_KEY: &str = include_str!("../tests/fixtures/ed25519_key");
+	// Framed messages as OpenSSH sends and expects them, written with Python
+	const IDENTITIES_REQUEST: &str = "000000010b";
+	const IDENTITIES_ANSWER: &str = "0000004a0c00000001000000330000000b7373682d656432353531390000002006cd595d78cda2a2fc0ac513bb8f8a685eeaa8cb96b74e\
+		4818bfa95e68673c210000000a4465706c6f79206b6579";
+	/// Signs "session data to sign", with the flags which ask RSA keys for SHA-256 signatures.
+	const SIGN_REQUEST: &str = "000000540d000000330000000b7373682d656432353531390000002006cd595d78cda2a2fc0ac513bb8f8a685eeaa8cb96b74e4818bfa95e\
+		68673c210000001473657373696f6e206461746120746f207369676e00000002";
+	const SIGN_RESPONSE: &str = "000000580e000000530000000b7373682d656432353531390000004027a0efe89c71eb46efb04ab6ffd156df2f38b897a0ab969ff9de82\
+		81f8711c4eaf6cd9595a1fc6345c9b1596d088222e7c570f9ad6cf5d335e5ef91c6f1fcd0a";
+	const FAILURE: &str = "0000000105";
+
+	/// A client connection which sends the given bytes and records the answers.
+	struct FakeStream {
+		input: Cursor<Vec<u8>>,
+		output: Vec<u8>,
+	}
+
+	impl Read for FakeStream {
+		fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+			self.input.read(buffer)
+		}
+	}
+
+	impl Write for FakeStream {
+		fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+			self.output.write(buffer)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	fn decode_hex(hex: &str) -> Vec<u8> {
+		(0..hex.len()).step_by(2).map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).unwrap()).collect()
+	}
+
+	fn serve(requests: &[&str], confirm: bool) -> Vec<u8> {
+		let key = AgentKey { account_id: 1, name: "Deploy key".to_string(), key: SshKey::from_openssh_pem(PRIVATE_KEY).unwrap(), confirm };
+		let mut stream = FakeStream { input: Cursor::new(decode_hex(&requests.concat())), output: Vec::new() };
+		serve_client(&mut stream, Arc::new(Mutex::new(vec![key])), Arc::new(Mutex::new(None)));
+		stream.output
+	}
+
+	#[test]
+	fn lists_identities() {
+		assert_eq!(serve(&[IDENTITIES_REQUEST], false), decode_hex(IDENTITIES_ANSWER));
+	}
+
+	#[test]
+	fn signs_request() {
+		assert_eq!(serve(&[SIGN_REQUEST, IDENTITIES_REQUEST], false), decode_hex(&[SIGN_RESPONSE, IDENTITIES_ANSWER].concat()));
+	}
+
+	#[test]
+	fn refuses_unknown_keys_and_messages() {
+		// The last byte of the public key differs
+		let unknown_key = SIGN_REQUEST.replacen("673c21", "673c22", 1);
+		let unknown_message = "0000000119";
+		assert_eq!(serve(&[&unknown_key, unknown_message], false), decode_hex(&[FAILURE, FAILURE].concat()));
+	}
+
+	#[test]
+	fn refuses_confirmed_key_without_anyone_to_ask() {
+		assert_eq!(serve(&[SIGN_REQUEST], true), decode_hex(FAILURE));
+	}
+
+	#[test]
+	fn stops_at_truncated_message() {
+		assert_eq!(serve(&[&SIGN_REQUEST[..40]], false), Vec::<u8>::new());
+	}
+}
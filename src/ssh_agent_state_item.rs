@@ -0,0 +1,210 @@
+use std::cell::RefCell;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use crossterm::event::KeyCode;
+use crate::database_context::DatabaseManager;
+use crate::file_accesssor::get_ssh_agent_socket_path;
+use crate::input_handler::evaluate_yes_no_answer;
+use crate::page_list_view::PageView;
+use crate::ssh_agent::{AgentKey, ConfirmRequest, SshAgent};
+use crate::ssh_key::{SshKey, PRIVATE_KEY_FIELD_KEY};
+use crate::state_item::StateItem;
+use crate::terminal_context::{StyleAttribute, TerminalContext};
+use crate::texts::get_texts;
+use crate::transition::Transition;
+
+struct KeyInfo {
+	account_id: i32,
+	name: String,
+	fingerprint: String,
+	confirm: bool,
+}
+
+/// Shows the keys served by the agent and asks for confirmations. The agent is started the first time
+/// this screen is opened and keeps running after it is left, until it is stopped or the program exits.
+pub struct SshAgentStateItem {
+	agent: Arc<Mutex<Option<SshAgent>>>,
+	start_error: Option<String>,
+	keys: Vec<KeyInfo>,
+	page_view: PageView,
+	confirm_receiver: Receiver<ConfirmRequest>,
+	pending_confirmation: RefCell<Option<ConfirmRequest>>,
+	next_state: Option<Transition>,
+	db_manager: Arc<Mutex<DatabaseManager>>,
+}
+
+impl SshAgentStateItem {
+	pub fn new(db_manager: Arc<Mutex<DatabaseManager>>, agent: Arc<Mutex<Option<SshAgent>>>) -> Self {
+		let accounts = match db_manager.lock().unwrap().get_database_context() {
+			Some(context) => context.list_all_accounts().unwrap(),
+			None => panic!("Database not initialized"),
+		};
+
+		let mut keys = Vec::new();
+		let mut agent_keys = Vec::new();
+		for account in accounts {
+			let ssh_key = match account.get_field(PRIVATE_KEY_FIELD_KEY).map(SshKey::from_stored) {
+				Some(Ok(ssh_key)) => ssh_key,
+				_ => continue,
+			};
+			keys.push(KeyInfo {
+				account_id: account.id,
+				name: account.account_name.clone(),
+				fingerprint: ssh_key.get_fingerprint(),
+				confirm: account.ssh_confirm,
+			});
+			agent_keys.push(AgentKey { account_id: account.id, name: account.account_name, key: ssh_key, confirm: account.ssh_confirm });
+		}
+
+		let (confirm_sender, confirm_receiver) = channel();
+		let mut start_error = None;
+		{
+			let mut running_agent = agent.lock().unwrap();
+			match &*running_agent {
+				Some(running_agent) => running_agent.set_keys(agent_keys),
+				None => match SshAgent::start(&get_ssh_agent_socket_path(), agent_keys) {
+					Ok(started_agent) => *running_agent = Some(started_agent),
+					Err(e) => start_error = Some(e.to_string()),
+				},
+			}
+			if let Some(running_agent) = &*running_agent {
+				running_agent.set_confirm_sender(Some(confirm_sender));
+			}
+		}
+
+		let mut state_item = Self {
+			agent,
+			start_error,
+			keys,
+			page_view: PageView::new_empty(),
+			confirm_receiver,
+			pending_confirmation: RefCell::new(None),
+			next_state: None,
+			db_manager,
+		};
+		state_item.update_page_view();
+		state_item
+	}
+
+	fn update_page_view(&mut self) {
+		let items = self.keys.iter().map(|key| {
+			let mut item = format!("{}  {}", key.name, key.fingerprint);
+			if key.confirm {
+				item = format!("{}  {}", item, get_texts().ssh_agent.confirm_marker);
+			}
+			(key.account_id, item)
+		}).collect();
+		self.page_view = PageView::new_from_items(items);
+	}
+
+	fn toggle_confirm(&mut self) {
+		let account_id = match self.page_view.get_selected_account_id() {
+			Some(id) => id,
+			None => return,
+		};
+		let key = match self.keys.iter_mut().find(|key| key.account_id == account_id) {
+			Some(key) => key,
+			None => return,
+		};
+		key.confirm = !key.confirm;
+
+		{
+			let database_manager = self.db_manager.lock().unwrap();
+			if let Some(db_context) = database_manager.get_database_context() {
+				db_context.set_ssh_confirm(account_id, key.confirm).unwrap();
+				database_manager.safe_database();
+			}
+		}
+		if let Some(agent) = &*self.agent.lock().unwrap() {
+			agent.set_confirm(account_id, key.confirm);
+		}
+		self.update_page_view();
+	}
+}
+
+impl StateItem for SshAgentStateItem {
+	fn display(&self, context: &mut TerminalContext) {
+		let texts = &get_texts().ssh_agent;
+		context.print_styled_at_position(0, 0, &texts.heading, StyleAttribute::Underline);
+
+		match (&*self.agent.lock().unwrap(), &self.start_error) {
+			(Some(agent), _) => {
+				context.print_at_position(0, 2, &texts.listening);
+				context.print_styled_at_position(0, 3, &format!("export SSH_AUTH_SOCK={}", agent.get_socket_path().display()), StyleAttribute::Bold);
+				context.print_at_position(0, 4, &texts.confirm_hint);
+			}
+			(None, error) => {
+				context.print_at_position(0, 2, &format!("{} {}", texts.start_failed, error.clone().unwrap_or_default()));
+			}
+		}
+
+		if self.keys.is_empty() {
+			context.print_at_position(0, 6, &texts.no_keys);
+		}
+		self.page_view.display_page(context, 0, 6);
+
+		match &*self.pending_confirmation.borrow() {
+			Some(request) => context.draw_request_footer(&texts.confirm_question.replace("{}", &request.key_name)),
+			None => {
+				let input = &get_texts().input;
+				context.draw_control_footer(vec![&texts.toggle_confirm_input, &texts.stop_input, &texts.quit_input, &input.down_arrow, &input.up_arrow]);
+			}
+		}
+	}
+
+	fn update_display(&self) -> bool {
+		if self.pending_confirmation.borrow().is_some() {
+			return false;
+		}
+		match self.confirm_receiver.try_recv() {
+			Ok(request) => {
+				*self.pending_confirmation.borrow_mut() = Some(request);
+				true
+			}
+			Err(_) => false,
+		}
+	}
+
+	fn register_input(&mut self, key_code: KeyCode) {
+		if self.pending_confirmation.borrow().is_some() {
+			if let Some(allow) = evaluate_yes_no_answer(key_code) {
+				if let Some(request) = self.pending_confirmation.borrow_mut().take() {
+					request.answer(allow);
+				}
+			}
+			return;
+		}
+
+		match key_code {
+			KeyCode::Down => self.page_view.next_account(),
+			KeyCode::Up => self.page_view.prev_account(),
+			KeyCode::Left => self.page_view.prev_page(),
+			KeyCode::Right => self.page_view.next_page(),
+			KeyCode::Char('c') => self.toggle_confirm(),
+			KeyCode::Char('s') => {
+				// Dropping the agent closes the socket
+				*self.agent.lock().unwrap() = None;
+				self.next_state = Some(Transition::ToMainMenu);
+			}
+			KeyCode::Char('q') | KeyCode::Esc => {
+				self.next_state = Some(Transition::ToMainMenu);
+			}
+			_ => {}
+		}
+	}
+
+	fn shutdown(&mut self) {
+		// Nobody is left to ask, keys which require confirmation are refused until the screen is opened again
+		if let Some(agent) = &*self.agent.lock().unwrap() {
+			agent.set_confirm_sender(None);
+		}
+		if let Some(request) = self.pending_confirmation.borrow_mut().take() {
+			request.answer(false);
+		}
+		print!("{}[2J", 27 as char);
+	}
+
+	fn next_state(&self) -> Option<Transition> {
+		self.next_state.clone()
+	}
+}
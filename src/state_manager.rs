@@ -1,10 +1,17 @@
 use crate::add_account_state_item::AddEntryStateItem;
+use crate::attachments_state_item::AttachmentsStateItem;
 use crate::authentication_state_item::AuthenticationStateItem;
 use crate::database_context::DatabaseManager;
+use crate::export_state_item::ExportStateItem;
+use crate::import_otp_state_item::ImportOtpStateItem;
+use crate::import_state_item::ImportStateItem;
 use crate::list_accounts_state::ListAccountsState;
 use crate::main_menu_state_item::MainMenuStateItem;
+use crate::rotation_reminder_state_item::RotationReminderStateItem;
 use crate::set_authentication_state_item::SetAuthenticationStateItem;
 use crate::show_account_state_item::ShowAccountStateItem;
+use crate::ssh_agent::SshAgent;
+use crate::ssh_agent_state_item::SshAgentStateItem;
 use crate::startup_state_item::StartupStateItem;
 use crate::state_item::StateItem;
 use crate::terminal_context::TerminalContext;
@@ -15,14 +22,23 @@ use std::sync::{Arc, Mutex};
 pub struct StateManager {
 	state: Option<Box<dyn StateItem>>,
 	db_manager: Arc<Mutex<DatabaseManager>>,
+	/// Keeps serving SSH keys after the agent screen is left.
+	ssh_agent: Arc<Mutex<Option<SshAgent>>>,
 	active: bool,
 }
 
+impl Default for StateManager {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl StateManager {
 	pub fn new() -> Self {
 		StateManager {
 			state: Some(Box::new(StartupStateItem::new())),
 			db_manager: Arc::new(Mutex::new(DatabaseManager::new())),
+			ssh_agent: Arc::new(Mutex::new(None)),
 			active: true,
 		}
 	}
@@ -72,9 +88,18 @@ impl StateManager {
 			Transition::ToListAccounts => self.transition_to(Box::new(ListAccountsState::new(Arc::clone(&self.db_manager)))),
 			Transition::ToChangeAuthentication => self.transition_to(Box::new(SetAuthenticationStateItem::new(Arc::clone(&self.db_manager)))),
 			Transition::ToShowAccount(account) => self.transition_to(Box::new(ShowAccountStateItem::new(Arc::clone(&self.db_manager), account))),
+			Transition::ToAttachments(account) => self.transition_to(Box::new(AttachmentsStateItem::new(Arc::clone(&self.db_manager), account))),
+			Transition::ToRotationReminder => self.transition_to(Box::new(RotationReminderStateItem::new(Arc::clone(&self.db_manager)))),
 			Transition::ToMainMenu => self.transition_to(Box::new(MainMenuStateItem::new())),
 			Transition::ToWipeDatabase => self.transition_to(Box::new(WipeDatabaseStateItem::new())),
-			Transition::ToExit => self.active = false,
+			Transition::ToImportOtp => self.transition_to(Box::new(ImportOtpStateItem::new(Arc::clone(&self.db_manager)))),
+			Transition::ToSshAgent => self.transition_to(Box::new(SshAgentStateItem::new(Arc::clone(&self.db_manager), Arc::clone(&self.ssh_agent)))),
+			Transition::ToImport => self.transition_to(Box::new(ImportStateItem::new(Arc::clone(&self.db_manager)))),
+			Transition::ToExport => self.transition_to(Box::new(ExportStateItem::new(Arc::clone(&self.db_manager)))),
+			Transition::ToExit => {
+				self.db_manager.lock().unwrap().save_pending_changes();
+				self.active = false;
+			}
 		}
 	}
 }
@@ -26,12 +26,16 @@ pub struct MainMenu {
 	list_accounts: String,
 	set_master_pwd: String,
 	wipe_database: String,
+	import_otp: String,
+	ssh_agent: String,
+	import: String,
+	export: String,
 	exit: String,
 }
 
 impl MainMenu {
 	pub fn get_menu_items(&self) -> Vec<String> {
-		vec![self.add_account.clone(), self.list_accounts.clone(), self.set_master_pwd.clone(), self.wipe_database.clone(), self.exit.clone()]
+		vec![self.add_account.clone(), self.list_accounts.clone(), self.set_master_pwd.clone(), self.wipe_database.clone(), self.import_otp.clone(), self.ssh_agent.clone(), self.import.clone(), self.export.clone(), self.exit.clone()]
 	}
 }
 
@@ -40,17 +44,22 @@ pub struct Account {
 	pub account_name: String,
 	pub email: String,
 	pub password: String,
+	pub password_history: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct AddAccount {
 	pub heading: String,
+	pub select_type: String,
 	pub account_exists: String,
-	pub add_email_question: String,
+	pub view_existing_input: String,
+	pub add_another_input: String,
+	pub add_field_question: String,
 	pub generate_pwd_question: String,
-	pub pwd_generated: String,
-	pub pwd_set: String,
+	pub account_saved: String,
 	pub cancel_question: String,
+	pub generate_ssh_key_question: String,
+	pub import_ssh_key_prompt: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -59,6 +68,7 @@ pub struct ListAccounts {
 	pub search: String,
 	pub search_input: String,
 	pub quit_input: String,
+	pub pinned_marker: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -71,9 +81,101 @@ pub struct ShowAccount {
 	pub copy_input: String,
 	pub edit_input: String,
 	pub delete_input: String,
+	pub attachments_input: String,
+	pub rotation_input: String,
+	pub pin_input: String,
+	pub unpin_input: String,
 	pub quit_input: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct Totp {
+	pub code_label: String,
+	pub invalid_secret: String,
+	pub setup_input: String,
+	pub copy_input: String,
+	pub copy_msg: String,
+	pub secret_prompt: String,
+	pub digits_prompt: String,
+	pub period_prompt: String,
+	pub algorithm_prompt: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QrCode {
+	pub show_input: String,
+	pub select_value: String,
+	pub wifi_value: String,
+	pub otpauth_value: String,
+	pub too_small: String,
+	pub too_long: String,
+	pub close_input: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OtpImport {
+	pub heading: String,
+	pub uri_prompt: String,
+	pub review_heading: String,
+	pub issuer: String,
+	pub label: String,
+	pub algorithm: String,
+	pub digits: String,
+	pub period: String,
+	pub new_entry_input: String,
+	pub attach_input: String,
+	pub skip_input: String,
+	pub select_account: String,
+	pub overwrite_question: String,
+	pub summary: String,
+	pub invalid_uri: String,
+	pub unsupported_type: String,
+	pub missing_secret: String,
+	pub invalid_payload: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SshAgent {
+	pub heading: String,
+	pub listening: String,
+	pub confirm_hint: String,
+	pub start_failed: String,
+	pub no_keys: String,
+	pub confirm_marker: String,
+	pub toggle_confirm_input: String,
+	pub stop_input: String,
+	pub quit_input: String,
+	pub confirm_question: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Attachments {
+	pub heading: String,
+	pub empty_msg: String,
+	pub add_input: String,
+	pub extract_input: String,
+	pub delete_input: String,
+	pub quit_input: String,
+	pub add_path_prompt: String,
+	pub extract_path_prompt: String,
+	pub delete_question: String,
+	pub added_msg: String,
+	pub extracted_msg: String,
+	pub read_failed: String,
+	pub write_failed: String,
+	pub too_large: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Rotation {
+	pub heading: String,
+	pub expired_on: String,
+	pub due_on: String,
+	pub expiry_prompt: String,
+	pub interval_prompt: String,
+	pub continue_input: String,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Auth {
 	pub enter_pwd_promt: String,
@@ -96,6 +198,209 @@ pub struct Wipe {
 	pub failure_msg: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct Validation {
+	pub required: String,
+	pub too_short: String,
+	pub too_long: String,
+	pub invalid_email: String,
+	pub invalid_url: String,
+	pub not_a_number: String,
+	pub invalid_card_number: String,
+	pub invalid_expiry: String,
+	pub invalid_port: String,
+	pub invalid_date: String,
+	pub invalid_secret: String,
+	pub invalid_digits: String,
+	pub invalid_algorithm: String,
+	pub invalid_ssh_key: String,
+	pub encrypted_ssh_key: String,
+	pub unreadable_file: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Import {
+	pub heading: String,
+	pub format_prompt: String,
+	pub keepass_format: String,
+	pub bitwarden_format: String,
+	pub onepassword_format: String,
+	pub csv_format: String,
+	pub portable_format: String,
+	pub path_prompt: String,
+	pub password_prompt: String,
+	pub bitwarden_password_prompt: String,
+	pub portable_password_prompt: String,
+	pub key_file_prompt: String,
+	pub read_failed: String,
+	pub summary: String,
+	pub unmapped_heading: String,
+	pub more_unmapped: String,
+	pub untitled: String,
+	pub recycle_bin_skipped: String,
+	pub field_conflict: String,
+	pub invalid_otp: String,
+	pub attachment_too_large: String,
+	pub attachment_missing: String,
+	pub unsupported_field: String,
+	pub password_history_skipped: String,
+	pub unknown_item_type: String,
+	pub passkey_skipped: String,
+	pub incomplete_expiry: String,
+	pub unsupported_ssh_key: String,
+	pub not_kdbx: String,
+	pub unsupported_kdbx_version: String,
+	pub unsupported_cipher: String,
+	pub unsupported_kdf: String,
+	pub invalid_key_file: String,
+	pub wrong_key: String,
+	pub corrupted_file: String,
+	pub not_bitwarden: String,
+	pub account_encrypted: String,
+	pub wrong_password: String,
+	pub not_onepux: String,
+	pub not_portable: String,
+	pub too_much_work: String,
+	pub unsupported_portable_version: String,
+	pub invalid_csv: String,
+	pub empty_csv: String,
+	pub no_password_column: String,
+	pub csv_rows: String,
+	pub csv_row: String,
+	pub csv_column_heading: String,
+	pub csv_target_heading: String,
+	pub csv_preview_heading: String,
+	pub csv_change_input: String,
+	pub csv_skip: String,
+	pub csv_name: String,
+	pub csv_username: String,
+	pub csv_email: String,
+	pub csv_password: String,
+	pub csv_url: String,
+	pub csv_notes: String,
+	pub csv_field: String,
+	pub conflict_heading: String,
+	pub conflict_duplicate_heading: String,
+	pub conflict_existing_heading: String,
+	pub conflict_earlier_heading: String,
+	pub conflict_imported_heading: String,
+	pub conflict_updated: String,
+	pub conflict_skip_input: String,
+	pub conflict_overwrite_input: String,
+	pub conflict_keep_both_input: String,
+	pub conflict_merge_input: String,
+	pub apply_to_all_off_input: String,
+	pub apply_to_all_on_input: String,
+	pub conflict_summary: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Export {
+	pub heading: String,
+	pub format_prompt: String,
+	pub portable_format: String,
+	pub keepass_format: String,
+	pub plain_csv_format: String,
+	pub plain_json_format: String,
+	pub emergency_kit_html_format: String,
+	pub emergency_kit_text_format: String,
+	pub filter_prompt: String,
+	pub no_matches: String,
+	pub selected_count: String,
+	pub path_prompt: String,
+	pub overwrite_question: String,
+	pub password_prompt: String,
+	pub confirm_password_prompt: String,
+	pub empty_password: String,
+	pub password_mismatch: String,
+	pub plaintext_warning: String,
+	pub plaintext_hint: String,
+	pub plaintext_question: String,
+	pub master_password_prompt: String,
+	pub wrong_master_password: String,
+	pub key_file_unreadable: String,
+	pub write_failed: String,
+	pub summary: String,
+	pub shred_question: String,
+	pub shredded: String,
+	pub shred_failed: String,
+	pub recovery_key_question: String,
+	pub kit_summary: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EmergencyKit {
+	pub title: String,
+	pub intro: String,
+	pub created: String,
+	pub vault_heading: String,
+	pub location: String,
+	pub database_file: String,
+	pub key_file: String,
+	pub format: String,
+	pub format_value: String,
+	pub kdf_heading: String,
+	pub kdf_function: String,
+	pub kdf_iterations: String,
+	pub kdf_salt: String,
+	pub kdf_key_length: String,
+	pub key_length_value: String,
+	pub master_password_heading: String,
+	pub master_password_hint: String,
+	pub recovery_key_heading: String,
+	pub recovery_key_hint: String,
+	pub instructions_heading: String,
+	pub instructions: Vec<String>,
+	pub recovery_key_instruction: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Cli {
+	pub not_found: String,
+	pub ambiguous: String,
+	pub unknown_type: String,
+	pub unknown_field: String,
+	pub empty_field: String,
+	pub invalid_assignment: String,
+	pub invalid_env_reference: String,
+	pub already_exists: String,
+	pub prompt_failed: String,
+	pub command_failed: String,
+	pub read_failed: String,
+	pub write_failed: String,
+	pub unresolved_references: String,
+	pub unterminated_reference: String,
+	pub line_label: String,
+	pub rendered_msg: String,
+	pub aborted: String,
+	pub remove_question: String,
+	pub added_msg: String,
+	pub updated_msg: String,
+	pub removed_msg: String,
+	pub hidden_value: String,
+	pub type_label: String,
+	pub totp_label: String,
+	pub master_password_prompt: String,
+	pub vault_missing: String,
+	pub wrong_master_password: String,
+	pub master_password_prompt_failed: String,
+	pub agent_locked_meanwhile: String,
+	pub agent_usage: String,
+	pub agent_invalid_timeout: String,
+	pub agent_already_running: String,
+	pub agent_started: String,
+	pub agent_start_failed: String,
+	pub agent_invalid_key: String,
+	pub agent_unlocked: String,
+	pub agent_not_running: String,
+	pub agent_locked: String,
+	pub agent_nothing_to_lock: String,
+	pub agent_vault_changed: String,
+	pub agent_stale_vault: String,
+	pub agent_damaged_vault: String,
+	pub agent_unknown_request: String,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Password {
 	one: char,
@@ -125,22 +430,36 @@ pub struct Texts {
 	pub add_account: AddAccount,
 	pub list_accounts: ListAccounts,
 	pub show_account: ShowAccount,
+	pub totp: Totp,
+	pub qr_code: QrCode,
+	pub otp_import: OtpImport,
+	pub ssh_agent: SshAgent,
+	pub import: Import,
+	pub export: Export,
+	pub emergency_kit: EmergencyKit,
+	pub cli: Cli,
+	pub attachments: Attachments,
+	pub rotation: Rotation,
 	pub auth: Auth,
 	pub wipe: Wipe,
+	pub validation: Validation,
 	pub password: Password,
 }
 
 pub fn load_texts() -> Texts {
-	let file_content = fs::read_to_string("texts_eng.toml").expect("Unable to read text files");
+	// The command line tools run from anywhere, so they fall back to the texts built into the binary
+	let file_content = fs::read_to_string("texts_eng.toml").unwrap_or_else(|_| include_str!("../texts_eng.toml").to_string());
 	from_str(&file_content).expect("Error parsing TOML")
 }
 
 static TEXTS: OnceCell<Texts> = OnceCell::new();
 
+/// Loads the texts at startup, texts which were already loaded are kept.
 pub fn init_texts() {
-	TEXTS.set(load_texts()).unwrap()
+	TEXTS.get_or_init(load_texts);
 }
 
+/// The texts are loaded when they are first needed if init_texts was not called, e.g. in tests.
 pub fn get_texts() -> &'static Texts {
-	TEXTS.get().expect("Texts not initialized!")
+	TEXTS.get_or_init(load_texts)
 }
\ No newline at end of file
@@ -0,0 +1,199 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use ring::hmac;
+
+pub const DEFAULT_DIGITS: u32 = 6;
+pub const DEFAULT_PERIOD: u64 = 30;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum TotpAlgorithm {
+	#[default]
+	Sha1,
+	Sha256,
+	Sha512,
+}
+
+impl TotpAlgorithm {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.trim().to_uppercase().replace('-', "").as_str() {
+			"SHA1" => Some(TotpAlgorithm::Sha1),
+			"SHA256" => Some(TotpAlgorithm::Sha256),
+			"SHA512" => Some(TotpAlgorithm::Sha512),
+			_ => None,
+		}
+	}
+
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			TotpAlgorithm::Sha1 => "SHA1",
+			TotpAlgorithm::Sha256 => "SHA256",
+			TotpAlgorithm::Sha512 => "SHA512",
+		}
+	}
+
+	fn get_hmac_algorithm(&self) -> hmac::Algorithm {
+		match self {
+			TotpAlgorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+			TotpAlgorithm::Sha256 => hmac::HMAC_SHA256,
+			TotpAlgorithm::Sha512 => hmac::HMAC_SHA512,
+		}
+	}
+}
+
+/// The shared secret and parameters of a time based one-time password (RFC 6238).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpConfig {
+	pub secret: String,
+	pub digits: u32,
+	pub period: u64,
+	pub algorithm: TotpAlgorithm,
+}
+
+impl TotpConfig {
+	pub fn new(secret: &str) -> Self {
+		Self {
+			secret: normalize_secret(secret),
+			digits: DEFAULT_DIGITS,
+			period: DEFAULT_PERIOD,
+			algorithm: TotpAlgorithm::default(),
+		}
+	}
+
+	/// Calculates the code for the given time, None if the secret is not valid base32 or the code can not have that many digits.
+	pub fn generate_code(&self, time: SystemTime) -> Option<String> {
+		let key = decode_base32(&self.secret)?;
+		let counter = get_unix_seconds(time) / self.period.max(1);
+		let signature = hmac::sign(&hmac::Key::new(self.algorithm.get_hmac_algorithm(), &key), &counter.to_be_bytes());
+		let hash = signature.as_ref();
+
+		let offset = (hash[hash.len() - 1] & 0x0F) as usize;
+		let binary = u32::from_be_bytes([hash[offset] & 0x7F, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+		let code = binary as u64 % 10u64.checked_pow(self.digits)?;
+		Some(format!("{:0width$}", code, width = self.digits as usize))
+	}
+
+	pub fn get_seconds_remaining(&self, time: SystemTime) -> u64 {
+		let period = self.period.max(1);
+		period - get_unix_seconds(time) % period
+	}
+}
+
+pub fn is_valid_digits(digits: u32) -> bool {
+	(6..=8).contains(&digits)
+}
+
+/// Removes spaces and padding, authenticator apps often show secrets in groups of four.
+pub fn normalize_secret(secret: &str) -> String {
+	secret.chars().filter(|c| !c.is_whitespace() && *c != '=' && *c != '-').collect::<String>().to_uppercase()
+}
+
+/// Decodes base32 as defined in RFC 4648 without padding, the encoding used for TOTP secrets.
+pub fn decode_base32(encoded: &str) -> Option<Vec<u8>> {
+	let encoded = normalize_secret(encoded);
+	if encoded.is_empty() {
+		return None;
+	}
+
+	let mut bytes = Vec::with_capacity(encoded.len() * 5 / 8);
+	let mut buffer: u32 = 0;
+	let mut bit_count = 0;
+	for c in encoded.chars() {
+		let value = match c {
+			'A'..='Z' => c as u32 - 'A' as u32,
+			'2'..='7' => c as u32 - '2' as u32 + 26,
+			_ => return None,
+		};
+		buffer = (buffer << 5) | value;
+		bit_count += 5;
+		if bit_count >= 8 {
+			bit_count -= 8;
+			bytes.push((buffer >> bit_count) as u8);
+			buffer &= (1 << bit_count) - 1;
+		}
+	}
+	Some(bytes)
+}
+
+pub fn encode_base32(bytes: &[u8]) -> String {
+	const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+	let mut encoded = String::with_capacity(bytes.len().div_ceil(5) * 8);
+	let mut buffer: u32 = 0;
+	let mut bit_count = 0;
+	for byte in bytes {
+		buffer = (buffer << 8) | *byte as u32;
+		bit_count += 8;
+		while bit_count >= 5 {
+			bit_count -= 5;
+			encoded.push(ALPHABET[((buffer >> bit_count) & 0x1F) as usize] as char);
+		}
+		buffer &= (1 << bit_count) - 1;
+	}
+	if bit_count > 0 {
+		encoded.push(ALPHABET[((buffer << (5 - bit_count)) & 0x1F) as usize] as char);
+	}
+	encoded
+}
+
+fn get_unix_seconds(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	/// The test vectors of RFC 6238, appendix B: time, SHA1, SHA256 and SHA512 code.
+	const RFC_6238_VECTORS: [(u64, &str, &str, &str); 6] = [
+		(59, "94287082", "46119246", "90693936"),
+		(1111111109, "07081804", "68084774", "25091201"),
+		(1111111111, "14050471", "67062674", "99943326"),
+		(1234567890, "89005924", "91819424", "93441116"),
+		(2000000000, "69279037", "90698825", "38618901"),
+		(20000000000, "65353130", "77737706", "47863826"),
+	];
+
+	fn create_config(key: &[u8], algorithm: TotpAlgorithm) -> TotpConfig {
+		TotpConfig { digits: 8, algorithm, ..TotpConfig::new(&encode_base32(key)) }
+	}
+
+	#[test]
+	fn generates_rfc_6238_codes() {
+		let sha1 = create_config(b"12345678901234567890", TotpAlgorithm::Sha1);
+		let sha256 = create_config(b"12345678901234567890123456789012", TotpAlgorithm::Sha256);
+		let sha512 = create_config(b"1234567890123456789012345678901234567890123456789012345678901234", TotpAlgorithm::Sha512);
+		for (seconds, sha1_code, sha256_code, sha512_code) in RFC_6238_VECTORS {
+			let time = UNIX_EPOCH + Duration::from_secs(seconds);
+			assert_eq!(sha1.generate_code(time).as_deref(), Some(sha1_code));
+			assert_eq!(sha256.generate_code(time).as_deref(), Some(sha256_code));
+			assert_eq!(sha512.generate_code(time).as_deref(), Some(sha512_code));
+		}
+	}
+
+	#[test]
+	fn keeps_leading_zeros_and_counts_down() {
+		let config = TotpConfig { digits: 6, ..create_config(b"12345678901234567890", TotpAlgorithm::Sha1) };
+		let time = UNIX_EPOCH + Duration::from_secs(1111111109);
+		assert_eq!(config.generate_code(time).as_deref(), Some("081804"));
+		assert_eq!(config.get_seconds_remaining(time), 1);
+	}
+
+	#[test]
+	fn refuses_more_digits_than_fit() {
+		let time = UNIX_EPOCH + Duration::from_secs(59);
+		let config = TotpConfig { digits: 19, ..create_config(b"12345678901234567890", TotpAlgorithm::Sha1) };
+		assert_eq!(config.generate_code(time).as_deref(), Some("0000000001094287082"));
+		assert_eq!(TotpConfig { digits: 20, ..config.clone() }.generate_code(time), None);
+		assert_eq!(TotpConfig { digits: u32::MAX, ..config }.generate_code(time), None);
+	}
+
+	#[test]
+	fn encodes_rfc_4648_base32() {
+		for (plain, encoded) in [("f", "MY"), ("fo", "MZXQ"), ("foo", "MZXW6"), ("foob", "MZXW6YQ"), ("fooba", "MZXW6YTB"), ("foobar", "MZXW6YTBOI")] {
+			assert_eq!(encode_base32(plain.as_bytes()), encoded);
+			assert_eq!(decode_base32(encoded).as_deref(), Some(plain.as_bytes()));
+		}
+		assert_eq!(decode_base32("mzxw 6ytb oi======").as_deref(), Some(b"foobar".as_slice()));
+		assert_eq!(decode_base32("MZXW1"), None);
+		assert_eq!(decode_base32(""), None);
+	}
+}
@@ -7,7 +7,13 @@ pub enum Transition{
 	ToMainMenu,
 	ToAddAccount,
 	ToShowAccount(Account),
+	ToAttachments(Account),
+	ToRotationReminder,
 	ToListAccounts,
 	ToWipeDatabase,
+	ToImportOtp,
+	ToSshAgent,
+	ToImport,
+	ToExport,
 	ToExit,
 }
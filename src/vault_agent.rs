@@ -0,0 +1,303 @@
+use std::cell::Cell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+use crate::cli::{find_account, get_field_value};
+use crate::database_context::{DatabaseContext, DatabaseManager};
+use crate::file_accesssor::{get_agent_socket_path, read_db_modified_time};
+use crate::texts::get_texts;
+#[cfg(unix)]
+use crate::file_accesssor::create_socket_directory;
+#[cfg(unix)]
+use crate::socket_util::is_peer_current_user;
+
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One JSON object per line is sent to the agent, which answers with one line as well.
+/// The key of the vault never leaves the agent.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AgentRequest {
+	Status,
+	/// Looks up a single value of an entry, the client does not get to see the rest of the vault.
+	Lookup {
+		name: String,
+		user: Option<String>,
+		field: String,
+	},
+	/// Hands out the decrypted vault with its revision, for clients which list, search or change entries.
+	Open,
+	/// Encrypts the vault a client changed and writes it to disk. The revision is the one the client opened,
+	/// a vault saved by someone else meanwhile is not overwritten.
+	Save {
+		database: String,
+		revision: u64,
+	},
+	Lock,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AgentResponse {
+	pub ok: bool,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	/// Seconds until the agent locks itself, unless it is used again.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub locks_in: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub value: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub database: Option<String>,
+	/// Counts the saves of the vault, so a client can tell whether its copy is still current.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub revision: Option<u64>,
+}
+
+impl AgentResponse {
+	fn failure(error: &str) -> Self {
+		Self { error: Some(error.to_string()), ..Default::default() }
+	}
+}
+
+/// Keeps the vault unlocked in memory. The agent runs until it is locked, its idle timeout passes
+/// or the vault on disk can no longer be decrypted with its key.
+pub struct VaultAgent {
+	db_manager: DatabaseManager,
+	db_modified_at: Option<SystemTime>,
+	revision: u64,
+	idle_timeout: Duration,
+	last_used_at: Instant,
+}
+
+impl VaultAgent {
+	pub fn new(db_manager: DatabaseManager, idle_timeout: Duration) -> Self {
+		Self {
+			db_manager,
+			db_modified_at: read_db_modified_time(),
+			revision: 0,
+			idle_timeout,
+			last_used_at: Instant::now(),
+		}
+	}
+
+	fn is_expired(&self) -> bool {
+		self.last_used_at.elapsed() >= self.idle_timeout
+	}
+
+	fn get_seconds_until_lock(&self) -> u64 {
+		self.idle_timeout.saturating_sub(self.last_used_at.elapsed()).as_secs()
+	}
+
+	/// Picks up changes other processes saved since the vault was loaded.
+	fn refresh(&mut self) -> bool {
+		let modified_at = read_db_modified_time();
+		if modified_at == self.db_modified_at {
+			return true;
+		}
+		self.db_modified_at = modified_at;
+		self.revision += 1;
+		self.db_manager.reload_from_disk()
+	}
+
+	/// Answers a request, the second value tells whether the agent has to lock afterwards.
+	fn handle_request(&mut self, request: AgentRequest) -> (AgentResponse, bool) {
+		match request {
+			AgentRequest::Status => {
+				(AgentResponse { ok: true, locks_in: Some(self.get_seconds_until_lock()), ..Default::default() }, false)
+			}
+			AgentRequest::Lock => (AgentResponse { ok: true, ..Default::default() }, true),
+			request => {
+				// Saving with the old key would also overwrite a vault whose master password was changed meanwhile
+				if !self.refresh() {
+					return (AgentResponse::failure(&get_texts().cli.agent_vault_changed), true);
+				}
+				self.last_used_at = Instant::now();
+				let response = self.handle_vault_request(request);
+				(AgentResponse { locks_in: Some(self.get_seconds_until_lock()), ..response }, false)
+			}
+		}
+	}
+
+	fn handle_vault_request(&mut self, request: AgentRequest) -> AgentResponse {
+		let db_context = match self.db_manager.get_database_context() {
+			Some(context) => context,
+			None => return AgentResponse::failure("Database not initialized"),
+		};
+		match request {
+			AgentRequest::Lookup { name, user, field } => {
+				match find_account(db_context, &name, user.as_deref()).and_then(|account| get_field_value(&account, &field)) {
+					Ok(value) => AgentResponse { ok: true, value: Some(value), ..Default::default() },
+					Err(e) => AgentResponse::failure(&e.get_message()),
+				}
+			}
+			AgentRequest::Open => AgentResponse {
+				ok: true,
+				database: Some(db_context.dump_db().unwrap()),
+				revision: Some(self.revision),
+				..Default::default()
+			},
+			AgentRequest::Save { database, revision } => {
+				if revision != self.revision {
+					return AgentResponse::failure(&get_texts().cli.agent_stale_vault);
+				}
+				if !self.db_manager.restore_from_dump(&database) {
+					return AgentResponse::failure(&get_texts().cli.agent_damaged_vault);
+				}
+				self.db_manager.safe_database();
+				self.db_modified_at = read_db_modified_time();
+				self.revision += 1;
+				AgentResponse { ok: true, revision: Some(self.revision), ..Default::default() }
+			}
+			AgentRequest::Status | AgentRequest::Lock => AgentResponse::failure(&get_texts().cli.agent_unknown_request),
+		}
+	}
+
+	/// Serves requests on the socket until the agent locks. The socket is removed afterwards.
+	#[cfg(unix)]
+	pub fn run(mut self, socket_path: &Path) -> io::Result<()> {
+		use std::os::unix::net::UnixListener;
+
+		// Only the user may enter the directory, so nobody else can connect to the socket in it
+		create_socket_directory()?;
+		// A socket left behind by a crashed agent would make bind fail, a running agent keeps its socket
+		if socket_path.exists() {
+			if std::os::unix::net::UnixStream::connect(socket_path).is_ok() {
+				return Err(io::Error::new(io::ErrorKind::AddrInUse, get_texts().cli.agent_already_running.clone()));
+			}
+			std::fs::remove_file(socket_path)?;
+		}
+		let listener = UnixListener::bind(socket_path)?;
+		listener.set_nonblocking(true)?;
+
+		while !self.is_expired() {
+			match listener.accept() {
+				Ok((stream, _)) => {
+					if self.serve_client(stream) {
+						break;
+					}
+				}
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(100)),
+				Err(_) => break,
+			}
+		}
+		std::fs::remove_file(socket_path)
+	}
+
+	#[cfg(not(unix))]
+	pub fn run(self, _socket_path: &Path) -> io::Result<()> {
+		Err(io::Error::new(io::ErrorKind::Unsupported, "The agent requires Unix sockets"))
+	}
+
+	/// Clients are served one at a time, each request is answered right away.
+	#[cfg(unix)]
+	fn serve_client(&mut self, stream: std::os::unix::net::UnixStream) -> bool {
+		if !is_peer_current_user(&stream) {
+			return false;
+		}
+		if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err() {
+			return false;
+		}
+		let mut writer = match stream.try_clone() {
+			Ok(writer) => writer,
+			Err(_) => return false,
+		};
+		let mut line = String::new();
+		if BufReader::new(stream).read_line(&mut line).is_err() {
+			return false;
+		}
+
+		let (response, lock) = match serde_json::from_str::<AgentRequest>(&line) {
+			Ok(request) => self.handle_request(request),
+			Err(_) => (AgentResponse::failure(&get_texts().cli.agent_unknown_request), false),
+		};
+		let mut response = serde_json::to_string(&response).expect("Could not serialize agent response");
+		response.push('\n');
+		let _ = writer.write_all(response.as_bytes());
+		lock
+	}
+}
+
+/// Sends a request to the running agent, None if there is no agent.
+#[cfg(unix)]
+pub fn send_agent_request(request: &AgentRequest) -> Option<AgentResponse> {
+	let stream = std::os::unix::net::UnixStream::connect(get_agent_socket_path()).ok()?;
+	stream.set_read_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+	let mut writer = stream.try_clone().ok()?;
+	let mut message = serde_json::to_string(request).ok()?;
+	message.push('\n');
+	writer.write_all(message.as_bytes()).ok()?;
+
+	let mut line = String::new();
+	BufReader::new(stream).read_line(&mut line).ok()?;
+	serde_json::from_str(&line).ok()
+}
+
+#[cfg(not(unix))]
+pub fn send_agent_request(_request: &AgentRequest) -> Option<AgentResponse> {
+	None
+}
+
+/// Takes the unlocked vault from the running agent, so neither the password nor the key derivation is needed.
+/// Changes are saved through the agent, as only the agent has the key.
+pub fn attach_to_agent() -> Option<DatabaseManager> {
+	let response = send_agent_request(&AgentRequest::Open).filter(|response| response.ok)?;
+	let revision = Cell::new(response.revision?);
+	let mut db_manager = DatabaseManager::new();
+	db_manager.unlock_from_dump(&response.database?, Box::new(move |context| save_through_agent(context, &revision)));
+	Some(db_manager)
+}
+
+/// The revision is advanced after each save, so the client can save again.
+fn save_through_agent(context: &DatabaseContext, revision: &Cell<u64>) -> Result<(), String> {
+	let request = AgentRequest::Save { database: context.dump_db().expect("Failed to dump db"), revision: revision.get() };
+	match send_agent_request(&request) {
+		Some(response) if response.ok => {
+			revision.set(response.revision.unwrap_or_default());
+			Ok(())
+		}
+		Some(response) => Err(response.error.unwrap_or_default()),
+		None => Err(get_texts().cli.agent_locked_meanwhile.clone()),
+	}
+}
+
+/// Looks up a single value through the running agent, None if there is no agent.
+/// An error is the message of the agent, e.g. when there is no such entry.
+pub fn lookup_through_agent(name: &str, user: Option<&str>, field: &str) -> Option<Result<String, String>> {
+	let request = AgentRequest::Lookup { name: name.to_string(), user: user.map(str::to_string), field: field.to_string() };
+	let response = send_agent_request(&request)?;
+	Some(match response.value {
+		Some(value) if response.ok => Ok(value),
+		_ => Err(response.error.unwrap_or_default()),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn open_agent() -> VaultAgent {
+		let mut db_manager = DatabaseManager::new();
+		db_manager.unlock_from_dump(&DatabaseContext::new().unwrap().dump_db().unwrap(), Box::new(|_| Ok(())));
+		VaultAgent::new(db_manager, DEFAULT_IDLE_TIMEOUT)
+	}
+
+	#[test]
+	fn refuses_save_of_outdated_vault() {
+		let mut agent = open_agent();
+		let opened = agent.handle_vault_request(AgentRequest::Open);
+		let (database, revision) = (opened.database.unwrap(), opened.revision.unwrap());
+
+		let saved = agent.handle_vault_request(AgentRequest::Save { database: database.clone(), revision });
+		assert!(saved.ok);
+		assert_eq!(saved.revision, Some(revision + 1));
+
+		// A second client which opened the vault before the save must not overwrite it
+		let stale = agent.handle_vault_request(AgentRequest::Save { database: database.clone(), revision });
+		assert!(!stale.ok);
+		assert_eq!(stale.error, Some(get_texts().cli.agent_stale_vault.clone()));
+		assert!(agent.handle_vault_request(AgentRequest::Save { database, revision: revision + 1 }).ok);
+	}
+}
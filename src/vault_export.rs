@@ -0,0 +1,48 @@
+use crate::database_context::{Account, DatabaseContext, GROUP_FIELD_KEY};
+use crate::emergency_kit::KitFormat;
+use crate::texts::get_texts;
+
+/// The formats the vault can be written to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+	Portable,
+	KeePass,
+	/// Unencrypted, only written after the master password was entered again.
+	PlainCsv,
+	PlainJson,
+	/// Not an export of entries, but a document to print with what is needed to recover the vault.
+	EmergencyKit(KitFormat),
+}
+
+impl ExportFormat {
+	pub const ALL: [ExportFormat; 6] = [ExportFormat::Portable, ExportFormat::KeePass, ExportFormat::PlainCsv, ExportFormat::PlainJson,
+		ExportFormat::EmergencyKit(KitFormat::Html), ExportFormat::EmergencyKit(KitFormat::Text)];
+
+	pub fn get_name(&self) -> &'static str {
+		let texts = &get_texts().export;
+		match self {
+			ExportFormat::Portable => &texts.portable_format,
+			ExportFormat::KeePass => &texts.keepass_format,
+			ExportFormat::PlainCsv => &texts.plain_csv_format,
+			ExportFormat::PlainJson => &texts.plain_json_format,
+			ExportFormat::EmergencyKit(KitFormat::Html) => &texts.emergency_kit_html_format,
+			ExportFormat::EmergencyKit(KitFormat::Text) => &texts.emergency_kit_text_format,
+		}
+	}
+
+	pub fn is_plaintext(&self) -> bool {
+		matches!(self, ExportFormat::PlainCsv | ExportFormat::PlainJson)
+	}
+}
+
+/// Selects the entries whose name or group contains the filter, all entries for an empty filter.
+pub fn filter_accounts(db_context: &DatabaseContext, filter: &str) -> Vec<Account> {
+	let filter = filter.trim().to_lowercase();
+	db_context.list_all_accounts().unwrap().into_iter()
+		.filter(|account| {
+			filter.is_empty()
+				|| account.account_name.to_lowercase().contains(&filter)
+				|| account.get_field(GROUP_FIELD_KEY).is_some_and(|group| group.to_lowercase().contains(&filter))
+		})
+		.collect()
+}
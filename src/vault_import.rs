@@ -0,0 +1,262 @@
+use crate::database_context::{Account, DatabaseContext, EntryField};
+use crate::date_util::format_date;
+use crate::entry_view::EntryView;
+use crate::texts::get_texts;
+
+/// The exports of other password managers which can be imported.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+	KeePass,
+	Bitwarden,
+	OnePassword,
+	Csv,
+	Portable,
+}
+
+impl ImportFormat {
+	pub const ALL: [ImportFormat; 5] = [ImportFormat::Portable, ImportFormat::KeePass, ImportFormat::Bitwarden, ImportFormat::OnePassword, ImportFormat::Csv];
+
+	pub fn get_name(&self) -> &'static str {
+		let texts = &get_texts().import;
+		match self {
+			ImportFormat::KeePass => &texts.keepass_format,
+			ImportFormat::Bitwarden => &texts.bitwarden_format,
+			ImportFormat::OnePassword => &texts.onepassword_format,
+			ImportFormat::Csv => &texts.csv_format,
+			ImportFormat::Portable => &texts.portable_format,
+		}
+	}
+}
+
+/// An entry ready to be inserted, with the files to attach to it.
+pub struct ImportedEntry {
+	pub account: Account,
+	pub attachments: Vec<(String, Vec<u8>)>,
+}
+
+/// The entries read from an export and a description of everything which could not be mapped onto them.
+#[derive(Default)]
+pub struct ImportResult {
+	pub entries: Vec<ImportedEntry>,
+	pub unmapped: Vec<String>,
+}
+
+/// How an imported entry is written which has the same name and username as an entry of the vault
+/// or as an earlier entry of the same import.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConflictResolution {
+	Skip,
+	Overwrite,
+	KeepBoth,
+	Merge,
+}
+
+pub struct ImportConflict {
+	pub entry_idx: usize,
+	pub existing: Account,
+	/// Set if the entry duplicates an earlier entry of the import instead of one of the vault.
+	pub earlier_idx: Option<usize>,
+	pub resolution: ConflictResolution,
+}
+
+/// A row of the side by side comparison of a conflict.
+pub struct FieldDiff {
+	pub label: String,
+	pub existing: String,
+	pub imported: String,
+	pub hidden: bool,
+}
+
+impl FieldDiff {
+	pub fn is_different(&self) -> bool {
+		self.existing != self.imported
+	}
+}
+
+/// Finds the imported entries which would duplicate an entry of the vault or an earlier imported entry, nothing is written yet.
+pub fn find_conflicts(db_context: &DatabaseContext, entries: &[ImportedEntry]) -> Vec<ImportConflict> {
+	let mut conflicts = Vec::new();
+	for (entry_idx, entry) in entries.iter().enumerate() {
+		let account = &entry.account;
+		if let Some(existing) = db_context.find_accounts_by_identity(&account.account_name, account.get_username()).unwrap().into_iter().next() {
+			conflicts.push(ImportConflict { entry_idx, existing, earlier_idx: None, resolution: ConflictResolution::Skip });
+		} else if let Some(earlier_idx) = entries[..entry_idx].iter().position(|earlier| has_same_identity(&earlier.account, account)) {
+			let existing = entries[earlier_idx].account.clone();
+			conflicts.push(ImportConflict { entry_idx, existing, earlier_idx: Some(earlier_idx), resolution: ConflictResolution::Skip });
+		}
+	}
+	conflicts
+}
+
+/// Compares like the vault does: the name ignoring ASCII case as SQLite does, the username ignoring any case.
+fn has_same_identity(first: &Account, second: &Account) -> bool {
+	first.account_name.trim().eq_ignore_ascii_case(second.account_name.trim())
+		&& first.get_username().trim().to_lowercase() == second.get_username().trim().to_lowercase()
+}
+
+/// Compares the fields of both entries in the order of their templates, fields empty on both sides are left out.
+pub fn get_field_diff(existing: &Account, imported: &Account) -> Vec<FieldDiff> {
+	let texts = &get_texts().import;
+	let mut diff: Vec<FieldDiff> = Vec::new();
+	for account in [existing, imported] {
+		for row in EntryView::new(account).get_rows() {
+			if diff.iter().any(|field| field.label == row.label) {
+				continue;
+			}
+			let existing_value = existing.get_field(&row.key).unwrap_or_default().to_string();
+			let imported_value = imported.get_field(&row.key).unwrap_or_default().to_string();
+			if !existing_value.is_empty() || !imported_value.is_empty() {
+				diff.push(FieldDiff { label: row.label.clone(), existing: existing_value, imported: imported_value, hidden: row.is_hidden() });
+			}
+		}
+	}
+	let totp_secret = |account: &Account| account.totp.as_ref().map(|totp| totp.secret.clone()).unwrap_or_default();
+	if existing.totp.is_some() || imported.totp.is_some() {
+		diff.push(FieldDiff { label: get_texts().totp.code_label.clone(), existing: totp_secret(existing), imported: totp_secret(imported), hidden: true });
+	}
+	diff.push(FieldDiff {
+		label: texts.conflict_updated.clone(),
+		existing: format_date(existing.updated_at),
+		imported: format_date(imported.updated_at),
+		hidden: false,
+	});
+	diff
+}
+
+/// Combines both entries into the existing one. Fields only one of them has are kept,
+/// for fields both have the values of the more recently changed entry win.
+pub fn merge_accounts(existing: &Account, imported: &Account) -> Account {
+	let (newer, older) = if imported.updated_at > existing.updated_at { (imported, existing) } else { (existing, imported) };
+	let mut merged = existing.clone();
+	let pick = |newer: &str, older: &str| if newer.is_empty() { older.to_string() } else { newer.to_string() };
+	merged.password = pick(&newer.password, &older.password);
+	merged.email = newer.email.clone().or_else(|| older.email.clone());
+	merged.totp = newer.totp.clone().or_else(|| older.totp.clone());
+	merged.expires_at = newer.expires_at.or(older.expires_at);
+	merged.fields = Vec::new();
+	for field in newer.fields.iter().chain(older.fields.iter()) {
+		if !merged.fields.iter().any(|merged_field| merged_field.key == field.key) {
+			let value = pick(newer.get_field(&field.key).unwrap_or_default(), older.get_field(&field.key).unwrap_or_default());
+			merged.fields.push(EntryField { key: field.key.clone(), value, hidden: field.hidden });
+		}
+	}
+	merged
+}
+
+/// Adds the entries and their attachments to the vault as the conflicts were resolved,
+/// the caller saves the database afterwards. Entries without a conflict are added.
+pub fn insert_entries(db_context: &DatabaseContext, entries: &[ImportedEntry], conflicts: &[ImportConflict]) {
+	let mut account_ids: Vec<Option<i32>> = Vec::with_capacity(entries.len());
+	for (entry_idx, entry) in entries.iter().enumerate() {
+		let conflict = conflicts.iter().find(|conflict| conflict.entry_idx == entry_idx);
+		let existing = match conflict {
+			// The earlier entry has no conflict with the vault, so it was written. It is compared as it is now,
+			// another duplicate may have been merged into it meanwhile
+			Some(ImportConflict { earlier_idx: Some(earlier_idx), .. }) => account_ids[*earlier_idx].and_then(|id| db_context.get_account_by_id(id).unwrap()),
+			Some(conflict) => Some(conflict.existing.clone()),
+			None => None,
+		};
+		let (account_id, replace_attachments) = match (conflict.map(|conflict| conflict.resolution), &existing) {
+			(Some(ConflictResolution::Skip), _) => {
+				account_ids.push(None);
+				continue;
+			}
+			(Some(ConflictResolution::Overwrite), Some(existing)) => {
+				let mut account = entry.account.clone();
+				account.id = existing.id;
+				db_context.update_account(&account);
+				(account.id, true)
+			}
+			(Some(ConflictResolution::Merge), Some(existing)) => {
+				db_context.update_account(&merge_accounts(existing, &entry.account));
+				(existing.id, false)
+			}
+			_ => (db_context.add_account(&entry.account).unwrap(), false),
+		};
+		account_ids.push(Some(account_id));
+
+		let existing_attachments = if existing.is_some() { db_context.list_attachments(account_id).unwrap() } else { Vec::new() };
+		for (file_name, data) in &entry.attachments {
+			let existing = existing_attachments.iter().find(|attachment| &attachment.file_name == file_name);
+			match existing {
+				Some(attachment) if replace_attachments => db_context.remove_attachment(attachment.id).unwrap(),
+				Some(_) => continue,
+				None => {}
+			}
+			db_context.add_attachment(account_id, file_name, data).unwrap();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::database_context::{NOTES_FIELD_KEY, USERNAME_FIELD_KEY};
+
+	fn entry(name: &str, username: &str, password: &str) -> ImportedEntry {
+		let mut account = Account::new("login");
+		account.account_name = name.to_string();
+		account.password = password.to_string();
+		account.set_field(USERNAME_FIELD_KEY, username.to_string());
+		ImportedEntry { account, attachments: Vec::new() }
+	}
+
+	fn resolve(conflicts: &mut [ImportConflict], resolutions: &[ConflictResolution]) {
+		for (conflict, resolution) in conflicts.iter_mut().zip(resolutions) {
+			conflict.resolution = *resolution;
+		}
+	}
+
+	#[test]
+	fn finds_duplicates_within_the_import() {
+		let db_context = DatabaseContext::new().unwrap();
+		db_context.add_account(&entry("Mail", "bob", "vault").account).unwrap();
+		let entries = [entry("Mail", "bob", "a"), entry("Bank", "alice", "b"), entry("bank ", "ALICE", "c"), entry("Bank", "carol", "d"), entry("mail", "Bob", "e")];
+
+		let conflicts = find_conflicts(&db_context, &entries);
+		let found: Vec<(usize, Option<usize>)> = conflicts.iter().map(|conflict| (conflict.entry_idx, conflict.earlier_idx)).collect();
+		assert_eq!(found, vec![(0, None), (2, Some(1)), (4, None)]);
+		assert_eq!(conflicts[0].existing.password, "vault");
+		assert_eq!(conflicts[1].existing.password, "b");
+	}
+
+	#[test]
+	fn resolves_duplicates_against_the_written_entry() {
+		let db_context = DatabaseContext::new().unwrap();
+		let mut first = entry("Bank", "alice", "old");
+		first.account.set_field(NOTES_FIELD_KEY, "first".to_string());
+		let mut second = entry("Bank", "alice", "new");
+		second.account.updated_at = first.account.updated_at + std::time::Duration::from_secs(60);
+		let entries = [first, second, entry("Shop", "carol", "a"), entry("Shop", "carol", "b"), entry("Shop", "carol", "c")];
+
+		let mut conflicts = find_conflicts(&db_context, &entries);
+		resolve(&mut conflicts, &[ConflictResolution::Merge, ConflictResolution::KeepBoth, ConflictResolution::Overwrite]);
+		insert_entries(&db_context, &entries, &conflicts);
+
+		let bank = db_context.find_accounts_by_name("Bank").unwrap();
+		assert_eq!(bank.len(), 1);
+		assert_eq!(bank[0].password, "new");
+		assert_eq!(bank[0].get_field(NOTES_FIELD_KEY), Some("first"));
+		let mut shop: Vec<String> = db_context.find_accounts_by_name("Shop").unwrap().into_iter().map(|account| account.password).collect();
+		shop.sort();
+		assert_eq!(shop, vec!["b", "c"]);
+	}
+
+	#[test]
+	fn skips_duplicates_by_default() {
+		let db_context = DatabaseContext::new().unwrap();
+		db_context.add_account(&entry("Mail", "bob", "vault").account).unwrap();
+		let entries = [entry("Mail", "bob", "a"), entry("Bank", "alice", "b"), entry("Bank", "alice", "c")];
+
+		let mut conflicts = find_conflicts(&db_context, &entries);
+		resolve(&mut conflicts, &[ConflictResolution::Skip]);
+		insert_entries(&db_context, &entries, &conflicts);
+
+		let bank = db_context.find_accounts_by_name("Bank").unwrap();
+		assert_eq!(bank.len(), 1);
+		assert_eq!(bank[0].password, "b");
+		let mail = db_context.find_accounts_by_name("Mail").unwrap();
+		assert_eq!(mail.len(), 1);
+		assert_eq!(mail[0].password, "vault");
+	}
+}
@@ -0,0 +1,64 @@
+use crate::database_context::DatabaseManager;
+use crate::encryption_controller::PasswordEncryption;
+use crate::file_accesssor::{does_directory_and_files_exist, read_password_from_disk};
+use crate::texts::get_texts;
+use crate::vault_agent::attach_to_agent;
+
+/// Reasons why a command line tool could not open the vault.
+#[derive(Debug, PartialEq)]
+pub enum UnlockError {
+	VaultMissing,
+	WrongPassword,
+	PromptFailed,
+	AgentLocked,
+}
+
+impl UnlockError {
+	pub fn get_message(&self) -> String {
+		let texts = &get_texts().cli;
+		match self {
+			UnlockError::VaultMissing => texts.vault_missing.clone(),
+			UnlockError::WrongPassword => texts.wrong_master_password.clone(),
+			UnlockError::PromptFailed => texts.master_password_prompt_failed.clone(),
+			UnlockError::AgentLocked => texts.agent_locked_meanwhile.clone(),
+		}
+	}
+}
+
+/// Checks the master password and returns the key the vault is encrypted with.
+pub fn derive_vault_key(password: &str) -> Result<[u8; 32], UnlockError> {
+	if !does_directory_and_files_exist() {
+		return Err(UnlockError::VaultMissing);
+	}
+	let pwd_string = read_password_from_disk().ok_or(UnlockError::VaultMissing)?;
+	let master_password = PasswordEncryption::create_from_string(pwd_string).map_err(|_| UnlockError::VaultMissing)?;
+	if !master_password.verify_string(password.trim()) {
+		return Err(UnlockError::WrongPassword);
+	}
+	Ok(master_password.get_encrypted_string())
+}
+
+/// Opens the vault on disk with the given master password.
+pub fn unlock_vault(password: &str) -> Result<DatabaseManager, UnlockError> {
+	let passkey = derive_vault_key(password)?;
+	let mut db_manager = DatabaseManager::new();
+	db_manager.load_database_from_disk();
+	db_manager.unlock(&passkey);
+	Ok(db_manager)
+}
+
+pub fn prompt_master_password() -> Result<String, UnlockError> {
+	if !does_directory_and_files_exist() {
+		return Err(UnlockError::VaultMissing);
+	}
+	rpassword::prompt_password(&get_texts().cli.master_password_prompt).map_err(|_| UnlockError::PromptFailed)
+}
+
+/// Takes the vault from a running agent, otherwise asks for the master password on the terminal,
+/// so stdin and stdout stay free for the protocol of the calling tool.
+pub fn prompt_and_unlock_vault() -> Result<DatabaseManager, UnlockError> {
+	if let Some(db_manager) = attach_to_agent() {
+		return Ok(db_manager);
+	}
+	unlock_vault(&prompt_master_password()?)
+}
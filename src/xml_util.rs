@@ -0,0 +1,28 @@
+/// Escapes text for XML and HTML documents and drops control characters, which XML 1.0 does not allow.
+pub fn escape_xml(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&apos;"),
+			'\t' | '\n' | '\r' => escaped.push(c),
+			c if c < ' ' => {}
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn escapes_markup_and_drops_control_characters() {
+		assert_eq!(escape_xml("<a href=\"x\">Tom & Jerry's</a>"), "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;");
+		assert_eq!(escape_xml("line\tone\r\nline\u{0}two\u{1b}"), "line\tone\r\nlinetwo");
+	}
+}
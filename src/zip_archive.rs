@@ -0,0 +1,176 @@
+use std::io::Read;
+use flate2::read::DeflateDecoder;
+use flate2::Crc;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const CENTRAL_DIRECTORY_HEADER_SIZE: usize = 46;
+const LOCAL_FILE_HEADER_SIZE: usize = 30;
+/// The comment at the end of the archive can be at most this long.
+const MAX_COMMENT_SIZE: usize = 0xFFFF;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+const FLAG_ENCRYPTED: u16 = 1;
+
+/// A file of the archive, it is only decompressed when read.
+pub struct ZipEntry {
+	pub name: String,
+	method: u16,
+	flags: u16,
+	crc: u32,
+	compressed_size: usize,
+	size: usize,
+	header_offset: usize,
+}
+
+impl ZipEntry {
+	pub fn get_size(&self) -> usize {
+		self.size
+	}
+}
+
+/// A ZIP archive held in memory. Only what exports of other applications use is supported:
+/// stored and deflated files without encryption and without the 64 bit extensions.
+pub struct ZipArchive<'a> {
+	data: &'a [u8],
+	entries: Vec<ZipEntry>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+	Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+	Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+impl<'a> ZipArchive<'a> {
+	/// Reads the central directory at the end of the archive, None if the data is no ZIP archive.
+	pub fn read(data: &'a [u8]) -> Option<Self> {
+		let search_start = data.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE + MAX_COMMENT_SIZE);
+		let end = (search_start..=data.len().checked_sub(END_OF_CENTRAL_DIRECTORY_SIZE)?).rev()
+			.find(|offset| read_u32(data, *offset) == Some(END_OF_CENTRAL_DIRECTORY))?;
+		let entry_count = read_u16(data, end + 10)? as usize;
+		let mut offset = read_u32(data, end + 16)? as usize;
+
+		let mut entries = Vec::with_capacity(entry_count);
+		for _ in 0..entry_count {
+			if read_u32(data, offset)? != CENTRAL_DIRECTORY_HEADER {
+				return None;
+			}
+			let name_length = read_u16(data, offset + 28)? as usize;
+			let extra_length = read_u16(data, offset + 30)? as usize;
+			let comment_length = read_u16(data, offset + 32)? as usize;
+			let name_start = offset + CENTRAL_DIRECTORY_HEADER_SIZE;
+			entries.push(ZipEntry {
+				name: String::from_utf8_lossy(data.get(name_start..name_start + name_length)?).to_string(),
+				flags: read_u16(data, offset + 8)?,
+				method: read_u16(data, offset + 10)?,
+				crc: read_u32(data, offset + 16)?,
+				compressed_size: read_u32(data, offset + 20)? as usize,
+				size: read_u32(data, offset + 24)? as usize,
+				header_offset: read_u32(data, offset + 42)? as usize,
+			});
+			offset = name_start + name_length + extra_length + comment_length;
+		}
+		Some(Self { data, entries })
+	}
+
+	pub fn get_entries(&self) -> &[ZipEntry] {
+		&self.entries
+	}
+
+	pub fn find_entry(&self, name: &str) -> Option<&ZipEntry> {
+		self.entries.iter().find(|entry| entry.name == name)
+	}
+
+	/// Decompresses the file, None if it is encrypted, compressed in an unsupported way or damaged.
+	pub fn read_entry(&self, entry: &ZipEntry) -> Option<Vec<u8>> {
+		if entry.flags & FLAG_ENCRYPTED != 0 || read_u32(self.data, entry.header_offset)? != LOCAL_FILE_HEADER {
+			return None;
+		}
+		// The local header repeats name and extra field, with lengths which may differ from the central directory
+		let name_length = read_u16(self.data, entry.header_offset + 26)? as usize;
+		let extra_length = read_u16(self.data, entry.header_offset + 28)? as usize;
+		let start = entry.header_offset + LOCAL_FILE_HEADER_SIZE + name_length + extra_length;
+		let compressed = self.data.get(start..start.checked_add(entry.compressed_size)?)?;
+
+		let content = match entry.method {
+			METHOD_STORED => compressed.to_vec(),
+			METHOD_DEFLATED => {
+				let mut content = Vec::new();
+				// Reading one byte more than announced tells a wrong size apart from the real one
+				DeflateDecoder::new(compressed).take(entry.size as u64 + 1).read_to_end(&mut content).ok()?;
+				content
+			}
+			_ => return None,
+		};
+		let mut crc = Crc::new();
+		crc.update(&content);
+		if content.len() != entry.size || crc.sum() != entry.crc {
+			return None;
+		}
+		Some(content)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use base64::engine::general_purpose::STANDARD as BASE64;
+	use base64::Engine;
+
+	/// Written with the Python zipfile module: "hello.txt" stored, "files/data.json" deflated,
+	/// an empty "empty" and a comment at the end of the archive.
+	const ARCHIVE: &str = "UEsDBBQAAAAAAAAAIQCGphA2BQAAAAUAAAAJAAAAaGVsbG8udHh0aGVsbG9QSwMEFAAAAAgAnSJTXQ20vzUXAAAAQAEAAA8AAABmaWxlcy9kYXRhLmpzb26rVspOrVSyUlAqS8wpTVWqrR7lk8QHAFBLAwQUAAAAAAAAACEAAAAAAAAAAAAAAAAABQAAAGVtcHR5UEsBAhQDFAAAAAAAAAAhAIamEDYFAAAABQAAAAkAAAAAAAAAAAAAAIABAAAAAGhlbGxvLnR4dFBLAQIUAxQAAAAIAJ0iU10NtL81FwAAAEABAAAPAAAAAAAAAAAAAACAASwAAABmaWxlcy9kYXRhLmpzb25QSwECFAMUAAAAAAAAACEAAAAAAAAAAAAAAAAABQAAAAAAAAAAAAAAgAFwAAAAZW1wdHlQSwUGAAAAAAMAAwCnAAAAkwAAABEAd3JpdHRlbiBieSBhIHRlc3Q=";
+
+	fn read_archive() -> Vec<u8> {
+		BASE64.decode(ARCHIVE).unwrap()
+	}
+
+	#[test]
+	fn reads_stored_and_deflated_entries() {
+		let data = read_archive();
+		let archive = ZipArchive::read(&data).unwrap();
+		let names: Vec<&str> = archive.get_entries().iter().map(|entry| entry.name.as_str()).collect();
+		assert_eq!(names, vec!["hello.txt", "files/data.json", "empty"]);
+
+		let json = archive.find_entry("files/data.json").unwrap();
+		assert_eq!(json.get_size(), 320);
+		assert_eq!(archive.read_entry(json).unwrap(), "{\"key\": \"value\"}".repeat(20).into_bytes());
+		assert_eq!(archive.read_entry(archive.find_entry("hello.txt").unwrap()).unwrap(), b"hello");
+		assert_eq!(archive.read_entry(archive.find_entry("empty").unwrap()).unwrap(), b"");
+		assert!(archive.find_entry("missing").is_none());
+	}
+
+	#[test]
+	fn rejects_damaged_content() {
+		let mut data = read_archive();
+		// The content is followed by the next local header, the name "hello.txt" is not
+		let content = data.windows(7).position(|window| window == b"helloPK").unwrap();
+		data[content] = b'j';
+		let archive = ZipArchive::read(&data).unwrap();
+		assert!(archive.read_entry(archive.find_entry("hello.txt").unwrap()).is_none());
+		assert!(archive.read_entry(archive.find_entry("files/data.json").unwrap()).is_some());
+	}
+
+	#[test]
+	fn rejects_encrypted_entries() {
+		let mut data = read_archive();
+		let central_directory = data.windows(4).position(|window| window == CENTRAL_DIRECTORY_HEADER.to_le_bytes()).unwrap();
+		data[central_directory + 8] |= FLAG_ENCRYPTED as u8;
+		let archive = ZipArchive::read(&data).unwrap();
+		assert!(archive.read_entry(archive.find_entry("hello.txt").unwrap()).is_none());
+	}
+
+	#[test]
+	fn rejects_other_data() {
+		let data = read_archive();
+		assert!(ZipArchive::read(&data[..data.len() - 40]).is_none());
+		assert!(ZipArchive::read(b"not an archive").is_none());
+		assert!(ZipArchive::read(b"").is_none());
+	}
+}